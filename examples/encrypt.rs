@@ -32,13 +32,28 @@ async fn main() -> Result<()> {
         Err(err) => println!("Error publishing message: {}", err),
     }
 
-    // Retrieve the message from history using another client which doesn't
-    // have the cipher params.
+    // Retrieving the message from history using another client which doesn't
+    // have the cipher params fails, since the data can't be decrypted.
     let client = ably::Rest::new(&key)?;
     let channel = client.channels().name("rust-example").get();
     let page = channel.history().pages().next().await.unwrap()?;
-    let msg = page.items().await?.pop().expect("Expected a message");
+    match page.items().await {
+        Ok(_) => println!("Unexpectedly decrypted the message"),
+        Err(err) => println!("Error decoding message: {}", err),
+    }
+
+    // Retrieve the message with items_with_errors instead, which returns the
+    // message still encrypted along with the error from decoding it.
+    let page = channel.history().send().await?;
+    let (msg, err) = page
+        .items_with_errors()
+        .await?
+        .pop()
+        .expect("Expected a message");
     println!("Retrieved message from history: data = {:?}", msg.data);
+    if let Some(err) = err {
+        println!("Message left undecoded: {}", err);
+    }
 
     // The data should be binary, and decrypting it should return the string we
     // published.
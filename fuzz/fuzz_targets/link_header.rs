@@ -0,0 +1,8 @@
+#![no_main]
+
+use ably::http::Link;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|header: &str| {
+    let _ = Link::parse(header);
+});
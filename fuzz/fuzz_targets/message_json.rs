@@ -0,0 +1,13 @@
+#![no_main]
+
+use ably::crypto::CipherParams;
+use ably::rest::Format;
+use ably::testing;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|body: &[u8]| {
+    let _ = testing::decode_message(body, Format::JSON, None);
+
+    let cipher = CipherParams::try_from(vec![0; 16]).unwrap();
+    let _ = testing::decode_message(body, Format::JSON, Some(cipher));
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use ably::auth::RequestOrDetails;
+use libfuzzer_sys::fuzz_target;
+
+// The body of an authUrl response, which is either a TokenRequest or
+// TokenDetails.
+fuzz_target!(|body: &[u8]| {
+    let _ = serde_json::from_slice::<RequestOrDetails>(body);
+});
@@ -1,4 +1,5 @@
 use std::convert::TryFrom;
+use std::fmt;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
@@ -11,16 +12,36 @@ use serde::{Deserialize, Serialize};
 use sha2::Sha256;
 
 use crate::error::{Error, ErrorCode};
+use crate::log::{self, LogLevel};
 use crate::rest::RestInner;
-use crate::{http, rest, Result};
+use crate::{http, json, rest, Result};
 
 /// The maximum length of a valid token. Tokens with a length longer than this
 /// are rejected with a ErrorCode::ErrorFromClientTokenCallback error code.
 const MAX_TOKEN_LENGTH: usize = 128 * 1024;
 
-mod duration {
-    use std::fmt;
+/// The maximum number of targets which can be included in a single request to
+/// the revokeTokens endpoint.
+const MAX_REVOCATION_TARGETS: usize = 100;
+
+/// How long a TokenRequest can be used for after it is signed, in minutes.
+const TOKEN_REQUEST_MAX_AGE_MINS: i64 = 60;
+
+/// How often a token refresh task checks whether the client's token can be
+/// renewed, and the minimum time between the renewals it makes.
+const TOKEN_REFRESH_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// How long a token refresh task waits before retrying a failed renewal.
+const TOKEN_REFRESH_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(10);
 
+/// The number of TokenParams in a call to Key::sign_batch above which they
+/// are signed in parallel.
+const SIGN_BATCH_PARALLEL_THRESHOLD: usize = 64;
+
+/// The clientId of a token which permits the client to use any clientId.
+const WILDCARD_CLIENT_ID: &str = "*";
+
+mod duration {
     use super::*;
     use serde::{de, Deserializer, Serializer};
 
@@ -41,6 +62,17 @@ mod duration {
         {
             Ok(Duration::milliseconds(value))
         }
+
+        /// Deserialize a timestamp in milliseconds since the epoch, which
+        /// is buffered as a u64 when deserializing an untagged enum
+        fn visit_u64<E>(self, value: u64) -> std::result::Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            i64::try_from(value)
+                .map(Duration::milliseconds)
+                .map_err(|_| E::custom("duration out of range"))
+        }
     }
 
     pub fn deserialize<'de, D>(d: D) -> std::result::Result<Duration, D::Error>
@@ -80,12 +112,114 @@ impl std::fmt::Debug for Credential {
     }
 }
 
+/// How an individual request is authenticated, set with
+/// RequestBuilder::auth_mode.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AuthMode {
+    /// Use basic auth if the client has an API key and hasn't switched to
+    /// token auth by calling Auth::authorize, and token auth otherwise.
+    #[default]
+    Auto,
+    /// Use basic auth with the client's API key, failing if it has none.
+    Basic,
+    /// Use token auth, obtaining a token with the client's credentials if
+    /// it doesn't have one.
+    Token,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct AuthOptions {
     pub token: Option<Credential>,
-    pub headers: Option<http::HeaderMap>,
+    pub headers: http::Headers,
     pub method: http::Method,
-    pub params: Option<http::UrlQuery>,
+    pub params: http::Params,
+}
+
+/// A URL to request tokens from, along with the method, headers and params
+/// of the request, passed to ClientOptions::try_with_auth_url (RSA8c).
+///
+/// The URL and headers are validated when the ClientOptions are created.
+///
+/// # Example
+///
+/// ```
+/// use ably::auth::AuthUrl;
+///
+/// let auth_url = AuthUrl::post("https://example.com/auth")
+///     .header("X-Tenant", "tenant-1")
+///     .param("deviceId", "device-1");
+/// let client = ably::ClientOptions::try_with_auth_url(auth_url)?.rest()?;
+/// # Ok::<(), ably::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct AuthUrl {
+    url: String,
+    method: http::Method,
+    headers: Vec<(String, String)>,
+    params: http::Params,
+}
+
+impl AuthUrl {
+    /// Request tokens with a GET request, including params in the query
+    /// string.
+    pub fn get(url: impl Into<String>) -> Self {
+        Self::new(http::Method::GET, url.into())
+    }
+
+    /// Request tokens with a POST request, including params in the
+    /// form-encoded body.
+    pub fn post(url: impl Into<String>) -> Self {
+        Self::new(http::Method::POST, url.into())
+    }
+
+    fn new(method: http::Method, url: String) -> Self {
+        Self {
+            url,
+            method,
+            headers: Vec::new(),
+            params: http::Params::new(),
+        }
+    }
+
+    /// Include a header in token requests, replacing any existing value.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        let name = name.into();
+        self.headers.retain(|(n, _)| !n.eq_ignore_ascii_case(&name));
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    /// Include a param in token requests, replacing any existing value.
+    pub fn param(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.params.insert(name, value);
+        self
+    }
+
+    /// Returns the parsed URL, method, headers and params, failing if the
+    /// URL isn't an absolute http or https URL or a header is invalid.
+    pub(crate) fn build(self) -> Result<(reqwest::Url, AuthOptions)> {
+        let url = reqwest::Url::parse(&self.url).map_err(|err| {
+            Error::with_cause(
+                ErrorCode::InvalidParameterValue,
+                err,
+                format!("invalid authUrl '{}'", self.url),
+            )
+        })?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(Error::new(
+                ErrorCode::InvalidParameterValue,
+                format!("authUrl must be an http or https URL, got '{}'", self.url),
+            ));
+        }
+
+        let options = AuthOptions {
+            token: None,
+            headers: http::Headers::from_pairs(self.headers)?,
+            method: self.method,
+            params: self.params,
+        };
+        Ok((url, options))
+    }
 }
 
 /// An API Key used to authenticate with the REST API using HTTP Basic Auth.
@@ -107,6 +241,78 @@ impl Key {
             Err(Error::new(ErrorCode::BadRequest, "Invalid key"))
         }
     }
+
+    /// Create an [Ably JWT] signed with the key, which a server can return
+    /// to clients to authenticate with instead of a token.
+    ///
+    /// The JWT has the capability, client_id and ttl of the given params,
+    /// and is issued at their timestamp, or now if it isn't set.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ably::auth::{Key, TokenParams};
+    ///
+    /// let key = Key::new("aaaaaa.bbbbbb:cccccc")?;
+    /// let params = TokenParams::new().client_id("alice").ttl(chrono::Duration::hours(1));
+    ///
+    /// let jwt = key.sign_jwt(&params)?;
+    /// assert_eq!(jwt.split('.').count(), 3);
+    /// # Ok::<(), ably::Error>(())
+    /// ```
+    ///
+    /// [Ably JWT]: https://ably.com/docs/auth/token#jwt
+    pub fn sign_jwt(&self, params: &TokenParams) -> Result<String> {
+        params.validate(false)?;
+
+        let issued = params.timestamp.unwrap_or_else(Utc::now);
+        let header = JwtHeader {
+            typ: "JWT",
+            alg: "HS256",
+            kid: &self.name,
+        };
+        let claims = JwtClaims {
+            iat: issued.timestamp(),
+            exp: (issued + params.ttl).timestamp(),
+            capability: &params.capability,
+            client_id: params.client_id.as_deref(),
+        };
+
+        let encode = |v: &[u8]| base64::encode_config(v, base64::URL_SAFE_NO_PAD);
+        let payload = format!(
+            "{}.{}",
+            encode(&serde_json::to_vec(&header)?),
+            encode(&serde_json::to_vec(&claims)?)
+        );
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.value.as_bytes())?;
+        mac.update(payload.as_bytes());
+
+        Ok(format!(
+            "{}.{}",
+            payload,
+            encode(&mac.finalize().into_bytes())
+        ))
+    }
+}
+
+/// The JOSE header of an Ably JWT.
+#[derive(Serialize)]
+struct JwtHeader<'a> {
+    typ: &'static str,
+    alg: &'static str,
+    kid: &'a str,
+}
+
+/// The claims of an Ably JWT.
+#[derive(Serialize)]
+struct JwtClaims<'a> {
+    iat: i64,
+    exp: i64,
+    #[serde(rename = "x-ably-capability")]
+    capability: &'a str,
+    #[serde(rename = "x-ably-clientId", skip_serializing_if = "Option::is_none")]
+    client_id: Option<&'a str>,
 }
 
 impl TryFrom<&str> for Key {
@@ -154,6 +360,85 @@ impl Key {
     pub fn sign(&self, params: &TokenParams) -> Result<TokenRequest> {
         params.sign(self)
     }
+
+    /// Use the API key to sign the given TokenParams like Key::sign, but
+    /// permit their client_id to be the wildcard '*', which allows the token
+    /// holder to assume any identity.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ably::auth::{Key, TokenParams};
+    ///
+    /// let key = Key::new("aaaaaa.bbbbbb:cccccc")?;
+    /// let params = TokenParams::new().client_id("*");
+    ///
+    /// assert!(key.sign(&params).is_err());
+    /// let req = key.sign_with_wildcard_client_id(&params)?;
+    /// assert_eq!(req.client_id.as_deref(), Some("*"));
+    /// # Ok::<(), ably::Error>(())
+    /// ```
+    pub fn sign_with_wildcard_client_id(&self, params: &TokenParams) -> Result<TokenRequest> {
+        params.validate(true)?;
+        params.sign_unchecked(self)
+    }
+
+    /// Use the API key to sign each of the given TokenParams, for example
+    /// in an auth server minting tokens for many clientIds at once.
+    ///
+    /// The current time is read once and used as the timestamp of every
+    /// TokenParams without one, and large batches are signed in parallel
+    /// across the available CPUs. The results are returned in the same
+    /// order as the params, so that invalid params only fail their own
+    /// TokenRequest.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ably::auth::{Key, TokenParams};
+    ///
+    /// let key = Key::new("aaaaaa.bbbbbb:cccccc")?;
+    /// let params = ["alice", "bob"]
+    ///     .into_iter()
+    ///     .map(|client_id| TokenParams::new().client_id(client_id))
+    ///     .collect();
+    ///
+    /// let reqs = key.sign_batch(params);
+    /// assert_eq!(reqs[1].as_ref().unwrap().client_id.as_deref(), Some("bob"));
+    /// # Ok::<(), ably::Error>(())
+    /// ```
+    pub fn sign_batch(&self, params: Vec<TokenParams>) -> Vec<Result<TokenRequest>> {
+        let now = Utc::now();
+        let sign = |params: TokenParams| -> Result<TokenRequest> {
+            match params.timestamp {
+                Some(_) => params.sign(self),
+                None => params.timestamp(now).sign(self),
+            }
+        };
+
+        let threads = std::thread::available_parallelism().map_or(1, usize::from);
+        if params.len() < SIGN_BATCH_PARALLEL_THRESHOLD || threads == 1 {
+            return params.into_iter().map(sign).collect();
+        }
+
+        let chunk_size = params.len().div_ceil(threads);
+        let mut chunks = Vec::with_capacity(threads);
+        let mut params = params.into_iter().peekable();
+        while params.peek().is_some() {
+            chunks.push(params.by_ref().take(chunk_size).collect::<Vec<_>>());
+        }
+
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = chunks
+                .into_iter()
+                .map(|chunk| scope.spawn(move || chunk.into_iter().map(sign).collect::<Vec<_>>()))
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("token request signing panicked"))
+                .collect()
+        })
+    }
 }
 
 /// Provides functions relating to Ably API authentication.
@@ -172,6 +457,9 @@ impl<'a> Auth<'a> {
     }
 
     /// Start building a TokenRequest to be signed by a local API key.
+    ///
+    /// If the query_time option is set and the server time has already been
+    /// queried, the TokenRequest timestamp is adjusted to the server's clock.
     pub fn create_token_request(
         &self,
         params: &TokenParams,
@@ -186,9 +474,109 @@ impl<'a> Auth<'a> {
                 ))
             }
         };
+        if params.timestamp.is_none() {
+            if let Some(offset) = *self.inner().time_offset.lock().unwrap() {
+                let params = TokenParams {
+                    timestamp: Some(Utc::now() + offset),
+                    ..params.clone()
+                };
+                return params.sign(key);
+            }
+        }
         params.sign(key)
     }
 
+    /// Check that a TokenRequest obtained from an auth callback or authUrl
+    /// is current and has a valid ttl if the validate_token_requests option
+    /// is set.
+    ///
+    /// The timestamp is compared with the server time if the query_time
+    /// option is set, querying the offset of the server's clock first if it
+    /// isn't yet known.
+    async fn validate_token_request(&self, req: &TokenRequest) -> Result<()> {
+        if !self.inner().opts.validate_token_requests {
+            return Ok(());
+        }
+
+        let now = self.timestamp().await?;
+        let max_age = Duration::minutes(TOKEN_REQUEST_MAX_AGE_MINS);
+        if req.timestamp < now - max_age || req.timestamp > now + max_age {
+            return Err(Error::with_status(
+                ErrorCode::TimestampNotCurrent,
+                401,
+                format!(
+                    "TokenRequest is stale; its timestamp {} is not within {} minutes of the current time {}",
+                    req.timestamp, TOKEN_REQUEST_MAX_AGE_MINS, now
+                ),
+            ));
+        }
+
+        if req.ttl < Duration::zero() {
+            return Err(Error::with_status(
+                ErrorCode::InvalidParameterValue,
+                400,
+                format!(
+                    "TokenRequest ttl of {}ms is invalid; it must not be negative",
+                    req.ttl.num_milliseconds()
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the time to sign a token request with, which is the server
+    /// time if the query_time option is set, querying the offset of the
+    /// server's clock first if it isn't yet known.
+    async fn timestamp(&self) -> Result<DateTime<Utc>> {
+        if !self.inner().opts.query_time {
+            return Ok(Utc::now());
+        }
+
+        if let Some(offset) = *self.inner().time_offset.lock().unwrap() {
+            return Ok(Utc::now() + offset);
+        }
+
+        // Boxed since the request is sent by the same code path which
+        // authenticates requests, which in turn can call this method.
+        let server_time = Box::pin(self.rest.request_time(false)).await?;
+        *self.inner().time_offset.lock().unwrap() = Some(server_time - Utc::now());
+        Ok(server_time)
+    }
+
+    /// Start building a request to revoke tokens issued by the client's API
+    /// key which match the given targets.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> ably::Result<()> {
+    /// # let results = serde_json::json!([
+    /// #     {"target": "clientId:user-1", "issuedBefore": 1650000000000_i64, "appliesAt": 1650000000000_i64},
+    /// #     {"target": "revocationKey:group-1", "error": {"code": 40000, "statusCode": 400, "message": "invalid"}},
+    /// # ]);
+    /// # let transport = ably::mock::MockTransport::new().on(ably::http::Method::POST, "/keys/aaaaaa.bbbbbb/revokeTokens", 200, results);
+    /// # let client = ably::Rest::with_http_executor(ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc"), transport)?;
+    /// let res = client
+    ///     .auth()
+    ///     .revoke_tokens()
+    ///     .client_id("user-1")
+    ///     .revocation_key("group-1")
+    ///     .send()
+    ///     .await?;
+    ///
+    /// assert_eq!(res.success_count, 1);
+    /// for failure in res.results.iter().filter(|r| !r.is_success()) {
+    ///     println!("failed to revoke {}: {:?}", failure.target, failure.error);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn revoke_tokens(&self) -> RevokeTokensBuilder<'a> {
+        RevokeTokensBuilder::new(self.rest)
+    }
+
     /// Exchange a TokenRequest for a token by making a HTTP request to the
     /// [requestToken endpoint] in the Ably REST API.
     ///
@@ -213,18 +601,30 @@ impl<'a> Auth<'a> {
         Box::pin(async move { req.send().await?.body().await.map_err(Into::into) })
     }
 
-    /// Request a token from the URL.
+    /// Request a token from the given authUrl, including the TokenParams
+    /// merged with the authParams either in the query string of a GET
+    /// request or in the form-encoded body of a POST request (RSA8c).
     fn request_url<'b>(
         &'b self,
         url: &'b reqwest::Url,
+        params: &'b TokenParams,
+        options: &'b AuthOptions,
     ) -> Pin<Box<dyn Future<Output = Result<TokenDetails>> + Send + 'b>> {
         let fut = async move {
-            let res = self
+            let mut auth_params = options.params.clone();
+            auth_params.merge(params.auth_url_params());
+
+            let req = self
                 .rest
-                .request_url(Default::default(), url.clone())
-                .authenticate(false)
-                .send()
-                .await?;
+                .request_url(options.method.clone(), url.clone())
+                .headers(options.headers.clone())
+                .authenticate(false);
+            let req = if options.method == http::Method::GET {
+                req.params(&auth_params)
+            } else {
+                req.form(&auth_params)
+            };
+            let res = req.send().await?;
 
             // Parse the token response based on the Content-Type header.
             let content_type = res.content_type().ok_or_else(|| {
@@ -239,10 +639,7 @@ impl<'a> Auth<'a> {
                 // let serde figure out which Token variant to decode the JSON
                 // response into.
                 let token: RequestOrDetails = res.json().await?;
-                match token {
-                    RequestOrDetails::Request(r) => self.exchange(&r).await,
-                    RequestOrDetails::Details(d) => Ok(d),
-                }
+                token.into_details(self).await
             },
 
             "text/plain" | "application/jwt" => {
@@ -263,6 +660,52 @@ impl<'a> Auth<'a> {
         &self,
         params: &TokenParams,
         options: &AuthOptions,
+    ) -> Result<TokenDetails> {
+        self.request_token_with_context(params, options, AuthContext::default())
+            .await
+    }
+
+    /// Obtain a new token and use it to authenticate subsequent requests,
+    /// for example to upgrade the client's capabilities or change its
+    /// clientId (RSA10).
+    ///
+    /// The given params and options are stored and used to request any
+    /// subsequent tokens, except for the timestamp and nonce (RSA10e). If
+    /// options has no token, the client's credentials and authUrl options are
+    /// used. Clients using an API key switch to token auth once authorize is
+    /// called.
+    pub async fn authorize(
+        &self,
+        params: &TokenParams,
+        options: &AuthOptions,
+    ) -> Result<TokenDetails> {
+        let options = match options.token {
+            Some(_) => options.clone(),
+            None => self.client_auth_options(),
+        };
+
+        let token = self
+            .obtain_token(params, &options, AuthContext::default())
+            .await?;
+
+        let params = TokenParams {
+            nonce: None,
+            timestamp: None,
+            ..params.clone()
+        };
+        *self.inner().token.lock().unwrap() = Some(token.clone());
+        *self.inner().authorized.lock().unwrap() = Some((params, options));
+
+        Ok(token)
+    }
+
+    /// Request a token, passing the given context to the AuthCallback if one
+    /// is used to obtain the token.
+    pub async fn request_token_with_context(
+        &self,
+        params: &TokenParams,
+        options: &AuthOptions,
+        context: AuthContext<'_>,
     ) -> Result<TokenDetails> {
         let token = options.token.as_ref().ok_or_else(|| {
             Error::new(
@@ -274,12 +717,21 @@ impl<'a> Auth<'a> {
         let mut details = match token {
             Credential::TokenDetails(token) => Ok(token.clone()),
             Credential::TokenRequest(r) => self.exchange(r).await,
-            Credential::Callback(f) => match f.token(params).await {
+            Credential::Callback(f) => match f.token_with_context(params, context).await {
                 Ok(token) => token.into_details(self).await,
                 Err(e) => Err(e),
             },
-            Credential::Key(k) => self.exchange(&params.sign(k)?).await,
-            Credential::Url(url) => self.request_url(url).await,
+            Credential::Key(k) => {
+                let params = match params.timestamp {
+                    Some(_) => params.clone(),
+                    None => TokenParams {
+                        timestamp: Some(self.timestamp().await?),
+                        ..params.clone()
+                    },
+                };
+                self.exchange(&params.sign(k)?).await
+            }
+            Credential::Url(url) => self.request_url(url, params, options).await,
         };
 
         if matches!(token, Credential::Callback(_) | Credential::Url(_)) {
@@ -309,20 +761,356 @@ impl<'a> Auth<'a> {
         Ok(details)
     }
 
-    /// Set the Authorization header in the given request.
-    pub(crate) async fn with_auth_headers(&self, req: &mut reqwest::Request) -> Result<()> {
-        if let Credential::Key(k) = &self.inner().opts.credential {
-            return Self::set_basic_auth(req, k);
+    /// Set the Authorization header in the given request using the given
+    /// AuthMode, passing the given context to the AuthCallback if one is used
+    /// to obtain a token.
+    pub(crate) async fn with_auth_headers(
+        &self,
+        req: &mut reqwest::Request,
+        mode: AuthMode,
+        context: AuthContext<'_>,
+    ) -> Result<()> {
+        // Use basic auth with an API key unless authorize has been called to
+        // switch to token auth (RSA10a), or the mode requires otherwise.
+        let authorized = self.inner().authorized.lock().unwrap().clone();
+        let use_token_auth = self.inner().opts.use_token_auth;
+        match (mode, &self.inner().opts.credential, &authorized) {
+            (AuthMode::Basic, Credential::Key(k), _) => return Self::set_basic_auth(req, k),
+            (AuthMode::Auto, Credential::Key(k), None) if !use_token_auth => {
+                return Self::set_basic_auth(req, k);
+            }
+            (AuthMode::Basic, _, _) => {
+                return Err(Error::new(
+                    ErrorCode::UnableToObtainCredentialsFromGivenParameters,
+                    "API key is required to use basic auth",
+                ));
+            }
+            _ => (),
+        }
+
+        // Reuse the current token unless it is being renewed or is about to
+        // expire, in which case renew it proactively.
+        let mut context = context;
+        let margin = Duration::from_std(self.inner().opts.token_renewal_margin)
+            .unwrap_or_else(|_| Duration::zero());
+        let current = self.inner().token.lock().unwrap().clone();
+        if context.reason == AuthReason::Initial {
+            match &current {
+                Some(token) if !token.is_expired(margin) => {
+                    return Self::set_bearer_auth(req, &token.token);
+                }
+                Some(_) => context.reason = AuthReason::Proactive,
+                None => (),
+            }
+        }
+
+        // Renew the token once for all the requests which need a new token at
+        // the same time, using the token renewed by another request whilst
+        // waiting as long as it hasn't expired, just as that request does.
+        let _renewing = self.inner().renewing.lock().await;
+        let token = self.inner().token.lock().unwrap().clone();
+        if let Some(token) = &token {
+            let id = |token: &TokenDetails| {
+                (
+                    token.token.clone(),
+                    token.metadata.as_ref().map(|metadata| metadata.expires),
+                )
+            };
+            let renewed = current.as_ref().map(id) != Some(id(token));
+            if renewed && !token.is_expired(Duration::zero()) {
+                return Self::set_bearer_auth(req, &token.token);
+            }
+        }
+
+        if context.reason != AuthReason::Initial {
+            if let Some(token) = token {
+                self.emit(AuthEvent::TokenExpired(&token));
+            }
+        }
+
+        let token = self.renew_token(context).await?;
+        Self::set_bearer_auth(req, &token.token)
+    }
+
+    /// Obtain a new token using the params and options passed to authorize,
+    /// or otherwise the client's credentials, and use it to authenticate
+    /// subsequent requests.
+    ///
+    /// Callers hold the renewing lock so that only one renewal is in flight.
+    async fn renew_token(&self, context: AuthContext<'_>) -> Result<TokenDetails> {
+        let authorized = self.inner().authorized.lock().unwrap().clone();
+        let (params, options) =
+            authorized.unwrap_or_else(|| (Default::default(), self.client_auth_options()));
+
+        let token = self.obtain_token(&params, &options, context).await?;
+        *self.inner().token.lock().unwrap() = Some(token.clone());
+        Ok(token)
+    }
+
+    /// Spawn a task on the current Tokio runtime which renews the client's
+    /// token the given margin before it expires, so that requests never wait
+    /// for a token to be obtained.
+    ///
+    /// A random delay of up to jitter is subtracted from each renewal time,
+    /// so that clients started together don't all renew their tokens at
+    /// once. Renewals are at least 1s apart, and a failed renewal is retried
+    /// after 10s and reported to the client's AuthListeners, as is a renewed
+    /// token which already expires within the margin. Clients which can't
+    /// renew their token, such as those using basic auth, are polled until
+    /// they can.
+    ///
+    /// The task runs until the returned JoinHandle is aborted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a Tokio runtime.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> ably::Result<()> {
+    /// use std::time::Duration;
+    ///
+    /// let client = ably::ClientOptions::with_auth_url("https://example.com/auth".parse().unwrap()).rest()?;
+    ///
+    /// let refresh = client
+    ///     .auth()
+    ///     .spawn_token_refresh(Duration::from_secs(60), Duration::from_secs(10));
+    ///
+    /// // ...
+    ///
+    /// refresh.abort();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn spawn_token_refresh(
+        &self,
+        margin: std::time::Duration,
+        jitter: std::time::Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        let rest = self.rest.clone();
+
+        tokio::spawn(async move {
+            let mut last_renewal: Option<tokio::time::Instant> = None;
+            let mut within_margin = false;
+
+            loop {
+                let auth = rest.auth();
+                let (current, mut delay) = match auth.token_refresh_delay(margin, jitter) {
+                    Some(refresh) => refresh,
+                    None => {
+                        tokio::time::sleep(TOKEN_REFRESH_POLL_INTERVAL).await;
+                        continue;
+                    }
+                };
+                if let Some(last_renewal) = last_renewal {
+                    delay = delay
+                        .max(TOKEN_REFRESH_POLL_INTERVAL.saturating_sub(last_renewal.elapsed()));
+                }
+                tokio::time::sleep(delay).await;
+
+                // Renew the token unless it has already been replaced, for
+                // example by Auth::authorize or a request, while waiting.
+                let renewing = auth.inner().renewing.lock().await;
+                let token = auth.inner().token.lock().unwrap().clone();
+                if token.map(|token| token.token) != current {
+                    continue;
+                }
+
+                let context = AuthContext {
+                    reason: match current {
+                        Some(_) => AuthReason::Proactive,
+                        None => AuthReason::Initial,
+                    },
+                    ..Default::default()
+                };
+                last_renewal = Some(tokio::time::Instant::now());
+                let renewed = auth.renew_token(context).await;
+                drop(renewing);
+                match renewed {
+                    // A token which expires within the margin would be renewed
+                    // again straight away, so report it once and back off.
+                    Ok(token)
+                        if Duration::from_std(margin)
+                            .is_ok_and(|margin| token.is_expired(margin)) =>
+                    {
+                        if !within_margin {
+                            auth.emit(AuthEvent::AuthFailed(&Error::new(
+                                ErrorCode::TokenErrorUnspecified,
+                                format!(
+                                    "renewed token expires within the refresh margin of {:?}",
+                                    margin
+                                ),
+                            )));
+                            within_margin = true;
+                        }
+                        tokio::time::sleep(TOKEN_REFRESH_RETRY_DELAY).await;
+                    }
+                    Ok(_) => within_margin = false,
+                    Err(_) => tokio::time::sleep(TOKEN_REFRESH_RETRY_DELAY).await,
+                }
+            }
+        })
+    }
+
+    /// Returns the current token and how long to wait before renewing it,
+    /// or None if the client can't renew its token or the expiry of its
+    /// token is unknown.
+    fn token_refresh_delay(
+        &self,
+        margin: std::time::Duration,
+        jitter: std::time::Duration,
+    ) -> Option<(Option<String>, std::time::Duration)> {
+        if !self.can_renew() {
+            return None;
         }
 
-        let options = AuthOptions {
-            token: Some(self.inner().opts.credential.clone()),
-            ..Default::default()
+        let token = match self.inner().token.lock().unwrap().clone() {
+            Some(token) => token,
+            None => return Some((None, std::time::Duration::ZERO)),
+        };
+
+        let expires = token.metadata?.expires;
+        let jitter = thread_rng().gen_range(std::time::Duration::ZERO..=jitter);
+        let renew_at = expires - Duration::from_std(margin + jitter).ok()?;
+        let delay = (renew_at - Utc::now())
+            .to_std()
+            .unwrap_or(std::time::Duration::ZERO);
+
+        Some((Some(token.token), delay))
+    }
+
+    /// Obtain a token for the client to authenticate with and check that its
+    /// clientId is compatible, notifying the client's AuthListeners.
+    async fn obtain_token(
+        &self,
+        params: &TokenParams,
+        options: &AuthOptions,
+        context: AuthContext<'_>,
+    ) -> Result<TokenDetails> {
+        self.emit(AuthEvent::TokenRequested(context.reason));
+
+        let res = self
+            .request_token_with_context(params, options, context)
+            .await
+            .and_then(|token| self.check_client_id(&token).map(|_| token));
+
+        match &res {
+            Ok(token) => self.emit(AuthEvent::TokenReceived(token)),
+            Err(err) => self.emit(AuthEvent::AuthFailed(err)),
+        }
+        res
+    }
+
+    fn emit(&self, event: AuthEvent<'_>) {
+        let opts = &self.inner().opts;
+        match &event {
+            AuthEvent::TokenRequested(reason) => opts.log(LogLevel::Debug, || {
+                format!("requesting a token ({:?})", reason)
+            }),
+            AuthEvent::TokenReceived(token) => opts.log(LogLevel::Info, || match &token.metadata {
+                Some(metadata) => {
+                    format!("obtained a token which expires at {}", metadata.expires)
+                }
+                None => "obtained a token".to_string(),
+            }),
+            AuthEvent::TokenExpired(_) => opts.log(LogLevel::Debug, || {
+                "token expired or about to expire, renewing it".to_string()
+            }),
+            AuthEvent::AuthFailed(err) => opts.log(LogLevel::Error, || {
+                format!("failed to obtain a token: {}", log::describe_error(err))
+            }),
+        }
+
+        for listener in &self.inner().opts.auth_listeners {
+            listener.on_auth_event(&event);
+        }
+    }
+
+    /// Returns the clientId which the client is identified as, and so which
+    /// messages published by the client are attributed to (RSA7).
+    ///
+    /// This is the clientId of the token the client is using if it has one,
+    /// and otherwise the clientId set in ClientOptions. A token with the
+    /// wildcard clientId '*' permits any clientId, so the clientId set in
+    /// ClientOptions is returned for it. A client using basic auth with an
+    /// API key has no clientId unless one is set in ClientOptions.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let client = ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc")
+    ///     .client_id("alice")?
+    ///     .rest()?;
+    ///
+    /// assert_eq!(client.auth().client_id().as_deref(), Some("alice"));
+    /// # Ok::<(), ably::Error>(())
+    /// ```
+    pub fn client_id(&self) -> Option<String> {
+        let inner = self.inner();
+        let token = inner.token.lock().unwrap().clone();
+        let token = token.or_else(|| match &inner.opts.credential {
+            Credential::TokenDetails(token) => Some(token.clone()),
+            _ => None,
+        });
+
+        match token.and_then(|token| token.metadata?.client_id) {
+            Some(client_id) if client_id != WILDCARD_CLIENT_ID => Some(client_id),
+            _ => inner.opts.client_id.clone(),
+        }
+    }
+
+    /// Check that the clientId of the given token is compatible with the
+    /// clientId set in ClientOptions, which it must either be equal to or be
+    /// the wildcard clientId '*' (RSA15a).
+    pub(crate) fn check_client_id(&self, token: &TokenDetails) -> Result<()> {
+        let expected = match &self.inner().opts.client_id {
+            Some(client_id) => client_id,
+            None => return Ok(()),
         };
 
-        // TODO defaults
-        let res = self.request_token(&Default::default(), &options).await?;
-        Self::set_bearer_auth(req, &res.token)
+        match token.metadata.as_ref().and_then(|m| m.client_id.as_ref()) {
+            Some(client_id) if client_id != WILDCARD_CLIENT_ID && client_id != expected => {
+                Err(Error::with_status(
+                    ErrorCode::IncompatibleCredentials,
+                    401,
+                    format!(
+                        "token clientId '{}' is incompatible with the clientId '{}' set in ClientOptions",
+                        client_id, expected
+                    ),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns AuthOptions with the client's credentials and the options for
+    /// requesting tokens from its authUrl.
+    fn client_auth_options(&self) -> AuthOptions {
+        let opts = &self.inner().opts;
+        AuthOptions {
+            token: Some(opts.credential.clone()),
+            headers: opts.auth_headers.clone(),
+            method: opts.auth_method.clone(),
+            params: opts.auth_params.clone(),
+        }
+    }
+
+    /// Returns whether the client can obtain a new token if its current
+    /// token is rejected, which requires an authCallback or authUrl, or an
+    /// API key passed to authorize (RSA4a).
+    pub(crate) fn can_renew(&self) -> bool {
+        match &*self.inner().authorized.lock().unwrap() {
+            Some((_, options)) => matches!(
+                options.token,
+                Some(Credential::Key(_) | Credential::Callback(_) | Credential::Url(_))
+            ),
+            None => matches!(
+                self.inner().opts.credential,
+                Credential::Callback(_) | Credential::Url(_)
+            ),
+        }
     }
 
     fn set_bearer_auth(req: &mut reqwest::Request, token: &str) -> Result<()> {
@@ -334,6 +1122,11 @@ impl<'a> Auth<'a> {
     }
 
     fn set_basic_auth(req: &mut reqwest::Request, key: &Key) -> Result<()> {
+        // Never send the API key in cleartext (RSA1).
+        if req.url().scheme() != "https" {
+            return Err(basic_auth_over_non_tls_error());
+        }
+
         let encoded = base64::encode(format!("{}:{}", key.name, key.value));
         Self::set_header(
             req,
@@ -355,43 +1148,6 @@ impl<'a> Auth<'a> {
             .map(char::from)
             .collect()
     }
-
-    /// Use the given API key to compute the HMAC of the canonicalised
-    /// representation of the given TokenRequest.
-    ///
-    /// See the [REST API Token Request Spec] for further details.
-    ///
-    /// [REST API Token Request Spec]: https://docs.ably.io/rest-api/token-request-spec/
-    fn compute_mac(
-        key: &Key,
-        ttl: Duration,
-        capability: &str,
-        client_id: Option<&str>,
-        timestamp: DateTime<Utc>,
-        nonce: &str,
-    ) -> Result<String> {
-        let mut mac = Hmac::<Sha256>::new_from_slice(key.value.as_bytes())?;
-
-        mac.update(key.name.as_bytes());
-        mac.update(b"\n");
-
-        mac.update(ttl.num_milliseconds().to_string().as_bytes());
-        mac.update(b"\n");
-
-        mac.update(capability.as_bytes());
-        mac.update(b"\n");
-
-        mac.update(client_id.map(|c| c.as_bytes()).unwrap_or_default());
-        mac.update(b"\n");
-
-        mac.update(timestamp.timestamp_millis().to_string().as_bytes());
-        mac.update(b"\n");
-
-        mac.update(nonce.as_bytes());
-        mac.update(b"\n");
-
-        Ok(base64::encode(mac.finalize().into_bytes()))
-    }
 }
 
 /// An Ably [TokenParams] object.
@@ -447,41 +1203,105 @@ impl TokenParams {
         self
     }
 
+    /// Check the params satisfy the constraints Ably places on tokens, so
+    /// that signing fails rather than Ably later rejecting the request:
+    ///
+    /// * the ttl must be positive
+    /// * the capability must be a non-empty JSON object mapping resource
+    ///   names to non-empty arrays of operations
+    /// * the client_id must not be empty, and must not be the wildcard '*'
+    ///   unless allow_wildcard_client_id is true
+    fn validate(&self, allow_wildcard_client_id: bool) -> Result<()> {
+        if self.ttl <= Duration::zero() {
+            return Err(Error::new(
+                ErrorCode::InvalidParameterValue,
+                format!(
+                    "ttl of {}ms is invalid; it must be positive",
+                    self.ttl.num_milliseconds()
+                ),
+            ));
+        }
+
+        let capability: Option<json::Map> = serde_json::from_str(&self.capability).ok();
+        let valid = capability.is_some_and(|capability| {
+            !capability.is_empty()
+                && capability.values().all(|ops| match ops.as_array() {
+                    Some(ops) => !ops.is_empty() && ops.iter().all(json::Value::is_string),
+                    None => false,
+                })
+        });
+        if !valid {
+            return Err(Error::new(
+                ErrorCode::InvalidParameterValue,
+                format!(
+                    "capability {} is invalid; it must be a JSON object mapping resource names to arrays of operations",
+                    self.capability
+                ),
+            ));
+        }
+
+        match self.client_id.as_deref() {
+            Some("") => Err(Error::new(
+                ErrorCode::InvalidClientID,
+                "client_id can’t be an empty string",
+            )),
+            Some(WILDCARD_CLIENT_ID) if !allow_wildcard_client_id => Err(Error::new(
+                ErrorCode::InvalidClientID,
+                "client_id can’t be the wildcard '*' unless signed with Key::sign_with_wildcard_client_id",
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the params to include in a request to an authUrl (RSA8c1).
+    ///
+    /// The capability and ttl are omitted when they are the defaults so that
+    /// the auth server can apply its own.
+    pub(crate) fn auth_url_params(&self) -> http::Params {
+        let defaults = Self::default();
+        let mut params = http::Params::new();
+        if self.capability != defaults.capability {
+            params.insert("capability", self.capability.as_str());
+        }
+        if let Some(client_id) = &self.client_id {
+            params.insert("clientId", client_id.as_str());
+        }
+        if let Some(nonce) = &self.nonce {
+            params.insert("nonce", nonce.as_str());
+        }
+        if let Some(timestamp) = &self.timestamp {
+            params.insert("timestamp", timestamp.timestamp_millis().to_string());
+        }
+        if self.ttl != defaults.ttl {
+            params.insert("ttl", self.ttl.num_milliseconds().to_string());
+        }
+        params
+    }
+
     /// Generate a signed TokenRequest for these TokenParams using the steps
     /// described in the [REST API Token Request Spec].
     ///
     /// [REST API Token Request Spec]: https://ably.com/documentation/rest-api/token-request-spec
-    fn sign(&self, key: &Key) -> Result<TokenRequest> {
-        // if client_id is set, it must be a non-empty string
-        if let Some(ref client_id) = self.client_id {
-            if client_id.is_empty() {
-                return Err(Error::new(
-                    ErrorCode::InvalidClientID,
-                    "client_id can’t be an empty string",
-                ));
-            }
-        }
+    pub(crate) fn sign(&self, key: &Key) -> Result<TokenRequest> {
+        self.validate(false)?;
+        self.sign_unchecked(key)
+    }
 
+    /// Generate a signed TokenRequest for these TokenParams without
+    /// validating them first.
+    fn sign_unchecked(&self, key: &Key) -> Result<TokenRequest> {
         let nonce = self.nonce.clone().unwrap_or_else(Auth::generate_nonce);
         let timestamp = self.timestamp.unwrap_or_else(Utc::now);
-        let key_name = key.name.clone();
-
-        let req = TokenRequest {
-            mac: Auth::compute_mac(
-                key,
-                self.ttl,
-                &self.capability,
-                self.client_id.as_deref(),
-                timestamp,
-                &nonce,
-            )?,
-            key_name,
+        let mut req = TokenRequest {
+            key_name: key.name.clone(),
             timestamp,
             capability: self.capability.clone(),
             client_id: self.client_id.clone(),
+            mac: String::new(),
             nonce,
             ttl: self.ttl,
         };
+        req.mac = req.compute_mac(key)?;
 
         Ok(req)
     }
@@ -505,15 +1325,71 @@ pub struct TokenRequest {
     pub ttl: Duration,
 }
 
+impl TokenRequest {
+    /// Returns the canonicalised representation of the TokenRequest which is
+    /// signed with the API key to compute its mac, as described in the [REST
+    /// API Token Request Spec].
+    ///
+    /// The keyName, ttl, capability, clientId, timestamp and nonce are each
+    /// followed by a newline, with an empty line for a missing clientId.
+    /// This can be used to cross-check signatures computed elsewhere.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ably::auth::{Key, TokenParams};
+    /// use chrono::{Duration, TimeZone, Utc};
+    ///
+    /// let key = Key::new("aaaaaa.bbbbbb:cccccc")?;
+    /// let params = TokenParams {
+    ///     nonce: Some("abcdefghijklmnop".to_string()),
+    ///     ..TokenParams::new()
+    ///         .timestamp(Utc.timestamp_millis_opt(1650000000000).unwrap())
+    ///         .ttl(Duration::hours(1))
+    /// };
+    /// let req = key.sign(&params)?;
+    ///
+    /// assert_eq!(
+    ///     req.canonical_string(),
+    ///     "aaaaaa.bbbbbb\n3600000\n{\"*\":[\"*\"]}\n\n1650000000000\nabcdefghijklmnop\n"
+    /// );
+    /// # Ok::<(), ably::Error>(())
+    /// ```
+    ///
+    /// [REST API Token Request Spec]: https://docs.ably.io/rest-api/token-request-spec/
+    pub fn canonical_string(&self) -> String {
+        format!(
+            "{}\n{}\n{}\n{}\n{}\n{}\n",
+            self.key_name,
+            self.ttl.num_milliseconds(),
+            self.capability,
+            self.client_id.as_deref().unwrap_or_default(),
+            self.timestamp.timestamp_millis(),
+            self.nonce,
+        )
+    }
+
+    /// Use the given API key to compute the HMAC of the canonicalised
+    /// representation of the TokenRequest.
+    fn compute_mac(&self, key: &Key) -> Result<String> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key.value.as_bytes())?;
+        mac.update(self.canonical_string().as_bytes());
+        Ok(base64::encode(mac.finalize().into_bytes()))
+    }
+}
+
 /// The token details returned in a successful response from the [REST
 /// requestToken endpoint].
 ///
+/// TokenDetails can be serialized, for example to persist a token across
+/// restarts and pass it back to ClientOptions::with_token_details.
+///
 /// [REST requestToken endpoint]: https://docs.ably.io/rest-api/#request-token
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TokenDetails {
     pub token: String,
-    #[serde(flatten)]
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
     pub metadata: Option<TokenMetadata>,
 }
 
@@ -524,6 +1400,27 @@ impl TokenDetails {
             metadata: None,
         }
     }
+
+    /// Returns whether the token has expired, or expires within the given
+    /// margin, which is never the case if its expiry is unknown.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn load_token() -> String {
+    /// #     serde_json::json!({"token": "xVLyHw.token", "issued": 1650000000000_i64, "expires": 1650003600000_i64, "capability": "{\"*\":[\"*\"]}"}).to_string()
+    /// # }
+    /// use ably::auth::TokenDetails;
+    ///
+    /// let token: TokenDetails = serde_json::from_str(&load_token())?;
+    /// if !token.is_expired(chrono::Duration::minutes(1)) {
+    ///     let client = ably::ClientOptions::with_token_details(token).rest()?;
+    /// }
+    /// # Ok::<(), ably::Error>(())
+    /// ```
+    pub fn is_expired(&self, margin: Duration) -> bool {
+        matches!(&self.metadata, Some(metadata) if metadata.expires <= Utc::now() + margin)
+    }
 }
 
 impl From<String> for TokenDetails {
@@ -535,7 +1432,7 @@ impl From<String> for TokenDetails {
     }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TokenMetadata {
     #[serde(with = "chrono::serde::ts_milliseconds")]
@@ -557,15 +1454,314 @@ pub enum RequestOrDetails {
 impl RequestOrDetails {
     async fn into_details(self, auth: &Auth<'_>) -> Result<TokenDetails> {
         match self {
-            RequestOrDetails::Request(r) => auth.exchange(&r).await,
+            RequestOrDetails::Request(r) => {
+                auth.validate_token_request(&r).await?;
+                auth.exchange(&r).await
+            }
             RequestOrDetails::Details(d) => Ok(d),
         }
     }
 }
 
+/// A target identifying which tokens to revoke.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RevocationTarget {
+    /// Revoke tokens issued with the given client ID.
+    ClientId(String),
+    /// Revoke tokens issued with the given revocation key.
+    RevocationKey(String),
+    /// Revoke tokens issued to the given push device ID.
+    DeviceId(String),
+}
+
+impl fmt::Display for RevocationTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ClientId(v) => write!(f, "clientId:{}", v),
+            Self::RevocationKey(v) => write!(f, "revocationKey:{}", v),
+            Self::DeviceId(v) => write!(f, "deviceId:{}", v),
+        }
+    }
+}
+
+impl Serialize for RevocationTarget {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+/// A builder to construct a request to the [revokeTokens endpoint].
+///
+/// Any number of targets can be added, and they are sent in batches of up to
+/// 100 targets per request, with the per-target results aggregated into a
+/// single TokenRevocationResult.
+///
+/// [revokeTokens endpoint]: https://ably.com/docs/api/rest-api#revoke-tokens
+pub struct RevokeTokensBuilder<'a> {
+    rest: &'a rest::Rest,
+    targets: Vec<RevocationTarget>,
+    issued_before: Option<DateTime<Utc>>,
+    allow_reauth_margin: bool,
+}
+
+impl<'a> RevokeTokensBuilder<'a> {
+    fn new(rest: &'a rest::Rest) -> Self {
+        Self {
+            rest,
+            targets: Vec::new(),
+            issued_before: None,
+            allow_reauth_margin: false,
+        }
+    }
+
+    /// Revoke tokens issued with the given client ID.
+    pub fn client_id(self, client_id: impl Into<String>) -> Self {
+        self.target(RevocationTarget::ClientId(client_id.into()))
+    }
+
+    /// Revoke tokens issued with the given revocation key.
+    pub fn revocation_key(self, key: impl Into<String>) -> Self {
+        self.target(RevocationTarget::RevocationKey(key.into()))
+    }
+
+    /// Revoke tokens issued to the given push device ID.
+    pub fn device_id(self, device_id: impl Into<String>) -> Self {
+        self.target(RevocationTarget::DeviceId(device_id.into()))
+    }
+
+    /// Add a target to revoke tokens for.
+    pub fn target(mut self, target: RevocationTarget) -> Self {
+        self.targets.push(target);
+        self
+    }
+
+    /// Add multiple targets to revoke tokens for.
+    pub fn targets(mut self, targets: impl IntoIterator<Item = RevocationTarget>) -> Self {
+        self.targets.extend(targets);
+        self
+    }
+
+    /// Only revoke tokens issued before the given time, rather than all
+    /// tokens issued before the request is received.
+    pub fn issued_before(mut self, issued_before: DateTime<Utc>) -> Self {
+        self.issued_before = Some(issued_before);
+        self
+    }
+
+    /// Delay the revocation by 30 seconds, giving connected clients time to
+    /// obtain a new token before their current token is revoked.
+    pub fn allow_reauth_margin(mut self, allow: bool) -> Self {
+        self.allow_reauth_margin = allow;
+        self
+    }
+
+    /// Send the revocation requests, returning the result for each target.
+    ///
+    /// Requests are sent in order, and an error is returned if any request
+    /// fails, in which case targets in earlier batches may have already been
+    /// revoked.
+    pub async fn send(self) -> Result<TokenRevocationResult> {
+        let key = match &self.rest.options().credential {
+            Credential::Key(k) => k,
+            _ => {
+                return Err(Error::new(
+                    ErrorCode::UnableToObtainCredentialsFromGivenParameters,
+                    "API key is required to revoke tokens",
+                ))
+            }
+        };
+
+        if self.targets.is_empty() {
+            return Err(Error::new(
+                ErrorCode::BadRequest,
+                "at least one revocation target is required",
+            ));
+        }
+
+        let path = format!("/keys/{}/revokeTokens", key.name);
+        let mut results = Vec::with_capacity(self.targets.len());
+        for targets in self.targets.chunks(MAX_REVOCATION_TARGETS) {
+            let body = RevokeTokensRequest {
+                targets,
+                issued_before: self.issued_before,
+                allow_reauth_margin: self.allow_reauth_margin,
+            };
+            let res: Vec<TokenRevocationTargetResult> = self
+                .rest
+                .request(http::Method::POST, &path)
+                .auth_mode(AuthMode::Basic)
+                .body(&body)
+                .send()
+                .await?
+                .body()
+                .await?;
+            results.extend(res);
+        }
+
+        Ok(TokenRevocationResult::new(results))
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RevokeTokensRequest<'a> {
+    targets: &'a [RevocationTarget],
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "chrono::serde::ts_milliseconds_option"
+    )]
+    issued_before: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    allow_reauth_margin: bool,
+}
+
+/// The aggregated result of revoking tokens for a set of targets.
+#[derive(Debug)]
+pub struct TokenRevocationResult {
+    /// The number of targets for which tokens were revoked.
+    pub success_count: usize,
+    /// The number of targets for which tokens could not be revoked.
+    pub failure_count: usize,
+    /// The result for each target.
+    pub results: Vec<TokenRevocationTargetResult>,
+}
+
+impl TokenRevocationResult {
+    fn new(results: Vec<TokenRevocationTargetResult>) -> Self {
+        let success_count = results.iter().filter(|r| r.is_success()).count();
+        Self {
+            success_count,
+            failure_count: results.len() - success_count,
+            results,
+        }
+    }
+}
+
+/// The result of revoking tokens for a single target.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenRevocationTargetResult {
+    /// The target, in the form `<type>:<value>`.
+    pub target: String,
+    /// Tokens issued before this time are revoked.
+    #[serde(default, with = "chrono::serde::ts_milliseconds_option")]
+    pub issued_before: Option<DateTime<Utc>>,
+    /// The time at which the revocation takes effect.
+    #[serde(default, with = "chrono::serde::ts_milliseconds_option")]
+    pub applies_at: Option<DateTime<Utc>>,
+    /// The reason tokens for the target could not be revoked.
+    pub error: Option<Error>,
+}
+
+impl TokenRevocationTargetResult {
+    /// Returns whether tokens for the target were revoked.
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Returns the error for attempting to use basic auth, and so send the API
+/// key in cleartext, over a non-TLS connection (RSA1).
+pub(crate) fn basic_auth_over_non_tls_error() -> Error {
+    Error::with_status(
+        ErrorCode::InvalidUseOfBasicAuthOverNonTLSTransport,
+        401,
+        "basic auth can't be used over a non-TLS connection, use token auth instead",
+    )
+}
+
+/// An event in the lifecycle of the tokens a client authenticates with,
+/// which is passed to its AuthListeners.
+#[derive(Debug)]
+pub enum AuthEvent<'a> {
+    /// A token is being requested for the given reason.
+    TokenRequested(AuthReason),
+    /// A token was obtained, and will be used to authenticate requests.
+    TokenReceived(&'a TokenDetails),
+    /// The current token has expired or been rejected by Ably, or is about
+    /// to expire, and is being renewed.
+    TokenExpired(&'a TokenDetails),
+    /// Obtaining a token failed with the given error.
+    AuthFailed(&'a Error),
+}
+
+/// Listens for the AuthEvents of a client, for example to log or record
+/// metrics about token renewals, or to persist tokens as they're issued so
+/// that they can be passed to ClientOptions::with_token_details on restart.
+///
+/// AuthListeners are registered with ClientOptions::auth_listener and are
+/// called in the order they were registered. Events are only emitted for
+/// tokens used to authenticate the client, and not for tokens requested with
+/// Auth::request_token.
+pub trait AuthListener: fmt::Debug + Send + Sync {
+    /// Called with each AuthEvent.
+    fn on_auth_event(&self, event: &AuthEvent<'_>);
+}
+
+impl<T: AuthListener + ?Sized> AuthListener for Arc<T> {
+    fn on_auth_event(&self, event: &AuthEvent<'_>) {
+        (**self).on_auth_event(event)
+    }
+}
+
+/// The reason a token is being requested.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuthReason {
+    /// The client doesn't have a token yet.
+    Initial,
+    /// The current token was rejected by Ably, for example because it has
+    /// expired.
+    Renewal,
+    /// The current token is about to expire, and is being renewed ahead of
+    /// time.
+    Proactive,
+}
+
+/// Context describing why a token is being requested, which is passed to
+/// AuthCallback::token_with_context.
+#[derive(Clone, Copy, Debug)]
+pub struct AuthContext<'a> {
+    /// Why the token is being requested.
+    pub reason: AuthReason,
+
+    /// The number of times a token has been requested for the current
+    /// operation, starting at 1.
+    pub attempt: u32,
+
+    /// The error which caused the previous token to be rejected, if any.
+    pub previous_error: Option<&'a Error>,
+}
+
+impl Default for AuthContext<'_> {
+    fn default() -> Self {
+        Self {
+            reason: AuthReason::Initial,
+            attempt: 1,
+            previous_error: None,
+        }
+    }
+}
+
 pub trait AuthCallback: Send + Sync {
     fn token<'a>(
         &'a self,
         params: &'a TokenParams,
     ) -> Pin<Box<dyn Send + Future<Output = Result<RequestOrDetails>> + 'a>>;
+
+    /// Obtain a token given some context about why it is being requested,
+    /// for example to request a broader capability when a renewal follows a
+    /// capability error.
+    ///
+    /// Defaults to calling token, ignoring the context.
+    fn token_with_context<'a>(
+        &'a self,
+        params: &'a TokenParams,
+        context: AuthContext<'a>,
+    ) -> Pin<Box<dyn Send + Future<Output = Result<RequestOrDetails>> + 'a>> {
+        let _ = context;
+        self.token(params)
+    }
 }
@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::rest::Message;
+
+/// A set of messages to publish to a set of channels with
+/// Rest::batch_publish, where every message is published to every channel.
+#[derive(Clone, Debug, Serialize)]
+pub struct BatchPublishSpec {
+    pub channels: Vec<String>,
+    pub messages: Vec<Message>,
+}
+
+impl BatchPublishSpec {
+    /// Returns a BatchPublishSpec publishing the given messages to each of the
+    /// given channels.
+    pub fn new(
+        channels: impl IntoIterator<Item = impl Into<String>>,
+        messages: Vec<Message>,
+    ) -> Self {
+        Self {
+            channels: channels.into_iter().map(Into::into).collect(),
+            messages,
+        }
+    }
+}
+
+/// The result of a batch operation, containing the result for each target
+/// of the operation, some of which may have failed.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchResult<T> {
+    /// The number of targets for which the operation succeeded.
+    pub success_count: usize,
+    /// The number of targets for which the operation failed.
+    pub failure_count: usize,
+    /// The result for each target.
+    pub results: Vec<T>,
+}
+
+impl<T> BatchResult<T> {
+    /// Returns whether the operation succeeded for every target.
+    pub fn is_success(&self) -> bool {
+        self.failure_count == 0
+    }
+}
+
+/// The result of publishing a BatchPublishSpec's messages to a single
+/// channel.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchPublishResult {
+    /// The name of the channel.
+    pub channel: String,
+    /// The ID prefix of the published messages, which are given IDs of the
+    /// form `<message_id>:<index>`.
+    #[serde(default)]
+    pub message_id: Option<String>,
+    /// The serial of each published message, or None for a message which
+    /// was discarded, for example by a conflation rule.
+    #[serde(default)]
+    pub serials: Vec<Option<String>>,
+    /// The reason the messages could not be published to the channel.
+    #[serde(default)]
+    pub error: Option<Error>,
+}
+
+impl BatchPublishResult {
+    /// Returns whether the messages were published to the channel.
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
@@ -0,0 +1,210 @@
+//! A high level client exposing the most common Ably operations.
+//!
+//! # Example
+//!
+//! ```
+//! # #[tokio::main]
+//! # async fn main() -> ably::Result<()> {
+//! # let transport = ably::mock::MockTransport::new()
+//! #     .on(ably::http::Method::POST, "/channels/greetings/messages", 201, serde_json::json!({}))
+//! #     .on(ably::http::Method::GET, "/channels/greetings/history", 200, serde_json::json!([{"name": "greeting", "data": "hello"}]));
+//! # let rest = ably::Rest::with_http_executor(ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc"), transport)?;
+//! # let client = ably::Client::from(rest);
+//! // let client = ably::Client::new("<api_key>")?;
+//!
+//! client.publish("greetings", "greeting", "hello").await?;
+//!
+//! let history = client.history("greetings").await?.items().await?;
+//! assert_eq!(history[0].data, ably::Data::from("hello"));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::ops::Deref;
+
+use futures::future::BoxFuture;
+
+use crate::auth::{AuthOptions, TokenDetails, TokenParams, TokenRequest};
+use crate::http::PaginatedResult;
+use crate::rest::{Data, Message, Rest};
+use crate::{ClientOptions, Result};
+
+/// A client which wraps a Rest client behind a single constructor, and
+/// exposes the most common operations as methods so that they can be used
+/// without knowing the layout of the crate's modules.
+///
+/// The underlying Rest client is available with Client::rest, or by
+/// dereferencing the Client, for everything else.
+///
+/// Subscribing to channels requires a realtime connection, which is not yet
+/// supported.
+#[derive(Clone, Debug)]
+pub struct Client {
+    rest: Rest,
+}
+
+impl Client {
+    /// Returns a Client initialised with an API key or token contained in
+    /// the given string.
+    pub fn new(key: &str) -> Result<Self> {
+        Rest::new(key).map(Self::from)
+    }
+
+    /// Returns a Client initialised with the given ClientOptions.
+    pub fn with_options(opts: ClientOptions) -> Result<Self> {
+        opts.rest().map(Self::from)
+    }
+
+    /// Returns the underlying Rest client.
+    pub fn rest(&self) -> &Rest {
+        &self.rest
+    }
+
+    /// Publish a message with the given name and data to the given channel.
+    pub async fn publish(
+        &self,
+        channel: &str,
+        name: impl Into<String>,
+        data: impl Into<Data>,
+    ) -> Result<()> {
+        let msg = Message {
+            name: Some(name.into()),
+            data: data.into(),
+            ..Default::default()
+        };
+
+        self.rest
+            .channels()
+            .get(channel)
+            .publish()
+            .message(msg)
+            .send()
+            .await
+    }
+
+    /// Retrieve the first page of the given channel's message history, most
+    /// recent first.
+    pub async fn history(&self, channel: &str) -> Result<PaginatedResult<Message>> {
+        self.rest.channels().get(channel).history().send().await
+    }
+
+    /// Request a token using the client's credentials.
+    pub async fn request_token(&self, params: &TokenParams) -> Result<TokenDetails> {
+        let options = AuthOptions {
+            token: Some(self.rest.options().credential.clone()),
+            ..Default::default()
+        };
+
+        self.rest.auth().request_token(params, &options).await
+    }
+
+    /// Create a TokenRequest signed with the client's API key, for example to
+    /// return to a browser client which then exchanges it for a token.
+    pub fn create_token_request(&self, params: &TokenParams) -> Result<TokenRequest> {
+        let options = AuthOptions {
+            token: Some(self.rest.options().credential.clone()),
+            ..Default::default()
+        };
+
+        self.rest.auth().create_token_request(params, &options)
+    }
+}
+
+impl From<Rest> for Client {
+    fn from(rest: Rest) -> Self {
+        Self { rest }
+    }
+}
+
+impl Deref for Client {
+    type Target = Rest;
+
+    fn deref(&self) -> &Rest {
+        &self.rest
+    }
+}
+
+/// Publishes messages to channels.
+///
+/// AblyPublisher is object-safe, so application code can depend on a
+/// `&dyn AblyPublisher` or `Arc<dyn AblyPublisher>` which is a Client or Rest
+/// client in production, and a mock::FakeClient in unit tests.
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() -> ably::Result<()> {
+/// use ably::client::AblyPublisher;
+/// use ably::rest::Message;
+///
+/// async fn notify(publisher: &dyn AblyPublisher, user: &str) -> ably::Result<()> {
+///     let msg = Message {
+///         name: Some("notification".to_string()),
+///         data: format!("hello {}", user).into(),
+///         ..Default::default()
+///     };
+///     publisher.publish("notifications", msg).await
+/// }
+///
+/// let fake = ably::mock::FakeClient::new();
+/// notify(&fake, "alice").await?;
+///
+/// let messages = fake.messages("notifications");
+/// assert_eq!(messages[0].data, ably::Data::from("hello alice"));
+/// # Ok(())
+/// # }
+/// ```
+pub trait AblyPublisher: Send + Sync {
+    /// Publish the message to the given channel.
+    fn publish<'a>(&'a self, channel: &'a str, message: Message) -> BoxFuture<'a, Result<()>>;
+}
+
+/// Retrieves the message history of channels.
+///
+/// Like AblyPublisher, AblyHistory is object-safe so that it can be
+/// implemented by a mock::FakeClient in unit tests.
+pub trait AblyHistory: Send + Sync {
+    /// Retrieve the first page of the given channel's message history, most
+    /// recent first.
+    fn history<'a>(&'a self, channel: &'a str) -> BoxFuture<'a, Result<Vec<Message>>>;
+}
+
+impl AblyPublisher for Rest {
+    fn publish<'a>(&'a self, channel: &'a str, message: Message) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            self.channels()
+                .get(channel)
+                .publish()
+                .message(message)
+                .send()
+                .await
+        })
+    }
+}
+
+impl AblyHistory for Rest {
+    fn history<'a>(&'a self, channel: &'a str) -> BoxFuture<'a, Result<Vec<Message>>> {
+        Box::pin(async move {
+            self.channels()
+                .get(channel)
+                .history()
+                .send()
+                .await?
+                .items()
+                .await
+        })
+    }
+}
+
+impl AblyPublisher for Client {
+    fn publish<'a>(&'a self, channel: &'a str, message: Message) -> BoxFuture<'a, Result<()>> {
+        self.rest.publish(channel, message)
+    }
+}
+
+impl AblyHistory for Client {
+    fn history<'a>(&'a self, channel: &'a str) -> BoxFuture<'a, Result<Vec<Message>>> {
+        AblyHistory::history(&self.rest, channel)
+    }
+}
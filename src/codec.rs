@@ -0,0 +1,244 @@
+//! The encoding and decoding of message data (RSL4, RSL6).
+//!
+//! When a message is published, its data is encoded to a form which can be
+//! carried by the protocol, and each transformation is recorded in the
+//! message's `encoding` as a list of encodings separated by '/':
+//!
+//! - JSON data is serialized to a string and recorded as `json`
+//! - when a cipher is set, the data is encrypted and recorded as
+//!   `cipher+<algorithm>`, after strings are converted to bytes and recorded
+//!   as `utf-8`
+//! - when the JSON protocol is used, binary data is encoded as a base64
+//!   string and recorded as `base64`
+//!
+//! When a message is received, the encodings are reversed from last to first
+//! to recover the original data, for example data with an encoding of
+//! `json/utf-8/cipher+aes-128-cbc/base64` is base64 decoded, decrypted,
+//! decoded as utf-8 and then parsed as JSON.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::crypto::Cipher;
+use crate::error::{Error, ErrorCode};
+use crate::rest::{ChannelOptions, Data, Encoding, Format};
+use crate::Result;
+
+/// Encode the given data to be sent in the given format, encrypting it if a
+/// cipher is given, and append each applied encoding to the given encoding.
+pub fn encode(
+    data: &mut Data,
+    encoding: &mut Encoding,
+    format: &Format,
+    cipher: Option<&dyn Cipher>,
+) -> Result<()> {
+    match &*data {
+        Data::String(s) => {
+            if let Some(cipher) = cipher {
+                *data = cipher.encrypt(s.as_bytes())?.into();
+                encoding.push("utf-8");
+                encoding.push(cipher.encoding());
+            }
+        }
+        Data::Binary(bytes) => {
+            if let Some(cipher) = cipher {
+                *data = cipher.encrypt(bytes)?.into();
+                encoding.push(cipher.encoding());
+            }
+        }
+        Data::JSON(value) => {
+            let json_str = serde_json::to_string(value)?;
+
+            if let Some(cipher) = cipher {
+                *data = cipher.encrypt(json_str.as_bytes())?.into();
+                encoding.push("json");
+                encoding.push("utf-8");
+                encoding.push(cipher.encoding());
+            } else {
+                *data = json_str.into();
+                encoding.push("json");
+            }
+        }
+        Data::None => (),
+    }
+
+    // If we have binary data but JSON format, base64 encode the data.
+    if let Data::Binary(bytes) = &*data {
+        if format.is_json() {
+            *data = base64::encode(bytes).into();
+            encoding.push("base64");
+        }
+    };
+
+    Ok(())
+}
+
+/// Iteratively decode the given data based on the given list of encodings.
+///
+/// If an encoding can't be decoded, the data is left in its partially
+/// decoded state with the remaining encodings, and the error is returned.
+pub fn decode(
+    data: &mut Data,
+    encoding: &mut Encoding,
+    opts: Option<&ChannelOptions>,
+) -> Result<()> {
+    while let Some(enc) = encoding.pop() {
+        *data = match decode_once(data, &enc, opts) {
+            Ok(data) => data,
+            Err(err) => {
+                encoding.push(enc);
+                return Err(err);
+            }
+        }
+    }
+    Ok(())
+}
+
+lazy_static! {
+    /// A regular expression to split a data encoding into its format and params.
+    static ref ENCODING_RE: Regex =
+        Regex::new(r#"^(?P<format>[\-\w]+)(?:\+(?P<params>[\-\w]+))?"#).unwrap();
+}
+
+fn decode_once(data: &mut Data, encoding: &str, opts: Option<&ChannelOptions>) -> Result<Data> {
+    let caps = ENCODING_RE
+        .captures(encoding)
+        .ok_or_else(|| Error::new(ErrorCode::InvalidHeader, "Invalid encoding"))?;
+    let format = caps
+        .name("format")
+        .ok_or_else(|| Error::new(ErrorCode::InvalidHeader, "Invalid encoding; missing format"))?
+        .as_str();
+
+    match format {
+        "utf-8" => match data {
+            Data::String(s) => Ok(Data::String(s.to_string())),
+            Data::Binary(data) => std::str::from_utf8(data)
+                .map(Into::into)
+                .map_err(Into::into),
+            _ => Err(Error::new(
+                ErrorCode::InvalidMessageDataOrEncoding,
+                "invalid utf-8 message data",
+            )),
+        },
+        "json" => match data {
+            Data::String(s) => serde_json::from_str::<serde_json::Value>(s)
+                .map(Into::into)
+                .map_err(Into::into),
+            _ => Err(Error::new(
+                ErrorCode::InvalidMessageDataOrEncoding,
+                "invalid JSON message data",
+            )),
+        },
+        "base64" => match data {
+            Data::String(s) => base64::decode(s).map(Into::into).map_err(Into::into),
+            _ => Err(Error::new(
+                ErrorCode::InvalidMessageDataOrEncoding,
+                "invalid base64 message data",
+            )),
+        },
+        "cipher" => match data {
+            Data::Binary(ref mut data) => {
+                let opts = opts.ok_or_else(|| {
+                    Error::new(
+                        ErrorCode::BadRequest,
+                        "unable to decrypt message, no channel options",
+                    )
+                })?;
+                let cipher = opts.cipher.as_ref().ok_or_else(|| {
+                    Error::new(
+                        ErrorCode::BadRequest,
+                        "unable to decrypt message, no cipher params",
+                    )
+                })?;
+                let params = caps.name("params").ok_or_else(|| {
+                    Error::new(ErrorCode::InvalidHeader, "Invalid encoding; missing params")
+                })?;
+                if params.as_str() != cipher.algorithm() {
+                    return Err(Error::new(
+                        ErrorCode::BadRequest,
+                        "unable to decrypt message, incompatible cipher params",
+                    ));
+                }
+                cipher.decrypt(data).map(Into::into)
+            }
+            _ => Err(Error::new(
+                ErrorCode::InvalidMessageDataOrEncoding,
+                "invalid cipher message data",
+            )),
+        },
+        _ => Err(Error::new(
+            ErrorCode::InvalidMessageDataOrEncoding,
+            "invalid message encoding",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::CipherParams;
+
+    fn cipher(key_len: crate::crypto::KeyLen) -> CipherParams {
+        CipherParams::builder().key_len(key_len).build().unwrap()
+    }
+
+    #[test]
+    fn encode_records_each_encoding_in_order() {
+        let cipher = cipher(crate::crypto::KeyLen::Bits256);
+        let mut data = Data::from(serde_json::json!({"a": 1}));
+        let mut encoding = Encoding::None;
+
+        encode(
+            &mut data,
+            &mut encoding,
+            &Format::JSON,
+            Some(&cipher as &dyn Cipher),
+        )
+        .unwrap();
+
+        assert!(matches!(data, Data::String(_)));
+        assert_eq!(
+            encoding,
+            Encoding::Some("json/utf-8/cipher+aes-256-cbc/base64".to_string())
+        );
+
+        let opts = ChannelOptions::new().cipher(cipher);
+        decode(&mut data, &mut encoding, Some(&opts)).unwrap();
+        assert_eq!(data, Data::from(serde_json::json!({"a": 1})));
+        assert_eq!(encoding, Encoding::None);
+    }
+
+    #[test]
+    fn decode_without_cipher_leaves_encrypted_data() {
+        let cipher = cipher(crate::crypto::KeyLen::Bits128);
+        let mut data = Data::from("a string");
+        let mut encoding = Encoding::None;
+        encode(
+            &mut data,
+            &mut encoding,
+            &Format::JSON,
+            Some(&cipher as &dyn Cipher),
+        )
+        .unwrap();
+
+        let err = decode(&mut data, &mut encoding, None).expect_err("Expected decode to fail");
+
+        assert_eq!(err.code, ErrorCode::BadRequest);
+        assert!(matches!(data, Data::Binary(_)));
+        assert_eq!(
+            encoding,
+            Encoding::Some("utf-8/cipher+aes-128-cbc".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_unknown_encoding_fails() {
+        let mut data = Data::from("data");
+        let mut encoding = Encoding::Some("utf-8/custom".to_string());
+
+        let err = decode(&mut data, &mut encoding, None).expect_err("Expected decode to fail");
+
+        assert_eq!(err.code, ErrorCode::InvalidMessageDataOrEncoding);
+        assert_eq!(encoding, Encoding::Some("utf-8/custom".to_string()));
+    }
+}
@@ -0,0 +1,234 @@
+use std::io::{Read, Write};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::error::{Error, ErrorCode};
+use crate::rest::{Data, Message};
+use crate::{json, Result};
+
+/// The extras.headers key used to mark message data as compressed.
+pub(crate) const CONTENT_ENCODING_HEADER: &str = "content-encoding";
+
+/// The extras.headers key used to record the type of compressed message data
+/// so it can be restored when decompressing.
+pub(crate) const CONTENT_TYPE_HEADER: &str = "content-type";
+
+/// The default maximum size of decompressed message data, which stops a
+/// small compressed message from expanding to exhaust memory.
+pub(crate) const DEFAULT_MAX_DECOMPRESSED_SIZE: u64 = 1024 * 1024;
+
+const GZIP: &str = "gzip";
+const TEXT_PLAIN: &str = "text/plain; charset=utf-8";
+const APPLICATION_JSON: &str = "application/json";
+
+/// Gzip the message data, replacing it with the compressed binary data and
+/// marking it as compressed in extras.headers.
+pub(crate) fn compress(msg: &mut Message) -> Result<()> {
+    let (data, content_type) = match &msg.data {
+        Data::String(s) => (s.as_bytes().to_vec(), Some(TEXT_PLAIN)),
+        Data::JSON(v) => (serde_json::to_vec(v)?, Some(APPLICATION_JSON)),
+        Data::Binary(b) => (b.to_vec(), None),
+        Data::None => return Ok(()),
+    };
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(&data)
+        .and_then(|_| encoder.finish())
+        .map(|compressed| msg.data = compressed.into())
+        .map_err(|err| {
+            Error::with_cause(
+                ErrorCode::InvalidMessageDataOrEncoding,
+                err,
+                "failed to compress message data",
+            )
+        })?;
+
+    let headers = headers_mut(msg)?;
+    headers.insert(CONTENT_ENCODING_HEADER.to_string(), GZIP.into());
+    if let Some(content_type) = content_type {
+        headers.insert(CONTENT_TYPE_HEADER.to_string(), content_type.into());
+    }
+
+    Ok(())
+}
+
+/// Decompress the message data if it is marked as gzipped in extras.headers,
+/// restoring string or JSON data based on the recorded content-type.
+///
+/// Fails with InvalidMessageDataOrEncoding if the decompressed data is larger
+/// than max_size bytes.
+pub(crate) fn decompress(msg: &mut Message, max_size: u64) -> Result<()> {
+    let headers = match msg
+        .extras
+        .as_ref()
+        .and_then(|extras| extras.get("headers"))
+        .and_then(json::Value::as_object)
+    {
+        Some(headers) => headers,
+        None => return Ok(()),
+    };
+
+    if headers
+        .get(CONTENT_ENCODING_HEADER)
+        .and_then(json::Value::as_str)
+        != Some(GZIP)
+    {
+        return Ok(());
+    }
+
+    let compressed = match &msg.data {
+        Data::Binary(b) => b,
+        _ => return Ok(()),
+    };
+
+    // Read at most one byte over the limit, which is enough to tell whether
+    // the data exceeds it without decompressing any more.
+    let mut data = Vec::new();
+    GzDecoder::new(compressed.as_slice())
+        .take(max_size.saturating_add(1))
+        .read_to_end(&mut data)
+        .map_err(|err| {
+            Error::with_cause(
+                ErrorCode::InvalidMessageDataOrEncoding,
+                err,
+                "failed to decompress message data",
+            )
+        })?;
+    if data.len() as u64 > max_size {
+        return Err(Error::new(
+            ErrorCode::InvalidMessageDataOrEncoding,
+            format!(
+                "decompressed message data exceeds the maximum size of {} bytes",
+                max_size
+            ),
+        ));
+    }
+
+    msg.data = match headers
+        .get(CONTENT_TYPE_HEADER)
+        .and_then(json::Value::as_str)
+    {
+        Some(TEXT_PLAIN) => String::from_utf8(data)
+            .map_err(|err| err.utf8_error())?
+            .into(),
+        Some(APPLICATION_JSON) => serde_json::from_slice::<json::Value>(&data)?.into(),
+        _ => data.into(),
+    };
+
+    Ok(())
+}
+
+/// Returns the extras.headers object of the message, creating it if needed.
+fn headers_mut(msg: &mut Message) -> Result<&mut json::Map> {
+    msg.extras
+        .get_or_insert_with(json::Map::new)
+        .entry("headers")
+        .or_insert_with(|| json::Value::Object(json::Map::new()))
+        .as_object_mut()
+        .ok_or_else(|| {
+            Error::new(
+                ErrorCode::InvalidMessageDataOrEncoding,
+                "message extras.headers must be an object",
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(data: Data) {
+        let mut msg = Message {
+            data: data.clone(),
+            ..Default::default()
+        };
+
+        compress(&mut msg).unwrap();
+        assert!(matches!(msg.data, Data::Binary(_)));
+
+        decompress(&mut msg, DEFAULT_MAX_DECOMPRESSED_SIZE).unwrap();
+        assert_eq!(msg.data, data);
+    }
+
+    #[test]
+    fn compress_roundtrip_string() {
+        roundtrip(Data::String("a string".repeat(100)));
+    }
+
+    #[test]
+    fn compress_roundtrip_json() {
+        roundtrip(Data::JSON(serde_json::json!({"a": [1, 2, 3], "b": "c"})));
+    }
+
+    #[test]
+    fn compress_roundtrip_binary() {
+        roundtrip(vec![0x1, 0x2, 0x3, 0x4].into());
+    }
+
+    #[test]
+    fn compress_preserves_existing_headers() {
+        let mut extras = json::Map::new();
+        extras.insert(
+            "headers".to_string(),
+            serde_json::json!({"some": "metadata"}),
+        );
+        let mut msg = Message {
+            data: "a string".into(),
+            extras: Some(extras),
+            ..Default::default()
+        };
+
+        compress(&mut msg).unwrap();
+
+        let headers = msg.extras.as_ref().unwrap()["headers"].as_object().unwrap();
+        assert_eq!(headers["some"], "metadata");
+        assert_eq!(headers[CONTENT_ENCODING_HEADER], "gzip");
+    }
+
+    #[test]
+    fn decompress_ignores_uncompressed_messages() {
+        let mut msg = Message {
+            data: vec![0x1, 0x2].into(),
+            ..Default::default()
+        };
+
+        decompress(&mut msg, DEFAULT_MAX_DECOMPRESSED_SIZE).unwrap();
+        assert_eq!(msg.data, vec![0x1, 0x2].into());
+    }
+
+    #[test]
+    fn decompress_rejects_data_over_max_size() {
+        let mut msg = Message {
+            data: Data::Binary(vec![0; 10 * 1024 * 1024].into()),
+            ..Default::default()
+        };
+        compress(&mut msg).unwrap();
+        let compressed_len = match &msg.data {
+            Data::Binary(b) => b.len(),
+            _ => unreachable!(),
+        };
+        assert!(compressed_len < 64 * 1024, "{}", compressed_len);
+
+        let err = decompress(&mut msg, DEFAULT_MAX_DECOMPRESSED_SIZE)
+            .expect_err("Expected decompressing to fail");
+
+        assert_eq!(err.code, ErrorCode::InvalidMessageDataOrEncoding);
+        assert!(matches!(&msg.data, Data::Binary(b) if b.len() == compressed_len));
+    }
+
+    #[test]
+    fn decompress_allows_data_of_max_size() {
+        let data = Data::Binary(vec![0; 1024].into());
+        let mut msg = Message {
+            data: data.clone(),
+            ..Default::default()
+        };
+        compress(&mut msg).unwrap();
+
+        decompress(&mut msg, 1024).unwrap();
+        assert_eq!(msg.data, data);
+    }
+}
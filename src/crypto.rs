@@ -1,4 +1,6 @@
 use std::convert::TryFrom;
+use std::fmt;
+use std::sync::Arc;
 
 use aes::cipher::block_padding::Pkcs7;
 use aes::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
@@ -14,6 +16,71 @@ type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
 
 pub(crate) type IV = [u8; 16];
 
+/// A cipher used to encrypt published message data and decrypt retrieved
+/// message data, which is set in a channel's ChannelOptions.
+///
+/// CipherParams implements AES-CBC as specified for Ably's client libraries,
+/// and a custom Cipher can be used instead, for example to encrypt with a
+/// key held in a hardware security module. The client records each
+/// encryption in the message encoding as `cipher+<algorithm>`, and only
+/// decrypts data with the algorithm of the channel's Cipher.
+///
+/// # Example
+///
+/// ```
+/// use ably::crypto::Cipher;
+/// use ably::rest::ChannelOptions;
+///
+/// #[derive(Debug)]
+/// struct Xor(u8);
+///
+/// impl Cipher for Xor {
+///     fn algorithm(&self) -> String {
+///         "xor".to_string()
+///     }
+///
+///     fn encrypt(&self, data: &[u8]) -> ably::Result<Vec<u8>> {
+///         Ok(data.iter().map(|b| b ^ self.0).collect())
+///     }
+///
+///     fn decrypt(&self, data: &[u8]) -> ably::Result<Vec<u8>> {
+///         self.encrypt(data)
+///     }
+/// }
+///
+/// let opts = ChannelOptions::new().cipher(Xor(42));
+/// ```
+pub trait Cipher: fmt::Debug + Send + Sync {
+    /// Returns the name of the algorithm, such as "aes-128-cbc", which is
+    /// included in the encoding of encrypted data.
+    fn algorithm(&self) -> String;
+
+    /// Encrypt the given data.
+    fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>>;
+
+    /// Decrypt the given data, which was encrypted with the same algorithm.
+    fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>>;
+
+    /// Returns the encoding recorded for data encrypted with the cipher.
+    fn encoding(&self) -> String {
+        format!("cipher+{}", self.algorithm())
+    }
+}
+
+impl<T: Cipher + ?Sized> Cipher for Arc<T> {
+    fn algorithm(&self) -> String {
+        (**self).algorithm()
+    }
+
+    fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        (**self).encrypt(data)
+    }
+
+    fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        (**self).decrypt(data)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum CipherParams {
     /// A 128 bit AES key.
@@ -45,6 +112,38 @@ pub enum KeyLen {
     Bits256,
 }
 
+impl KeyLen {
+    /// Returns the length of the key in bytes.
+    fn bytes(&self) -> usize {
+        match self {
+            Self::Bits128 => 16,
+            Self::Bits256 => 32,
+        }
+    }
+}
+
+/// Returns a random key of the given length, suitable for encrypting the
+/// messages of a channel with CipherParams (RSE2).
+///
+/// The key should be shared with the other clients using the channel so
+/// that they can decrypt its messages, for example as a base64 string.
+///
+/// # Example
+///
+/// ```
+/// use ably::crypto::{self, CipherParams, KeyLen};
+///
+/// let key = crypto::generate_random_key(KeyLen::Bits256);
+/// let cipher = CipherParams::try_from(key.as_slice())?;
+/// assert_eq!(cipher.algorithm(), "aes-256-cbc");
+/// # Ok::<(), ably::Error>(())
+/// ```
+pub fn generate_random_key(len: KeyLen) -> Vec<u8> {
+    let mut key = vec![0; len.bytes()];
+    thread_rng().fill_bytes(&mut key);
+    key
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct CipherParamsBuilder {
     kind: CipherKind,
@@ -86,26 +185,20 @@ impl CipherParamsBuilder {
         let cipher = match self.kind {
             CipherKind::AesCbc => match len {
                 Some(KeyLen::Bits128) => {
-                    let key = if let Some(key) = self.key {
-                        key.try_into()
-                            .map_err(|_| Error::new(ErrorCode::BadRequest, "Invalid key size"))?
-                    } else {
-                        let mut data = [0; 16];
-                        thread_rng().fill_bytes(&mut data);
-                        data
-                    };
+                    let key = self
+                        .key
+                        .unwrap_or_else(|| generate_random_key(KeyLen::Bits128))
+                        .try_into()
+                        .map_err(|_| Error::new(ErrorCode::BadRequest, "Invalid key size"))?;
 
                     CipherParams::Aes128Cbc(key)
                 }
                 Some(KeyLen::Bits256) | None => {
-                    let key = if let Some(key) = self.key {
-                        key.try_into()
-                            .map_err(|_| Error::new(ErrorCode::BadRequest, "Invalid key size"))?
-                    } else {
-                        let mut data = [0; 32];
-                        thread_rng().fill_bytes(&mut data);
-                        data
-                    };
+                    let key = self
+                        .key
+                        .unwrap_or_else(|| generate_random_key(KeyLen::Bits256))
+                        .try_into()
+                        .map_err(|_| Error::new(ErrorCode::BadRequest, "Invalid key size"))?;
                     CipherParams::Aes256Cbc(key)
                 }
             },
@@ -140,7 +233,12 @@ impl CipherParams {
     }
 
     pub fn algorithm(&self) -> String {
-        format!("aes-{}-cbc", self.bits())
+        format!("aes-{}-{}", self.bits(), self.mode())
+    }
+
+    /// Returns the block cipher mode, which is always "cbc".
+    pub fn mode(&self) -> &'static str {
+        "cbc"
     }
 
     pub(crate) fn block_size(&self) -> usize {
@@ -222,6 +320,22 @@ impl CipherParams {
     }
 }
 
+/// Encrypts with AES-CBC using a random IV, which prefixes the encrypted
+/// data.
+impl Cipher for CipherParams {
+    fn algorithm(&self) -> String {
+        CipherParams::algorithm(self)
+    }
+
+    fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        CipherParams::encrypt(self, None, data)
+    }
+
+    fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        CipherParams::decrypt(self, &mut data.to_vec())
+    }
+}
+
 impl TryFrom<&str> for CipherParams {
     type Error = Error;
 
@@ -282,6 +396,16 @@ mod tests {
         assert_eq!(key.bits(), 256);
     }
 
+    #[test]
+    fn generate_random_key_returns_distinct_keys() {
+        let key = generate_random_key(KeyLen::Bits128);
+        assert_eq!(key.len(), 16);
+        assert_ne!(key, generate_random_key(KeyLen::Bits128));
+
+        let cipher: CipherParams = generate_random_key(KeyLen::Bits256).try_into().unwrap();
+        assert_eq!(cipher.encoding(), "cipher+aes-256-cbc");
+    }
+
     #[derive(Deserialize)]
     struct CryptoData {
         key: String,
@@ -298,15 +422,13 @@ mod tests {
         }
 
         fn opts(&self) -> rest::ChannelOptions {
-            rest::ChannelOptions {
-                cipher: Some(
-                    CipherParams::builder()
-                        .string(&self.key)
-                        .unwrap()
-                        .build()
-                        .unwrap(),
-                ),
-            }
+            rest::ChannelOptions::new().cipher(
+                CipherParams::builder()
+                    .string(&self.key)
+                    .unwrap()
+                    .build()
+                    .unwrap(),
+            )
         }
 
         fn cipher(&self) -> CipherParams {
@@ -327,17 +449,31 @@ mod tests {
         encrypted: json::Value,
     }
 
+    /// Encrypts with the fixture IV rather than a random one.
+    #[derive(Debug)]
+    struct FixedIv(CipherParams, Vec<u8>);
+
+    impl Cipher for FixedIv {
+        fn algorithm(&self) -> String {
+            self.0.algorithm()
+        }
+
+        fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+            self.0.encrypt(Some(self.1.clone()), data)
+        }
+
+        fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+            Cipher::decrypt(&self.0, data)
+        }
+    }
+
     #[tokio::test]
     async fn encrypt_message_128() -> Result<()> {
         let data = CryptoData::load("crypto-data-128.json");
-        let cipher = data.cipher();
+        let cipher = FixedIv(data.cipher(), data.cipher_iv());
         for item in data.items.iter() {
             let mut msg = rest::Message::from_encoded(item.encoded.clone(), None)?;
-            msg.encode_with_iv(
-                &rest::Format::MessagePack,
-                Some(&cipher),
-                Some(data.cipher_iv().clone()),
-            )?;
+            msg.encode(&rest::Format::MessagePack, Some(&cipher))?;
             let expected = rest::Message::from_encoded(item.encrypted.clone(), None)?;
             assert_eq!(msg.data, expected.data);
             assert_eq!(msg.encoding, expected.encoding);
@@ -348,14 +484,10 @@ mod tests {
     #[tokio::test]
     async fn encrypt_message_256() -> Result<()> {
         let data = CryptoData::load("crypto-data-256.json");
-        let cipher = data.cipher();
+        let cipher = FixedIv(data.cipher(), data.cipher_iv());
         for item in data.items.iter() {
             let mut msg = rest::Message::from_encoded(item.encoded.clone(), None)?;
-            msg.encode_with_iv(
-                &rest::Format::MessagePack,
-                Some(&cipher),
-                Some(data.cipher_iv().clone()),
-            )?;
+            msg.encode(&rest::Format::MessagePack, Some(&cipher))?;
             let expected = rest::Message::from_encoded(item.encrypted.clone(), None)?;
             assert_eq!(msg.data, expected.data);
             assert_eq!(msg.encoding, expected.encoding);
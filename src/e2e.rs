@@ -0,0 +1,542 @@
+//! An end-to-end harness which runs scripted scenarios against Ably, so that
+//! applications and CI environments can certify their integration with the
+//! library in one call.
+//!
+//! Enabled with the `e2e` feature, [run] executes each scenario against
+//! either a temporary app in the Ably Sandbox environment, or an in-memory
+//! mock of the REST API which needs no network access:
+//!
+//! * `publish_history` publishes a message and reads it back from history
+//! * `token_renewal` checks a token is reused until it expires, and then
+//!   renewed using the auth callback
+//! * `fallback` checks requests are retried against a fallback host when the
+//!   primary host is unavailable
+//!
+//! # Example
+//!
+//! ```
+//! # #[tokio::main]
+//! # async fn main() {
+//! use ably::e2e::{self, Target};
+//!
+//! let report = e2e::run(Target::Mock).await;
+//! assert!(report.is_success(), "{}", report);
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use chrono::{DateTime, Duration, Utc};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use crate::auth::{self, TokenDetails, TokenMetadata, TokenParams, TokenRequest};
+use crate::error::ErrorCode;
+use crate::http::Method;
+use crate::mock::MockTransport;
+use crate::rest::{Message, Rest};
+use crate::{json, ClientOptions, Error, Result};
+
+/// The Ably Sandbox environment.
+const SANDBOX_ENVIRONMENT: &str = "sandbox";
+
+/// The host used as the primary host in the fallback scenario, which never
+/// resolves, and which the mock responds to with 503 Service Unavailable.
+const OUTAGE_HOST: &str = "outage.e2e.invalid";
+
+/// The ttl of the tokens requested in the token_renewal scenario against
+/// the Sandbox, which allows for skew between the local and Ably clocks.
+const SANDBOX_TOKEN_TTL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// The ttl of the tokens requested in the token_renewal scenario against the
+/// mock, which shares the local clock.
+const MOCK_TOKEN_TTL: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// The number of times the publish_history scenario reads history waiting
+/// for the published message to appear.
+const HISTORY_ATTEMPTS: usize = 5;
+
+/// The environment the scenarios are run against.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    /// A temporary app created in the Ably Sandbox environment.
+    Sandbox,
+    /// An in-memory mock of the Ably REST API.
+    Mock,
+}
+
+/// The outcome of running the scenarios.
+#[derive(Debug)]
+pub struct Report {
+    pub scenarios: Vec<ScenarioReport>,
+}
+
+impl Report {
+    /// Returns whether every scenario succeeded.
+    pub fn is_success(&self) -> bool {
+        self.scenarios.iter().all(|s| s.result.is_ok())
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for scenario in &self.scenarios {
+            writeln!(f, "{}", scenario)?;
+        }
+        Ok(())
+    }
+}
+
+/// The outcome of running a single scenario.
+#[derive(Debug)]
+pub struct ScenarioReport {
+    pub name: &'static str,
+    pub duration: std::time::Duration,
+    pub result: Result<()>,
+}
+
+impl fmt::Display for ScenarioReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.result {
+            Ok(()) => write!(f, "ok     {} ({:?})", self.name, self.duration),
+            Err(err) => write!(f, "FAILED {} ({:?}): {}", self.name, self.duration, err),
+        }
+    }
+}
+
+/// Run every scenario against the given target.
+///
+/// Scenarios are run in order, and a failing scenario doesn't prevent the
+/// rest from running. If the target can't be set up, for example because
+/// the Sandbox app can't be created, the report contains a single failed
+/// `setup` scenario.
+pub async fn run(target: Target) -> Report {
+    let start = Instant::now();
+    let env = match Env::new(target).await {
+        Ok(env) => env,
+        Err(err) => {
+            return Report {
+                scenarios: vec![ScenarioReport {
+                    name: "setup",
+                    duration: start.elapsed(),
+                    result: Err(err),
+                }],
+            }
+        }
+    };
+
+    let scenarios = vec![
+        scenario("publish_history", publish_history(&env)).await,
+        scenario("token_renewal", token_renewal(&env)).await,
+        scenario("fallback", fallback(&env)).await,
+    ];
+
+    Report { scenarios }
+}
+
+async fn scenario(name: &'static str, fut: impl Future<Output = Result<()>>) -> ScenarioReport {
+    let start = Instant::now();
+    let result = fut.await;
+    ScenarioReport {
+        name,
+        duration: start.elapsed(),
+        result,
+    }
+}
+
+/// Publish a message with a unique name to a new channel, and check it's
+/// returned by the channel's history.
+async fn publish_history(env: &Env) -> Result<()> {
+    let client = env.client(env.options()?)?;
+    let channel = client.channels().get(random_string());
+    let name = random_string();
+
+    channel.publish().name(&name).string("e2e").send().await?;
+
+    for _ in 0..HISTORY_ATTEMPTS {
+        let items = channel.history().send().await?.items().await?;
+        if items.iter().any(|msg| msg.name.as_deref() == Some(&name)) {
+            return Ok(());
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    }
+
+    Err(failure(format!(
+        "published message {} missing from history",
+        name
+    )))
+}
+
+/// Check a short lived token is reused until it expires, and then renewed
+/// using the auth callback.
+async fn token_renewal(env: &Env) -> Result<()> {
+    let callback = Arc::new(CountingCallback {
+        key: env.key.clone(),
+        ttl: Duration::from_std(env.token_ttl)
+            .map_err(|err| failure(format!("invalid token ttl: {}", err)))?,
+        calls: AtomicUsize::new(0),
+    });
+    let opts = env
+        .configure(ClientOptions::with_auth_callback(callback.clone()))?
+        .token_renewal_margin(std::time::Duration::ZERO);
+    let client = env.client(opts)?;
+
+    client.time().await?;
+    client.time().await?;
+    if callback.calls() != 1 {
+        return Err(failure(format!(
+            "expected the token to be reused, but it was requested {} times",
+            callback.calls()
+        )));
+    }
+
+    tokio::time::sleep(env.token_ttl * 2).await;
+
+    client.time().await?;
+    if callback.calls() != 2 {
+        return Err(failure(format!(
+            "expected the expired token to be renewed, but it was requested {} times",
+            callback.calls()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Check an authenticated request succeeds against a fallback host when the
+/// primary host is unavailable.
+async fn fallback(env: &Env) -> Result<()> {
+    let opts = ClientOptions::with_key(env.key.clone())
+        .rest_host(OUTAGE_HOST)?
+        .fallback_hosts(vec![env.options()?.rest_host]);
+    let client = env.client(opts)?;
+
+    client.time().await?;
+    client
+        .channels()
+        .get(random_string())
+        .publish()
+        .string("e2e")
+        .send()
+        .await
+}
+
+fn failure(message: String) -> Error {
+    Error::new(ErrorCode::InternalError, message)
+}
+
+fn random_string() -> String {
+    thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(12)
+        .map(char::from)
+        .collect()
+}
+
+/// The target the scenarios are run against, with a key for its app.
+struct Env {
+    key: auth::Key,
+    environment: Option<&'static str>,
+    token_ttl: std::time::Duration,
+    mock: Option<Arc<MockTransport>>,
+}
+
+#[derive(Deserialize)]
+struct SandboxApp {
+    keys: Vec<auth::Key>,
+}
+
+impl Env {
+    async fn new(target: Target) -> Result<Self> {
+        match target {
+            Target::Sandbox => {
+                let app: SandboxApp = ClientOptions::new("aaaaaa.bbbbbb:cccccc")
+                    .environment(SANDBOX_ENVIRONMENT)?
+                    .rest()?
+                    .request(Method::POST, "/apps")
+                    .authenticate(false)
+                    .body(&serde_json::json!({"keys": [{}]}))
+                    .send()
+                    .await?
+                    .body()
+                    .await?;
+                let key = app.keys.into_iter().next().ok_or_else(|| {
+                    failure("the sandbox app was created without a key".to_string())
+                })?;
+                Ok(Self {
+                    key,
+                    environment: Some(SANDBOX_ENVIRONMENT),
+                    token_ttl: SANDBOX_TOKEN_TTL,
+                    mock: None,
+                })
+            }
+            Target::Mock => {
+                let key =
+                    auth::Key::new(&format!("e2eapp.{}:{}", random_string(), random_string()))?;
+                let ably = MockAbly::new(key.clone());
+                Ok(Self {
+                    key,
+                    environment: None,
+                    token_ttl: MOCK_TOKEN_TTL,
+                    mock: Some(Arc::new(
+                        MockTransport::new().respond_with(move |req| ably.respond(req)),
+                    )),
+                })
+            }
+        }
+    }
+
+    /// Returns the given options configured for the target's environment.
+    fn configure(&self, opts: ClientOptions) -> Result<ClientOptions> {
+        match self.environment {
+            Some(environment) => opts.environment(environment),
+            None => Ok(opts),
+        }
+    }
+
+    /// Returns options using the key against the target's environment.
+    fn options(&self) -> Result<ClientOptions> {
+        self.configure(ClientOptions::with_key(self.key.clone()))
+    }
+
+    fn client(&self, opts: ClientOptions) -> Result<Rest> {
+        match &self.mock {
+            Some(mock) => Rest::with_http_executor(opts, mock.clone()),
+            None => opts.rest(),
+        }
+    }
+}
+
+/// An AuthCallback which signs TokenRequests with a short ttl, counting how
+/// many tokens were requested.
+struct CountingCallback {
+    key: auth::Key,
+    ttl: Duration,
+    calls: AtomicUsize,
+}
+
+impl CountingCallback {
+    fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+}
+
+impl auth::AuthCallback for CountingCallback {
+    fn token<'a>(
+        &'a self,
+        _params: &'a TokenParams,
+    ) -> Pin<Box<dyn Send + Future<Output = Result<auth::RequestOrDetails>> + 'a>> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        let params = TokenParams::new().ttl(self.ttl);
+        let req = self.key.sign(&params).map(auth::RequestOrDetails::Request);
+        Box::pin(async move { req })
+    }
+}
+
+/// A stateful in-memory mock of the parts of the Ably REST API used by the
+/// scenarios, which stores published messages, issues tokens for
+/// TokenRequests signed with its key, and rejects expired tokens.
+///
+/// Requests are routed to the mock by a MockTransport.
+struct MockAbly {
+    key: auth::Key,
+    channels: Mutex<HashMap<String, Vec<Message>>>,
+    tokens: Mutex<HashMap<String, DateTime<Utc>>>,
+}
+
+/// The body of a publish request, which is either a single message or an
+/// array of messages.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Published {
+    One(Box<Message>),
+    Many(Vec<Message>),
+}
+
+impl MockAbly {
+    fn new(key: auth::Key) -> Self {
+        Self {
+            key,
+            channels: Mutex::new(HashMap::new()),
+            tokens: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the status code and JSON body of the response to the given
+    /// request.
+    fn respond(&self, req: &reqwest::Request) -> (u16, json::Value) {
+        self.route(req).unwrap_or_else(|err| {
+            let status = err.status_code.unwrap_or(500);
+            let body = serde_json::json!({
+                "error": {"code": err.code.code(), "statusCode": status, "message": err.message}
+            });
+            (status as u16, body)
+        })
+    }
+
+    fn route(&self, req: &reqwest::Request) -> Result<(u16, json::Value)> {
+        if req.url().host_str() == Some(OUTAGE_HOST) {
+            return Err(Error::with_status(
+                ErrorCode::InternalError,
+                503,
+                "simulated outage",
+            ));
+        }
+
+        let segments: Vec<&str> = req
+            .url()
+            .path()
+            .trim_start_matches('/')
+            .split('/')
+            .collect();
+        match (req.method(), &segments[..]) {
+            (&Method::GET, ["time"]) => {
+                Ok((200, serde_json::json!([Utc::now().timestamp_millis()])))
+            }
+            (&Method::POST, ["keys", name, "requestToken"]) => self.request_token(req, name),
+            (&Method::POST, ["channels", channel, "messages"]) => {
+                self.authenticate(req)?;
+                self.publish(req, channel)
+            }
+            (&Method::GET, ["channels", channel, "history"]) => {
+                self.authenticate(req)?;
+                self.history(channel)
+            }
+            _ => Err(not_found(req)),
+        }
+    }
+
+    fn request_token(&self, req: &reqwest::Request, name: &str) -> Result<(u16, json::Value)> {
+        let token_req: TokenRequest = decode_body(req)?;
+        if name != self.key.name || token_req.key_name != self.key.name {
+            return Err(Error::with_status(
+                ErrorCode::InvalidCredentials,
+                401,
+                "TokenRequest signed with an unknown key",
+            ));
+        }
+
+        let issued = Utc::now();
+        let expires = issued + token_req.ttl;
+        let token = random_string();
+        self.tokens.lock().unwrap().insert(token.clone(), expires);
+
+        let details = TokenDetails {
+            token,
+            metadata: Some(TokenMetadata {
+                expires,
+                issued,
+                capability: token_req.capability,
+                client_id: token_req.client_id,
+            }),
+        };
+        Ok((200, serde_json::to_value(details)?))
+    }
+
+    fn publish(&self, req: &reqwest::Request, channel: &str) -> Result<(u16, json::Value)> {
+        let mut messages = match decode_body(req)? {
+            Published::One(msg) => vec![*msg],
+            Published::Many(msgs) => msgs,
+        };
+        for msg in messages.iter_mut() {
+            msg.timestamp.get_or_insert_with(Utc::now);
+        }
+        self.channels
+            .lock()
+            .unwrap()
+            .entry(channel.to_string())
+            .or_default()
+            .extend(messages);
+        Ok((201, serde_json::json!({})))
+    }
+
+    fn history(&self, channel: &str) -> Result<(u16, json::Value)> {
+        let mut messages = self
+            .channels
+            .lock()
+            .unwrap()
+            .get(channel)
+            .cloned()
+            .unwrap_or_default();
+        messages.reverse();
+        Ok((200, serde_json::to_value(messages)?))
+    }
+
+    /// Check the request has either Basic auth with the mock's key, or
+    /// Bearer auth with an unexpired token issued by the mock.
+    fn authenticate(&self, req: &reqwest::Request) -> Result<()> {
+        let header = req
+            .headers()
+            .get(reqwest::header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+
+        if let Some(encoded) = header.strip_prefix("Basic ") {
+            let expected = base64::encode(format!("{}:{}", self.key.name, self.key.value));
+            if encoded == expected {
+                return Ok(());
+            }
+        } else if let Some(token) = header.strip_prefix("Bearer ") {
+            match self.tokens.lock().unwrap().get(token) {
+                Some(expires) if *expires > Utc::now() => return Ok(()),
+                Some(_) => {
+                    return Err(Error::with_status(
+                        ErrorCode::TokenExpired,
+                        401,
+                        "Token expired",
+                    ))
+                }
+                None => {}
+            }
+        }
+
+        Err(Error::with_status(
+            ErrorCode::InvalidCredentials,
+            401,
+            "Invalid credentials",
+        ))
+    }
+}
+
+fn not_found(req: &reqwest::Request) -> Error {
+    Error::with_status(
+        ErrorCode::NotFound,
+        404,
+        format!("no mock route for {} {}", req.method(), req.url().path()),
+    )
+}
+
+/// Deserialize a request body as either MessagePack or JSON depending on
+/// its Content-Type.
+fn decode_body<T: DeserializeOwned>(req: &reqwest::Request) -> Result<T> {
+    let body = req.body().and_then(|b| b.as_bytes()).unwrap_or_default();
+    let msgpack = req
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .is_some_and(|v| v == "application/x-msgpack");
+    if msgpack {
+        Ok(rmp_serde::from_slice(body)?)
+    } else {
+        Ok(serde_json::from_slice(body)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn run_mock_succeeds() {
+        let report = run(Target::Mock).await;
+        assert!(report.is_success(), "{}", report);
+        assert_eq!(report.scenarios.len(), 3);
+    }
+}
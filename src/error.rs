@@ -1,4 +1,5 @@
 use std::fmt::{self, Debug, Display};
+use std::time::Duration;
 
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
@@ -174,8 +175,19 @@ pub struct Error {
     pub status_code: Option<u32>,
 
     /// Link to Ably documenation with more information about the error.
+    #[serde(default)]
     pub href: String,
 
+    /// The request_id query param of the request which resulted in this
+    /// error, if ClientOptions.add_request_ids is set (RSC7c).
+    #[serde(rename(deserialize = "requestId"))]
+    pub request_id: Option<String>,
+
+    /// How long the server asked the client to wait before retrying, taken
+    /// from the Retry-After header of a 429 or 503 response.
+    #[serde(skip)]
+    pub retry_after: Option<Duration>,
+
     /// Underlying error
     #[serde(skip)]
     pub cause: Option<Box<dyn std::error::Error + Send + Sync>>,
@@ -195,6 +207,8 @@ impl Error {
             message: message.into(),
             status_code: None,
             href: format!("https://help.ably.io/error/{}", code.code()),
+            request_id: None,
+            retry_after: None,
             cause: None,
         }
     }
@@ -206,6 +220,8 @@ impl Error {
             message: message.into(),
             status_code: Some(status_code),
             href: format!("https://help.ably.io/error/{}", code.code()),
+            request_id: None,
+            retry_after: None,
             cause: None,
         }
     }
@@ -219,15 +235,38 @@ impl Error {
             message: message.into(),
             status_code: None,
             href: format!("https://help.ably.io/error/{}", code.code()),
+            request_id: None,
+            retry_after: None,
             cause: Some(Box::new(cause)),
         }
     }
+
+    /// Returns a ServiceUnavailable if this error is the result of a 503
+    /// Service Unavailable response, for example during an Ably maintenance
+    /// window.
+    pub fn service_unavailable(&self) -> Option<ServiceUnavailable> {
+        match self.status_code {
+            Some(503) => Some(ServiceUnavailable {
+                retry_after: self.retry_after,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// The details of a 503 Service Unavailable error, returned by
+/// Error::service_unavailable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ServiceUnavailable {
+    /// How long the server asked the client to wait before retrying, if the
+    /// response included a Retry-After header.
+    pub retry_after: Option<Duration>,
 }
 
 impl fmt::Display for Error {
     /// Format the error like:
     ///
-    /// [ErrorInfo: <msg>; statusCode=<statusCode>; code=<code>; see <url>]
+    /// [ErrorInfo: <msg>; statusCode=<statusCode>; code=<code>; requestId=<requestId>; see <url>]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "[ErrorInfo")?;
         if !self.message.is_empty() {
@@ -240,6 +279,9 @@ impl fmt::Display for Error {
             write!(f, "; statusCode={}", code)?;
         }
         write!(f, "; code={}", self.code.code())?;
+        if let Some(request_id) = &self.request_id {
+            write!(f, "; requestId={}", request_id)?;
+        }
         if !self.href.is_empty() {
             write!(f, "; see {} ", self.href)?;
         }
@@ -358,6 +400,13 @@ mod tests {
         assert_eq!(format!("{}", err), "[ErrorInfo: error message; statusCode=401; code=40101; see https://help.ably.io/error/40101 ]");
     }
 
+    #[test]
+    fn error_fmt_with_request_id() {
+        let mut err = Error::with_status(ErrorCode::InvalidCredentials, 401, "error message");
+        err.request_id = Some("abc123".to_string());
+        assert_eq!(format!("{}", err), "[ErrorInfo: error message; statusCode=401; code=40101; requestId=abc123; see https://help.ably.io/error/40101 ]");
+    }
+
     #[test]
     fn unkown_code() {
         let err: Error =
@@ -365,6 +414,21 @@ mod tests {
         assert_eq!(err.code, ErrorCode::UnknownError);
     }
 
+    #[test]
+    fn service_unavailable() {
+        let mut err = Error::with_status(ErrorCode::InternalError, 503, "maintenance");
+        err.retry_after = Some(Duration::from_secs(30));
+        assert_eq!(
+            err.service_unavailable(),
+            Some(ServiceUnavailable {
+                retry_after: Some(Duration::from_secs(30))
+            })
+        );
+
+        let err = Error::with_status(ErrorCode::InternalError, 500, "error");
+        assert_eq!(err.service_unavailable(), None);
+    }
+
     #[test]
     fn no_code() {
         let err: Error = serde_json::from_str(r#"{"message": "", "href": ""}"#).unwrap();
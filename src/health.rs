@@ -0,0 +1,128 @@
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+
+use crate::auth::{AuthOptions, Credential};
+use crate::error::{Error, ErrorCode};
+use crate::{http, rest, Result};
+
+/// The result of checking whether a client can reach and authenticate with
+/// Ably, returned by Rest::health_check.
+///
+/// Each check is run independently so that a single report shows every
+/// failing stage, which makes it suitable for use in readiness probes.
+#[derive(Debug)]
+pub struct HealthCheck {
+    /// The REST host which was checked.
+    pub host: String,
+
+    /// The result of resolving the REST host, containing the resolved
+    /// addresses.
+    pub dns: Result<Vec<SocketAddr>>,
+
+    /// The result of sending an unauthenticated GET request to /time, which
+    /// checks the TLS handshake and HTTP connectivity, containing the round
+    /// trip time.
+    pub connection: Result<Duration>,
+
+    /// The result of obtaining a valid token or verifying the API key using
+    /// the client's credentials.
+    pub auth: Result<()>,
+}
+
+impl HealthCheck {
+    /// Returns whether all of the checks succeeded.
+    pub fn is_healthy(&self) -> bool {
+        self.dns.is_ok() && self.connection.is_ok() && self.auth.is_ok()
+    }
+}
+
+pub(crate) async fn check(rest: &rest::Rest) -> HealthCheck {
+    let url = &rest.inner.url;
+    let host = url.host_str().unwrap_or_default().to_string();
+    let port = url.port_or_known_default().unwrap_or(443);
+
+    HealthCheck {
+        dns: check_dns(rest, &host, port).await,
+        connection: check_connection(rest).await,
+        auth: check_auth(rest).await,
+        host,
+    }
+}
+
+async fn check_dns(rest: &rest::Rest, host: &str, port: u16) -> Result<Vec<SocketAddr>> {
+    // Resolve the host the same way the HTTP client does, using any
+    // overrides or custom resolver from the client options.
+    let opts = rest.options();
+    let addrs: Vec<SocketAddr> = if let Some(ips) = opts.dns_overrides.get(host) {
+        ips.iter().map(|ip| SocketAddr::new(*ip, port)).collect()
+    } else if let Some(resolver) = &opts.dns_resolver {
+        resolver
+            .resolve(host)
+            .await?
+            .into_iter()
+            .map(|ip| SocketAddr::new(ip, port))
+            .collect()
+    } else {
+        tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|err| {
+                Error::with_cause(
+                    ErrorCode::ConnectionFailed,
+                    err,
+                    format!("failed to resolve host '{}'", host),
+                )
+            })?
+            .collect()
+    };
+
+    if addrs.is_empty() {
+        return Err(Error::new(
+            ErrorCode::ConnectionFailed,
+            format!("host '{}' resolved to no addresses", host),
+        ));
+    }
+
+    Ok(addrs)
+}
+
+async fn check_connection(rest: &rest::Rest) -> Result<Duration> {
+    let start = Instant::now();
+    rest.request(http::Method::GET, "/time")
+        .authenticate(false)
+        .send()
+        .await?;
+    Ok(start.elapsed())
+}
+
+async fn check_auth(rest: &rest::Rest) -> Result<()> {
+    let options = AuthOptions {
+        token: Some(rest.options().credential.clone()),
+        ..Default::default()
+    };
+
+    // Requesting a token signed with the API key checks that the key is
+    // valid, and requesting a token via a callback or authUrl checks that
+    // a token can be obtained.
+    let token = rest
+        .auth()
+        .request_token(&Default::default(), &options)
+        .await?;
+
+    // A token literal can't be checked without using it, so only check that
+    // it hasn't expired if its expiry is known.
+    if let (Credential::TokenDetails(_), Some(metadata)) =
+        (&rest.options().credential, &token.metadata)
+    {
+        if metadata.expires <= Utc::now() {
+            return Err(Error::with_status(
+                ErrorCode::TokenExpired,
+                401,
+                "token has expired",
+            ));
+        }
+    }
+
+    Ok(())
+}
@@ -1,21 +1,402 @@
+pub use bytes::Bytes;
 pub use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+#[cfg(feature = "__tls")]
+pub use reqwest::Certificate;
 pub use reqwest::Method;
 
 use std::convert::TryFrom;
-use std::fmt::Display;
+use std::fmt::{Debug, Display};
+use std::time::Duration;
 
-use futures::future::FutureExt;
-use futures::stream::{self, Stream, StreamExt};
-use lazy_static::lazy_static;
-use regex::Regex;
+use chrono::{DateTime, Utc};
+use futures::future::{self, BoxFuture, FutureExt};
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
-use crate::error::{Error, ErrorCode};
+use crate::auth::AuthMode;
+use crate::error::{Error, ErrorCode, ServiceUnavailable, WrappedError};
+use crate::log::{self, LogLevel};
 use crate::rest::Decode;
-use crate::{rest, Result};
+use crate::{json, rest, Result};
 
-pub type UrlQuery = Box<[(String, String)]>;
+/// A list of query string or form parameters.
+///
+/// Setting a parameter replaces any existing values with the same name, and
+/// merging parameters replaces the values of any parameters in both.
+///
+/// # Example
+///
+/// ```
+/// use ably::http::Params;
+///
+/// let mut params = Params::from([("limit", "10"), ("direction", "forwards")]);
+/// params.merge(Params::new().set("limit", "100"));
+///
+/// assert_eq!(params.get("limit"), Some("100"));
+/// assert_eq!(params.get("direction"), Some("forwards"));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Params(Vec<(String, String)>);
+
+impl Params {
+    /// Returns an empty set of parameters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a parameter, replacing any existing values with the same name.
+    pub fn set(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.insert(name, value);
+        self
+    }
+
+    /// Set a parameter, replacing any existing values with the same name.
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let name = name.into();
+        self.0.retain(|(n, _)| *n != name);
+        self.0.push((name, value.into()));
+    }
+
+    /// Returns the value of the given parameter, if it is set.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Set all of the given parameters, replacing the values of any which
+    /// are already set.
+    pub fn merge(&mut self, other: Params) {
+        for (name, value) in other.0 {
+            self.insert(name, value);
+        }
+    }
+
+    /// Returns an iterator over the parameter names and values.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+
+    /// Returns whether there are no parameters.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for Params {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut params = Self::new();
+        for (name, value) in iter {
+            params.insert(name, value);
+        }
+        params
+    }
+}
+
+impl<K: Into<String>, V: Into<String>, const N: usize> From<[(K, V); N]> for Params {
+    fn from(params: [(K, V); N]) -> Self {
+        params.into_iter().collect()
+    }
+}
+
+impl Serialize for Params {
+    /// Serialize the parameters as a sequence of name and value pairs, as
+    /// expected by query string and form encoders.
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+/// A set of HTTP headers.
+///
+/// Merging headers replaces all values of any headers in both.
+///
+/// # Example
+///
+/// ```
+/// use ably::http::Headers;
+///
+/// let mut headers = Headers::from_pairs([("X-Tenant", "tenant-1"), ("X-Trace", "a")])?;
+/// headers.merge(Headers::from_pairs([("X-Trace", "b")])?);
+///
+/// assert_eq!(headers.get("X-Tenant").unwrap(), "tenant-1");
+/// assert_eq!(headers.get("X-Trace").unwrap(), "b");
+/// # Ok::<(), ably::Error>(())
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Headers(HeaderMap);
+
+impl Headers {
+    /// Returns an empty set of headers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns headers with the given names and values, failing if any are
+    /// not valid HTTP header names or values.
+    pub fn from_pairs<K: AsRef<str>, V: AsRef<str>>(
+        pairs: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<Self> {
+        let mut headers = Self::new();
+        for (name, value) in pairs {
+            let name = HeaderName::try_from(name.as_ref()).map_err(|err| {
+                Error::with_cause(
+                    ErrorCode::InvalidHeader,
+                    err,
+                    format!("invalid header name '{}'", name.as_ref()),
+                )
+            })?;
+            headers.insert(name, HeaderValue::try_from(value.as_ref())?);
+        }
+        Ok(headers)
+    }
+
+    /// Set a header, replacing any existing values with the same name.
+    pub fn insert(&mut self, name: HeaderName, value: HeaderValue) {
+        self.0.insert(name, value);
+    }
+
+    /// Returns the first value of the given header, if it is set.
+    pub fn get(&self, name: &str) -> Option<&HeaderValue> {
+        self.0.get(name)
+    }
+
+    /// Set all of the given headers, replacing all values of any which are
+    /// already set.
+    pub fn merge(&mut self, other: Headers) {
+        for name in other.0.keys() {
+            self.0.remove(name);
+        }
+        for (name, value) in other.0.iter() {
+            self.0.append(name.clone(), value.clone());
+        }
+    }
+
+    /// Returns whether there are no headers.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the underlying HeaderMap.
+    pub fn as_map(&self) -> &HeaderMap {
+        &self.0
+    }
+}
+
+impl From<HeaderMap> for Headers {
+    fn from(headers: HeaderMap) -> Self {
+        Self(headers)
+    }
+}
+
+impl From<Headers> for HeaderMap {
+    fn from(headers: Headers) -> Self {
+        headers.0
+    }
+}
+
+/// The result of a generic paginated request to an arbitrary REST endpoint
+/// (RSC19), which exposes the status code and headers of the response along
+/// with pages of JSON items.
+pub type HttpPaginatedResponse = PaginatedResult<rest::DecodeRaw<json::Value>>;
+
+/// Executes HTTP requests on behalf of a Rest client.
+///
+/// A reqwest::Client is used by default, but an alternative executor can be
+/// injected with Rest::with_http_executor, for example to return canned
+/// responses in unit tests, or to send requests using a different HTTP stack.
+///
+/// Requests are built with the Rest client's reqwest::Client, so they have
+/// the client's headers, authentication and query parameters set.
+pub trait HttpExecutor: Debug + Send + Sync {
+    /// Execute the request and return its response.
+    fn execute(&self, req: reqwest::Request) -> BoxFuture<'_, Result<reqwest::Response>>;
+}
+
+impl<T: HttpExecutor + ?Sized> HttpExecutor for std::sync::Arc<T> {
+    fn execute(&self, req: reqwest::Request) -> BoxFuture<'_, Result<reqwest::Response>> {
+        (**self).execute(req)
+    }
+}
+
+impl HttpExecutor for reqwest::Client {
+    fn execute(&self, req: reqwest::Request) -> BoxFuture<'_, Result<reqwest::Response>> {
+        Box::pin(async move {
+            reqwest::Client::execute(self, req)
+                .await
+                .map_err(Into::into)
+        })
+    }
+}
+
+/// Resolves the IP addresses of hosts the client sends requests to, in place
+/// of the system DNS resolver.
+///
+/// A Resolver is registered with ClientOptions::dns_resolver, for example to
+/// use a split-horizon DNS service, or to send requests to a local
+/// Ably-compatible mock server.
+///
+/// # Example
+///
+/// ```
+/// use std::net::{IpAddr, Ipv4Addr};
+///
+/// use ably::http::Resolver;
+/// use futures::future::BoxFuture;
+///
+/// #[derive(Debug)]
+/// struct Localhost;
+///
+/// impl Resolver for Localhost {
+///     fn resolve(&self, _host: &str) -> BoxFuture<'_, ably::Result<Vec<IpAddr>>> {
+///         Box::pin(async { Ok(vec![IpAddr::V4(Ipv4Addr::LOCALHOST)]) })
+///     }
+/// }
+///
+/// let client = ably::ClientOptions::new("<api_key>")
+///     .dns_resolver(Localhost)
+///     .rest()?;
+/// # Ok::<(), ably::Error>(())
+/// ```
+pub trait Resolver: Debug + Send + Sync {
+    /// Resolve the IP addresses of the given host.
+    fn resolve(&self, host: &str) -> BoxFuture<'_, Result<Vec<std::net::IpAddr>>>;
+}
+
+impl<T: Resolver + ?Sized> Resolver for std::sync::Arc<T> {
+    fn resolve(&self, host: &str) -> BoxFuture<'_, Result<Vec<std::net::IpAddr>>> {
+        (**self).resolve(host)
+    }
+}
+
+/// Adapts a Resolver to the reqwest DNS resolver interface.
+pub(crate) struct ReqwestResolver(pub(crate) std::sync::Arc<dyn Resolver>);
+
+impl reqwest::dns::Resolve for ReqwestResolver {
+    fn resolve(&self, name: hyper::client::connect::dns::Name) -> reqwest::dns::Resolving {
+        let resolver = self.0.clone();
+        Box::pin(async move {
+            let ips = resolver.resolve(name.as_str()).await?;
+            let addrs: reqwest::dns::Addrs =
+                Box::new(ips.into_iter().map(|ip| std::net::SocketAddr::new(ip, 0)));
+            Ok(addrs)
+        })
+    }
+}
+
+/// Signs outgoing HTTP requests with a custom scheme, for example to add an
+/// HMAC header required by an authenticated egress gateway.
+///
+/// A RequestSigner is registered with ClientOptions::request_signer, and is
+/// called for every attempt to send a request, including retries against
+/// fallback hosts, once all other headers have been set and middleware has
+/// been applied. The returned headers are added to the request, replacing any
+/// with the same name.
+///
+/// # Example
+///
+/// ```
+/// use ably::http::{Headers, RequestSigner, SignableRequest};
+/// use hmac::{Hmac, Mac};
+/// use sha2::Sha256;
+///
+/// #[derive(Debug)]
+/// struct GatewaySigner {
+///     secret: Vec<u8>,
+/// }
+///
+/// impl RequestSigner for GatewaySigner {
+///     fn sign(&self, req: &SignableRequest) -> ably::Result<Headers> {
+///         let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret)?;
+///         mac.update(req.method.as_str().as_bytes());
+///         mac.update(req.url.path().as_bytes());
+///         mac.update(req.body);
+///         let signature = base64::encode(mac.finalize().into_bytes());
+///
+///         Headers::from_pairs([("X-Gateway-Signature", signature)])
+///     }
+/// }
+///
+/// let client = ably::ClientOptions::new("<api_key>")
+///     .request_signer(GatewaySigner { secret: b"secret".to_vec() })
+///     .rest()?;
+/// # Ok::<(), ably::Error>(())
+/// ```
+pub trait RequestSigner: Debug + Send + Sync {
+    /// Returns the headers to add to the request to sign it.
+    fn sign(&self, req: &SignableRequest) -> Result<Headers>;
+}
+
+impl<T: RequestSigner + ?Sized> RequestSigner for std::sync::Arc<T> {
+    fn sign(&self, req: &SignableRequest) -> Result<Headers> {
+        (**self).sign(req)
+    }
+}
+
+/// The parts of an outgoing request which a RequestSigner can sign.
+#[derive(Debug)]
+pub struct SignableRequest<'a> {
+    pub method: &'a Method,
+    pub url: &'a reqwest::Url,
+    pub headers: &'a HeaderMap,
+    /// The request body, which is empty if the request doesn't have one.
+    pub body: &'a [u8],
+}
+
+/// Sign the request with the given signer, adding the resulting headers.
+pub(crate) fn sign_request(signer: &dyn RequestSigner, req: &mut reqwest::Request) -> Result<()> {
+    let signed = signer.sign(&SignableRequest {
+        method: req.method(),
+        url: req.url(),
+        headers: req.headers(),
+        body: req.body().and_then(|b| b.as_bytes()).unwrap_or_default(),
+    })?;
+
+    let mut headers = Headers::from(std::mem::take(req.headers_mut()));
+    headers.merge(signed);
+    *req.headers_mut() = headers.into();
+
+    Ok(())
+}
+
+/// Middleware which can observe and modify the HTTP requests sent by a Rest
+/// client, and observe the responses, for example to add tenant headers,
+/// sign requests, or write an audit log.
+///
+/// Middleware is registered with ClientOptions::middleware and is called in
+/// the order it was registered. It is called for every attempt to send a
+/// request, including retries against fallback hosts, after authentication
+/// headers have been set.
+///
+/// Returning an error from either method fails the request with that error.
+pub trait Middleware: Debug + Send + Sync {
+    /// Called with each request before it is sent.
+    fn on_request(&self, req: &mut reqwest::Request) -> Result<()> {
+        let _ = req;
+        Ok(())
+    }
+
+    /// Called with each response before it is checked for errors.
+    fn on_response(&self, res: &reqwest::Response) -> Result<()> {
+        let _ = res;
+        Ok(())
+    }
+}
+
+impl<T: Middleware + ?Sized> Middleware for std::sync::Arc<T> {
+    fn on_request(&self, req: &mut reqwest::Request) -> Result<()> {
+        (**self).on_request(req)
+    }
+
+    fn on_response(&self, res: &reqwest::Response) -> Result<()> {
+        (**self).on_response(res)
+    }
+}
 
 /// A builder to construct a HTTP request to the [Ably REST API].
 ///
@@ -24,7 +405,11 @@ pub struct RequestBuilder<'a> {
     rest: &'a rest::Rest,
     inner: Result<reqwest::RequestBuilder>,
     format: rest::Format,
+    accept_format: bool,
     authenticate: bool,
+    auth_mode: AuthMode,
+    timeout: Option<Duration>,
+    cancel: Option<BoxFuture<'a, ()>>,
 }
 
 impl<'a> RequestBuilder<'a> {
@@ -33,16 +418,80 @@ impl<'a> RequestBuilder<'a> {
             rest,
             inner: Ok(inner),
             format,
+            accept_format: false,
             authenticate: true,
+            auth_mode: AuthMode::Auto,
+            timeout: None,
+            cancel: None,
         }
     }
 
+    /// Fail the request with a TimeoutError if it doesn't complete within
+    /// the given duration.
+    ///
+    /// The timeout covers the whole request, including authentication,
+    /// fallback hosts and retries, and applies in addition to
+    /// ClientOptions.http_request_timeout.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Cancel the request when the given future completes, failing it with
+    /// a ConnectionClosed error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> ably::Result<()> {
+    /// # let client = ably::Rest::with_http_executor(
+    /// #     ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc"),
+    /// #     ably::mock::MockTransport::new().on(
+    /// #         ably::http::Method::GET,
+    /// #         "/time",
+    /// #         200,
+    /// #         serde_json::json!([1650000000000_i64]),
+    /// #     ),
+    /// # )?;
+    /// use std::time::Duration;
+    ///
+    /// let shutdown = tokio::time::sleep(Duration::from_secs(5));
+    ///
+    /// let res = client
+    ///     .request(ably::http::Method::GET, "/time")
+    ///     .timeout(Duration::from_secs(1))
+    ///     .cancel_on(shutdown)
+    ///     .send()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn cancel_on<F>(mut self, cancel: F) -> Self
+    where
+        F: std::future::Future<Output = ()> + Send + 'a,
+    {
+        self.cancel = Some(cancel.boxed());
+        self
+    }
+
     /// Set the request format.
+    ///
+    /// For requests to the Ably REST API, this sets both the format of the
+    /// request body and the format requested for the response in the Accept
+    /// header.
     pub fn format(mut self, format: rest::Format) -> Self {
         self.format = format;
         self
     }
 
+    /// Send an Accept header requesting a response in the request format,
+    /// unless one is set explicitly.
+    pub(crate) fn accept_format(mut self) -> Self {
+        self.accept_format = true;
+        self
+    }
+
     /// Modify the query params of the request, adding the parameters provided.
     pub fn params<T: Serialize + ?Sized>(mut self, params: &T) -> Self {
         if let Ok(req) = self.inner {
@@ -59,6 +508,14 @@ impl<'a> RequestBuilder<'a> {
         }
     }
 
+    /// Set a form-encoded request body.
+    pub fn form<T: Serialize + ?Sized>(mut self, form: &T) -> Self {
+        if let Ok(req) = self.inner {
+            self.inner = Ok(req.form(form));
+        }
+        self
+    }
+
     /// Set the JSON request body.
     fn json<T: Serialize + ?Sized>(mut self, body: &T) -> Self {
         if let Ok(req) = self.inner {
@@ -72,6 +529,20 @@ impl<'a> RequestBuilder<'a> {
         self
     }
 
+    /// Set how the request is authenticated, for example to force basic
+    /// auth for an endpoint which doesn't accept tokens. Defaults to
+    /// AuthMode::Auto.
+    pub fn auth_mode(mut self, mode: AuthMode) -> Self {
+        self.auth_mode = mode;
+        self
+    }
+
+    /// Returns the AuthMode to send the request with, or None if it is
+    /// unauthenticated.
+    fn auth(&self) -> Option<AuthMode> {
+        self.authenticate.then_some(self.auth_mode)
+    }
+
     /// Set the MessagePack request body.
     fn msgpack<T: Serialize + ?Sized>(mut self, body: &T) -> Self {
         if let Ok(req) = self.inner {
@@ -79,7 +550,7 @@ impl<'a> RequestBuilder<'a> {
                 .map(|data| {
                     req.header(
                         reqwest::header::CONTENT_TYPE,
-                        HeaderValue::from_static("application/x-msgpack"),
+                        HeaderValue::from_static(rest::Format::MessagePack.mime_type()),
                     )
                     .body(data)
                 })
@@ -89,9 +560,9 @@ impl<'a> RequestBuilder<'a> {
     }
 
     /// Add a set of HTTP headers to the request.
-    pub fn headers(mut self, headers: HeaderMap) -> Self {
+    pub fn headers(mut self, headers: impl Into<Headers>) -> Self {
         if let Ok(req) = self.inner {
-            self.inner = Ok(req.headers(headers));
+            self.inner = Ok(req.headers(headers.into().into()));
         }
         self
     }
@@ -111,15 +582,54 @@ impl<'a> RequestBuilder<'a> {
     }
 
     /// Send the request to the Ably REST API.
-    pub async fn send(self) -> Result<Response> {
+    pub async fn send(mut self) -> Result<Response> {
         let rest = self.rest;
-        let auth = self.authenticate;
+        let auth = self.auth();
+        let timeout = self.timeout;
+        let cancel = self.cancel.take();
         let req = self.build()?;
-        rest.send(req, auth).await
+
+        let res = rest.send(req, auth);
+        let res = async move {
+            match timeout {
+                Some(timeout) => tokio::time::timeout(timeout, res).await.map_err(|_| {
+                    Error::new(
+                        ErrorCode::TimeoutError,
+                        format!("request timed out after {:?}", timeout),
+                    )
+                })?,
+                None => res.await,
+            }
+        };
+
+        let cancel = match cancel {
+            Some(cancel) => cancel,
+            None => return res.await,
+        };
+        futures::pin_mut!(res);
+        match futures::future::select(res, cancel).await {
+            future::Either::Left((res, _)) => res,
+            future::Either::Right(_) => {
+                Err(Error::new(ErrorCode::ConnectionClosed, "request cancelled"))
+            }
+        }
     }
 
     fn build(self) -> Result<reqwest::Request> {
-        self.inner?.build().map_err(Into::into)
+        let mut req = self.inner?.build()?;
+        if !self.rest.inner.opts.headers.is_empty() {
+            // Headers set on the request replace the client's headers.
+            let mut headers = self.rest.inner.opts.headers.clone();
+            headers.merge(Headers::from(std::mem::take(req.headers_mut())));
+            *req.headers_mut() = headers.into();
+        }
+        if self.accept_format && !req.headers().contains_key(reqwest::header::ACCEPT) {
+            req.headers_mut().insert(
+                reqwest::header::ACCEPT,
+                HeaderValue::from_static(self.format.mime_type()),
+            );
+        }
+        Ok(req)
     }
 }
 
@@ -127,17 +637,27 @@ struct PaginatedState<'a, T: 'a> {
     next_req: Option<Result<reqwest::Request>>,
     rest: &'a rest::Rest,
     options: T,
+    auth: Option<AuthMode>,
+    resume_on_maintenance: bool,
+    error_responses: bool,
 }
 
 /// A builder to construct a paginated REST request.
 pub struct PaginatedRequestBuilder<'a, T: Decode> {
     inner: RequestBuilder<'a>,
     options: T::Options,
+    resume_on_maintenance: bool,
+    error_responses: bool,
 }
 
 impl<'a, T: Decode + 'a> PaginatedRequestBuilder<'a, T> {
     pub fn new(inner: RequestBuilder<'a>, options: T::Options) -> Self {
-        Self { inner, options }
+        Self {
+            inner,
+            options,
+            resume_on_maintenance: false,
+            error_responses: false,
+        }
     }
 
     /// Set the start interval of the request.
@@ -150,6 +670,43 @@ impl<'a, T: Decode + 'a> PaginatedRequestBuilder<'a, T> {
         self.params(&[("end", interval)])
     }
 
+    /// Set the start of the request to the given time.
+    pub fn start_at(self, time: DateTime<Utc>) -> Self {
+        self.start(&time.timestamp_millis().to_string())
+    }
+
+    /// Set the end of the request to the given time.
+    pub fn end_at(self, time: DateTime<Utc>) -> Self {
+        self.end(&time.timestamp_millis().to_string())
+    }
+
+    /// Set the start of the request to the given duration before now, so
+    /// that only the results of that last duration are returned.
+    ///
+    /// Now is the server time if the offset of the server's clock has been
+    /// queried, for example because the query_time option is set, so that a
+    /// skewed local clock doesn't shift the window.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # async fn run() -> ably::Result<()> {
+    /// let client = ably::Rest::from("<api_key>");
+    /// let page = client
+    ///     .channels()
+    ///     .get("rust-example")
+    ///     .history()
+    ///     .last(chrono::Duration::minutes(5))
+    ///     .send()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn last(self, duration: chrono::Duration) -> Self {
+        let now = self.inner.rest.server_now();
+        self.start_at(now - duration)
+    }
+
     /// Paginate forwards.
     pub fn forwards(self) -> Self {
         self.params(&[("direction", "forwards")])
@@ -171,6 +728,44 @@ impl<'a, T: Decode + 'a> PaginatedRequestBuilder<'a, T> {
         self
     }
 
+    /// Add a set of HTTP headers to the request.
+    pub fn headers(mut self, headers: impl Into<Headers>) -> Self {
+        self.inner = self.inner.headers(headers);
+        self
+    }
+
+    /// Set the request body.
+    pub fn body<B: Serialize + ?Sized>(mut self, body: &B) -> Self {
+        self.inner = self.inner.body(body);
+        self
+    }
+
+    /// Set how the page requests are authenticated. Defaults to
+    /// AuthMode::Auto.
+    pub fn auth_mode(mut self, mode: AuthMode) -> Self {
+        self.inner = self.inner.auth_mode(mode);
+        self
+    }
+
+    /// Wait and retry a page request which fails with a 503 Service
+    /// Unavailable response containing a Retry-After header, rather than
+    /// ending the stream with an error.
+    ///
+    /// This allows long running consumers of the pages or items streams to
+    /// continue after an Ably maintenance window. A 503 response without a
+    /// Retry-After header still ends the stream with an error.
+    pub fn resume_on_maintenance(mut self) -> Self {
+        self.resume_on_maintenance = true;
+        self
+    }
+
+    /// Return a response with an error status code as a page rather than
+    /// as an error (RSC19).
+    pub(crate) fn return_error_responses(mut self) -> Self {
+        self.error_responses = true;
+        self
+    }
+
     /// Request a stream of pages from the Ably REST API.
     pub fn pages(self) -> impl Stream<Item = Result<PaginatedResult<T>>> + 'a {
         // Use stream::unfold to create a stream of pages where the internal
@@ -178,10 +773,14 @@ impl<'a, T: Decode + 'a> PaginatedRequestBuilder<'a, T> {
         // request and returns both a PaginatedResult and the request for the
         // next page if the response has a 'Link: ...; rel="next"' header.
         let rest = self.inner.rest;
+        let auth = self.inner.auth();
         let seed_state = PaginatedState {
             next_req: Some(self.inner.build()),
             rest,
             options: self.options,
+            auth,
+            resume_on_maintenance: self.resume_on_maintenance,
+            error_responses: self.error_responses,
         };
 
         stream::unfold(seed_state, move |mut state| {
@@ -208,7 +807,7 @@ impl<'a, T: Decode + 'a> PaginatedRequestBuilder<'a, T> {
                 // If the request is not cloneable, for example because it has
                 // a streamed body, map it to an error which will be yielded on
                 // the next iteration of the stream.
-                let mut next_req = req
+                let next_req = req
                     .try_clone()
                     .ok_or_else(|| Error::new(ErrorCode::BadRequest, "not a pageable request"));
 
@@ -216,23 +815,46 @@ impl<'a, T: Decode + 'a> PaginatedRequestBuilder<'a, T> {
                 //
                 // If there's an error, yield the error and set the next
                 // request to None to end the stream on the next iteration.
-                let res = match state.rest.send(req, true).await {
+                //
+                // Keep a copy of the request in the PaginatedResult so that
+                // it can follow links to other pages itself.
+                let template = next_req.as_ref().ok().and_then(|req| req.try_clone());
+                let res = match send_page(state.rest, req, state.auth, state.resume_on_maintenance)
+                    .await
+                    .or_else(|err| match state.error_responses {
+                        true => error_response(err),
+                        false => Err(err),
+                    }) {
                     Err(err) => {
                         state.next_req = None;
                         return Some((Err(err), state));
                     }
-                    Ok(res) => PaginatedResult::new(res, state.options.clone()),
+                    Ok(res) => PaginatedResult::with_context(
+                        res,
+                        state.options.clone(),
+                        template.map(|req| PageContext {
+                            rest: state.rest.clone(),
+                            req,
+                            auth: state.auth,
+                        }),
+                    ),
                 };
 
                 // If there's a next link in the response, merge its params
                 // into the next request if we have one, otherwise set the next
                 // request to None to end the stream on the next iteration.
                 state.next_req = None;
-                if let Some(link) = res.next_link() {
-                    if let Ok(req) = &mut next_req {
-                        req.url_mut().set_query(Some(&link.params));
-                    }
-                    state.next_req = Some(next_req)
+                if let Some(link) = res.link("next") {
+                    state.next_req = Some(next_req.and_then(|mut req| {
+                        *req.url_mut() = link.resolve(req.url())?;
+                        state.rest.inner.opts.log(LogLevel::Debug, || {
+                            format!(
+                                "following link to the next page: {}",
+                                log::describe_request(&req)
+                            )
+                        });
+                        Ok(req)
+                    }));
                 };
 
                 // Yield the PaginatedResult and the next state.
@@ -242,6 +864,13 @@ impl<'a, T: Decode + 'a> PaginatedRequestBuilder<'a, T> {
         })
     }
 
+    /// Request a stream of items from the Ably REST API, which transparently
+    /// requests the next page once the items in the current page have been
+    /// yielded.
+    pub fn items(self) -> impl Stream<Item = Result<T::Item>> + 'a {
+        self.pages().map_ok(|page| page.item_stream()).try_flatten()
+    }
+
     /// Retrieve the first page of the paginated response.
     pub async fn send(self) -> Result<PaginatedResult<T>> {
         // The pages stream always returns at least one non-None value, even if
@@ -259,63 +888,410 @@ impl<'a, T: Decode + 'a> PaginatedRequestBuilder<'a, T> {
     }
 }
 
-/// A Link HTTP header.
-struct Link {
-    rel: String,
-    params: String,
+/// Send a page request, waiting and retrying whilst it fails with a 503
+/// Service Unavailable response containing a Retry-After header if
+/// resume_on_maintenance is set.
+///
+/// Rate limited requests are retried by Rest::send according to
+/// ClientOptions.rate_limit_retry_count, like any other request.
+async fn send_page(
+    rest: &rest::Rest,
+    mut req: reqwest::Request,
+    auth: Option<AuthMode>,
+    resume_on_maintenance: bool,
+) -> Result<Response> {
+    loop {
+        let retry = if resume_on_maintenance {
+            req.try_clone()
+        } else {
+            None
+        };
+
+        let err = match rest.send(req, auth).await {
+            Err(err) => err,
+            res => return res,
+        };
+
+        let delay = match (err.service_unavailable(), retry) {
+            (
+                Some(ServiceUnavailable {
+                    retry_after: Some(delay),
+                }),
+                Some(retry),
+            ) => {
+                req = retry;
+                delay
+            }
+            _ => return Err(err),
+        };
+
+        rest.inner.opts.log(LogLevel::Warn, || {
+            format!(
+                "page request failed, retrying in {:?}: {}",
+                delay,
+                log::describe_error(&err)
+            )
+        });
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// The status code, headers and body of a response with an error status
+/// code, which is the cause of the resulting Error.
+#[derive(Debug)]
+pub(crate) struct ErrorResponse {
+    pub(crate) status: reqwest::StatusCode,
+    pub(crate) headers: HeaderMap,
+    pub(crate) body: Bytes,
+}
+
+impl std::fmt::Display for ErrorResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "received a {} response", self.status)
+    }
+}
+
+impl std::error::Error for ErrorResponse {}
+
+/// Convert an error which resulted from a response with an error status code
+/// back into a Response with that status code, headers and body, or return
+/// the error if it wasn't the result of a response.
+///
+/// If the response has no X-Ably-Errorcode or X-Ably-Errormessage headers,
+/// they are set from the error in the body, so that the error code and
+/// message of the page are available either way (HP6, HP7).
+fn error_response(err: Error) -> Result<Response> {
+    let cause = match err.cause.as_ref().and_then(|cause| cause.downcast_ref()) {
+        Some(cause) => cause,
+        None => return Err(err),
+    };
+    let ErrorResponse {
+        status,
+        headers,
+        body,
+    } = cause;
+
+    let mut res = ::http::Response::new(body.to_vec());
+    *res.status_mut() = *status;
+    *res.headers_mut() = headers.clone();
+    if let Ok(WrappedError { error }) = serde_json::from_slice(body) {
+        let headers = res.headers_mut();
+        if !headers.contains_key("x-ably-errorcode") {
+            headers.insert("x-ably-errorcode", error.code.code().into());
+        }
+        if !headers.contains_key("x-ably-errormessage") {
+            if let Ok(message) = HeaderValue::from_str(&error.message) {
+                headers.insert("x-ably-errormessage", message);
+            }
+        }
+    }
+    Ok(Response::new(res.into()))
+}
+
+/// A link from a [Link HTTP header], which is used to link to other pages of
+/// a paginated response.
+///
+/// A single header can contain multiple comma-separated links, each with a
+/// target URI (which may be relative to the request URL) and any number of
+/// parameters, such as the relation types.
+///
+/// # Example
+///
+/// ```
+/// use ably::http::Link;
+///
+/// let links = Link::parse(r#"<./messages?cont=true>; rel="next", <./messages?start=0>; rel="first current""#)?;
+///
+/// assert_eq!(links.len(), 2);
+/// assert_eq!(links[0].target, "./messages?cont=true");
+/// assert!(links[0].has_rel("next"));
+/// assert!(links[1].has_rel("first"));
+/// assert!(links[1].has_rel("current"));
+/// # Ok::<(), ably::Error>(())
+/// ```
+///
+/// [Link HTTP header]: https://www.rfc-editor.org/rfc/rfc8288
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Link {
+    /// The target URI of the link, which may be relative.
+    pub target: String,
+
+    /// The relation types of the link, from the rel parameter.
+    pub rels: Vec<String>,
+
+    /// The other parameters of the link, with lowercase names and unquoted
+    /// values.
+    pub params: Vec<(String, String)>,
 }
 
-lazy_static! {
-    /// A static regular expression to extract the rel and params fields
-    /// from a Link header, which looks something like:
+impl Link {
+    /// Parse all of the links in the value of a Link header.
+    pub fn parse(header: &str) -> Result<Vec<Link>> {
+        LinkParser {
+            s: header.as_bytes(),
+            pos: 0,
+        }
+        .links()
+    }
+
+    /// Returns whether the link has the given relation type.
+    pub fn has_rel(&self, rel: &str) -> bool {
+        self.rels.iter().any(|r| r.eq_ignore_ascii_case(rel))
+    }
+
+    /// Returns the value of the given parameter, if the link has it.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Resolve the link target against the URL of the request which the
+    /// response containing the link was for.
     ///
-    /// Link: <./messages?limit=10&direction=forwards&cont=true&format=json&firstStart=0&end=1635552598723>; rel="next"
-    static ref LINK_RE: Regex = Regex::new(r#"^\s*<[^?]+\?(?P<params>.+)>;\s*rel="(?P<rel>\w+)"$"#).unwrap();
+    /// Returns an error if the target has a different scheme, host or port
+    /// to the base URL, so that following a link never sends the client's
+    /// credentials to another server.
+    pub fn resolve(&self, base: &reqwest::Url) -> Result<reqwest::Url> {
+        let url = base.join(&self.target).map_err(|err| {
+            Error::with_cause(
+                ErrorCode::InvalidHeader,
+                err,
+                format!("Invalid Link header; invalid target '{}'", self.target),
+            )
+        })?;
+
+        if url.scheme() != base.scheme()
+            || url.host_str() != base.host_str()
+            || url.port_or_known_default() != base.port_or_known_default()
+        {
+            return Err(Error::new(
+                ErrorCode::InvalidHeader,
+                format!(
+                    "Invalid Link header; target '{}' is not on the same host as the request",
+                    self.target
+                ),
+            ));
+        }
+
+        Ok(url)
+    }
 }
 
 impl TryFrom<&reqwest::header::HeaderValue> for Link {
     type Error = Error;
 
-    /// Try and extract a Link object from a Link HTTP header.
+    /// Try and extract a single Link object from a Link HTTP header.
     fn try_from(v: &reqwest::header::HeaderValue) -> Result<Link> {
-        // Check we have a valid utf-8 string.
         let link = v
             .to_str()
             .map_err(|_| Error::new(ErrorCode::InvalidHeader, "Invalid Link header"))?;
 
-        // Extract the rel and params from the header using the LINK_RE regular
-        // expression.
-        let caps = LINK_RE
-            .captures(link)
-            .ok_or_else(|| Error::new(ErrorCode::InvalidHeader, "Invalid Link header"))?;
-        let rel = caps.name("rel").ok_or_else(|| {
-            Error::new(ErrorCode::InvalidHeader, "Invalid Link header; missing rel")
-        })?;
-        let params = caps.name("params").ok_or_else(|| {
-            Error::new(
+        let mut links = Link::parse(link)?;
+        match links.len() {
+            1 => Ok(links.remove(0)),
+            n => Err(Error::new(
                 ErrorCode::InvalidHeader,
-                "Invalid Link header; missing params",
-            )
-        })?;
-
-        Ok(Self {
-            rel: rel.as_str().to_string(),
-            params: params.as_str().to_string(),
-        })
+                format!("Invalid Link header; expected 1 link, got {}", n),
+            )),
+        }
     }
 }
 
-/// A successful Response from the [Ably REST API].
+/// A parser for the value of a Link header, as defined in RFC 8288:
 ///
-/// [Ably REST API]: https://ably.com/documentation/rest-api
-#[derive(Debug)]
-pub struct Response {
-    inner: reqwest::Response,
+/// Link       = #link-value
+/// link-value = "<" URI-Reference ">" *( OWS ";" OWS link-param )
+/// link-param = token BWS [ "=" BWS ( token / quoted-string ) ]
+struct LinkParser<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> LinkParser<'a> {
+    fn links(mut self) -> Result<Vec<Link>> {
+        let mut links = Vec::new();
+        loop {
+            // Skip whitespace and empty list elements.
+            while self.eat(b',') || self.eat(b' ') || self.eat(b'\t') {}
+            if self.peek().is_none() {
+                return Ok(links);
+            }
+
+            links.push(self.link()?);
+
+            self.skip_whitespace();
+            if self.peek().is_some() && !self.eat(b',') {
+                return Err(self.error("expected ','"));
+            }
+        }
+    }
+
+    fn link(&mut self) -> Result<Link> {
+        if !self.eat(b'<') {
+            return Err(self.error("expected '<'"));
+        }
+        let target = self.take_while(|c| c != b'>');
+        if !self.eat(b'>') {
+            return Err(self.error("unterminated target"));
+        }
+
+        let mut link = Link {
+            target,
+            rels: Vec::new(),
+            params: Vec::new(),
+        };
+        let mut seen_rel = false;
+
+        loop {
+            self.skip_whitespace();
+            if !self.eat(b';') {
+                return Ok(link);
+            }
+            self.skip_whitespace();
+
+            let name = self.token()?.to_ascii_lowercase();
+            self.skip_whitespace();
+            let value = if self.eat(b'=') {
+                self.skip_whitespace();
+                if self.peek() == Some(b'"') {
+                    self.quoted_string()?
+                } else {
+                    self.token()?
+                }
+            } else {
+                String::new()
+            };
+
+            // Only the first rel parameter is used (RFC 8288 section 3.3).
+            if name == "rel" {
+                if !seen_rel {
+                    link.rels = value.split_whitespace().map(String::from).collect();
+                    seen_rel = true;
+                }
+            } else {
+                link.params.push((name, value));
+            }
+        }
+    }
+
+    fn token(&mut self) -> Result<String> {
+        let token =
+            self.take_while(|c| c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&c));
+        if token.is_empty() {
+            return Err(self.error("expected a token"));
+        }
+        Ok(token)
+    }
+
+    fn quoted_string(&mut self) -> Result<String> {
+        self.eat(b'"');
+        let mut value = Vec::new();
+        loop {
+            match self.next() {
+                Some(b'"') => return Ok(String::from_utf8_lossy(&value).into_owned()),
+                Some(b'\\') => match self.next() {
+                    Some(c) => value.push(c),
+                    None => break,
+                },
+                Some(c) => value.push(c),
+                None => break,
+            }
+        }
+        Err(self.error("unterminated quoted string"))
+    }
+
+    fn take_while(&mut self, f: impl Fn(u8) -> bool) -> String {
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if f(c)) {
+            self.pos += 1;
+        }
+        String::from_utf8_lossy(&self.s[start..self.pos]).into_owned()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.eat(b' ') || self.eat(b'\t') {}
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.s.get(self.pos).copied()
+    }
+
+    fn error(&self, msg: &str) -> Error {
+        Error::new(
+            ErrorCode::InvalidHeader,
+            format!("Invalid Link header; {} at position {}", msg, self.pos),
+        )
+    }
+}
+
+/// The name of the HTTP header identifying the Ably server which handled a
+/// request.
+pub const SERVER_ID_HEADER: &str = "X-Ably-ServerId";
+
+/// Parse a Retry-After header value, which is either a number of seconds or a
+/// HTTP date.
+pub(crate) fn parse_retry_after(v: &HeaderValue) -> Option<Duration> {
+    let v = v.to_str().ok()?.trim();
+
+    if let Ok(secs) = v.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(v).ok()?;
+    let delay = date.signed_duration_since(chrono::Utc::now());
+    Some(delay.to_std().unwrap_or(Duration::ZERO))
+}
+
+/// A successful Response from the [Ably REST API].
+///
+/// [Ably REST API]: https://ably.com/documentation/rest-api
+#[derive(Debug)]
+pub struct Response {
+    inner: reqwest::Response,
+    max_body_size: Option<usize>,
+
+    /// The request slot held whilst the response body is read, when
+    /// ClientOptions.max_concurrent_requests is set.
+    permit: Option<tokio::sync::OwnedSemaphorePermit>,
 }
 
 impl Response {
     pub fn new(response: reqwest::Response) -> Self {
-        Self { inner: response }
+        Self {
+            inner: response,
+            max_body_size: None,
+            permit: None,
+        }
+    }
+
+    /// Limit the size of the response body which will be read into memory,
+    /// failing with an InvalidContentLength error if it is larger.
+    pub fn with_max_body_size(mut self, max: Option<usize>) -> Self {
+        self.max_body_size = max;
+        self
+    }
+
+    /// Hold the given request slot until the response body has been read.
+    pub(crate) fn with_permit(mut self, permit: Option<tokio::sync::OwnedSemaphorePermit>) -> Self {
+        self.permit = permit;
+        self
     }
 
     /// The HTTP status code of the response.
@@ -323,6 +1299,11 @@ impl Response {
         self.inner.status()
     }
 
+    /// The HTTP headers of the response.
+    pub fn headers(&self) -> &HeaderMap {
+        self.inner.headers()
+    }
+
     /// The value of the Content-Type header.
     pub fn content_type(&self) -> Option<mime::Mime> {
         self.inner
@@ -332,6 +1313,40 @@ impl Response {
             .and_then(|v| v.parse().ok())
     }
 
+    /// The value of the X-Ably-ServerId header, which identifies the Ably
+    /// server which handled the request.
+    pub fn server_id(&self) -> Option<&str> {
+        self.inner
+            .headers()
+            .get(SERVER_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+    }
+
+    /// The value of the Retry-After header, which is set on rate limited or
+    /// unavailable responses to indicate how long to wait before retrying.
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.inner
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(parse_retry_after)
+    }
+
+    /// Return the raw response body, without decoding it.
+    pub async fn bytes(self) -> Result<Bytes> {
+        read_body(self.inner, self.max_body_size).await
+    }
+
+    /// Return the underlying reqwest::Response, for example to stream the
+    /// response body.
+    ///
+    /// The maximum body size set by ClientOptions.max_response_body_size is
+    /// not enforced when reading the body of the returned response, and the
+    /// response no longer counts towards
+    /// ClientOptions.max_concurrent_requests.
+    pub fn into_reqwest(self) -> reqwest::Response {
+        self.inner
+    }
+
     /// Deserialize the response body.
     pub async fn body<T: DeserializeOwned>(self) -> Result<T> {
         let content_type = self
@@ -350,49 +1365,739 @@ impl Response {
 
     /// Deserialize the response body as JSON.
     pub async fn json<T: DeserializeOwned>(self) -> Result<T> {
-        self.inner.json().await.map_err(Into::into)
+        let data = self.bytes().await?;
+
+        serde_json::from_slice(&data).map_err(Into::into)
     }
 
     /// Deserialize the response body as MessagePack.
     pub async fn msgpack<T: DeserializeOwned>(self) -> Result<T> {
-        let data = self.inner.bytes().await?;
+        let data = self.bytes().await?;
 
-        rmp_serde::from_read(&*data).map_err(Into::into)
+        rmp_serde::from_slice(&data).map_err(Into::into)
+    }
+
+    /// Deserialize a MessagePack array response body as a stream of its
+    /// items.
+    ///
+    /// Each item is decoded as soon as all of its bytes have been received,
+    /// and the bytes are then discarded, so a large response is never held
+    /// in memory in both its encoded and decoded form.
+    pub fn msgpack_items<T: DeserializeOwned>(self) -> impl Stream<Item = Result<T>> {
+        let state = MsgpackItems {
+            res: self.inner,
+            buf: bytes::BytesMut::new(),
+            read: 0,
+            max_body_size: self.max_body_size,
+            remaining: None,
+            _permit: self.permit,
+        };
+
+        stream::unfold(Some(state), |state| async move {
+            let mut state = state?;
+            match state.next().await {
+                Ok(Some(item)) => Some((Ok(item), Some(state))),
+                Ok(None) => None,
+                Err(err) => Some((Err(err), None)),
+            }
+        })
     }
 
     /// Return the response body as a String.
     pub async fn text(self) -> Result<String> {
-        self.inner.text().await.map_err(Into::into)
+        if self.max_body_size.is_none() {
+            return self.inner.text().await.map_err(Into::into);
+        }
+
+        let data = self.bytes().await?;
+        Ok(std::str::from_utf8(&data)?.to_string())
+    }
+}
+
+/// Read the response body into memory, failing if it is larger than the
+/// given maximum size.
+///
+/// The Content-Length header is checked before reading, and the size is also
+/// checked as the body is read in case the header is missing or wrong.
+pub(crate) async fn read_body(mut res: reqwest::Response, max: Option<usize>) -> Result<Bytes> {
+    let max = match max {
+        Some(max) => max,
+        None => return res.bytes().await.map_err(Into::into),
+    };
+
+    if matches!(res.content_length(), Some(len) if len > max as u64) {
+        return Err(body_too_large(max));
+    }
+
+    let mut body = bytes::BytesMut::new();
+    while let Some(chunk) = res.chunk().await? {
+        if body.len() + chunk.len() > max {
+            return Err(body_too_large(max));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(body.freeze())
+}
+
+fn body_too_large(max: usize) -> Error {
+    Error::new(
+        ErrorCode::InvalidContentLength,
+        format!("response body exceeds the maximum size of {} bytes", max),
+    )
+}
+
+/// The state of a stream of items being decoded from a MessagePack array
+/// response body.
+struct MsgpackItems {
+    res: reqwest::Response,
+    buf: bytes::BytesMut,
+    read: usize,
+    max_body_size: Option<usize>,
+
+    /// The number of items left to decode, or None if the array header has
+    /// not been read yet.
+    remaining: Option<usize>,
+
+    /// The request slot held until the stream is dropped.
+    _permit: Option<tokio::sync::OwnedSemaphorePermit>,
+}
+
+impl MsgpackItems {
+    /// Decode the next item, reading more of the body as necessary, returning
+    /// None once all of the items in the array have been decoded.
+    async fn next<T: DeserializeOwned>(&mut self) -> Result<Option<T>> {
+        use bytes::Buf;
+
+        let remaining = match self.remaining {
+            Some(remaining) => remaining,
+            None => {
+                if let (Some(len), Some(max)) = (self.res.content_length(), self.max_body_size) {
+                    if len > max as u64 {
+                        return Err(body_too_large(max));
+                    }
+                }
+                loop {
+                    if let Some((header_len, array_len)) = msgpack_array_header(&self.buf)? {
+                        self.buf.advance(header_len);
+                        break array_len;
+                    }
+                    self.fill().await?;
+                }
+            }
+        };
+
+        if remaining == 0 {
+            return Ok(None);
+        }
+
+        let len = loop {
+            if let Some(len) = msgpack_value_len(&self.buf) {
+                break len;
+            }
+            self.fill().await?;
+        };
+
+        let item = rmp_serde::from_slice(&self.buf[..len])?;
+        self.buf.advance(len);
+        self.remaining = Some(remaining - 1);
+        Ok(Some(item))
+    }
+
+    /// Read the next chunk of the body into the buffer, failing if the body
+    /// has ended or exceeds the maximum size.
+    async fn fill(&mut self) -> Result<()> {
+        let chunk = self.res.chunk().await?.ok_or_else(|| {
+            Error::new(
+                ErrorCode::InvalidRequestBody,
+                "unexpected end of MessagePack data",
+            )
+        })?;
+
+        self.read += chunk.len();
+        if let Some(max) = self.max_body_size {
+            if self.read > max {
+                return Err(body_too_large(max));
+            }
+        }
+
+        self.buf.extend_from_slice(&chunk);
+        Ok(())
+    }
+}
+
+/// Returns the length of the MessagePack array header at the start of buf
+/// and the number of items in the array, or None if buf does not contain the
+/// whole header.
+fn msgpack_array_header(buf: &[u8]) -> Result<Option<(usize, usize)>> {
+    let marker = match buf.first() {
+        Some(marker) => *marker,
+        None => return Ok(None),
+    };
+
+    let header = match marker {
+        0x90..=0x9f => Some((1, (marker & 0x0f) as usize)),
+        0xdc => msgpack_uint(buf, 1, 2).map(|len| (3, len)),
+        0xdd => msgpack_uint(buf, 1, 4).map(|len| (5, len)),
+        _ => {
+            return Err(Error::new(
+                ErrorCode::InvalidRequestBody,
+                "expected a MessagePack array",
+            ))
+        }
+    };
+
+    Ok(header)
+}
+
+/// Returns the length of the MessagePack value at the start of buf, or None
+/// if buf does not contain the whole value.
+fn msgpack_value_len(buf: &[u8]) -> Option<usize> {
+    let mut pos = 0;
+
+    // The number of values left to skip, which increases when an array or
+    // map is encountered to include its elements.
+    let mut pending: usize = 1;
+
+    while pending > 0 {
+        let marker = *buf.get(pos)?;
+        pos += 1;
+        pending -= 1;
+
+        // The number of bytes following the marker, and the number of nested
+        // values in the case of arrays and maps.
+        let (data_len, values) = match marker {
+            0x80..=0x8f => (0, 2 * (marker & 0x0f) as usize),
+            0x90..=0x9f => (0, (marker & 0x0f) as usize),
+            0xa0..=0xbf => ((marker & 0x1f) as usize, 0),
+            0xc4 | 0xd9 => (1 + msgpack_uint(buf, pos, 1)?, 0),
+            0xc5 | 0xda => (2 + msgpack_uint(buf, pos, 2)?, 0),
+            0xc6 | 0xdb => (4 + msgpack_uint(buf, pos, 4)?, 0),
+            0xc7 => (2 + msgpack_uint(buf, pos, 1)?, 0),
+            0xc8 => (3 + msgpack_uint(buf, pos, 2)?, 0),
+            0xc9 => (5 + msgpack_uint(buf, pos, 4)?, 0),
+            0xcc | 0xd0 => (1, 0),
+            0xcd | 0xd1 => (2, 0),
+            0xca | 0xce | 0xd2 => (4, 0),
+            0xcb | 0xcf | 0xd3 => (8, 0),
+            0xd4 => (2, 0),
+            0xd5 => (3, 0),
+            0xd6 => (5, 0),
+            0xd7 => (9, 0),
+            0xd8 => (17, 0),
+            0xdc => (2, msgpack_uint(buf, pos, 2)?),
+            0xdd => (4, msgpack_uint(buf, pos, 4)?),
+            0xde => (2, 2 * msgpack_uint(buf, pos, 2)?),
+            0xdf => (4, 2 * msgpack_uint(buf, pos, 4)?),
+            _ => (0, 0),
+        };
+
+        pos += data_len;
+        pending += values;
+    }
+
+    if pos > buf.len() {
+        return None;
     }
+
+    Some(pos)
+}
+
+/// Read a big-endian unsigned integer of the given size at the given position
+/// of buf, or None if buf is too short.
+fn msgpack_uint(buf: &[u8], pos: usize, size: usize) -> Option<usize> {
+    let bytes = buf.get(pos..pos + size)?;
+    Some(bytes.iter().fold(0, |n, b| (n << 8) | *b as usize))
 }
 
+/// A page of results from a paginated request.
 pub struct PaginatedResult<T: Decode> {
     res: Response,
     options: T::Options,
+    context: Option<PageContext>,
+}
+
+/// The client and request used to retrieve a page, which are needed to follow
+/// the Link headers in the response to other pages.
+struct PageContext {
+    rest: rest::Rest,
+    req: reqwest::Request,
+    auth: Option<AuthMode>,
 }
 
 impl<T: Decode> PaginatedResult<T> {
     pub fn new(res: Response, options: T::Options) -> Self {
-        Self { res, options }
+        Self::with_context(res, options, None)
+    }
+
+    fn with_context(res: Response, options: T::Options, context: Option<PageContext>) -> Self {
+        Self {
+            res,
+            options,
+            context,
+        }
+    }
+
+    /// The HTTP status code of the response.
+    pub fn status_code(&self) -> reqwest::StatusCode {
+        self.res.status()
+    }
+
+    /// Returns whether the response has a 2xx status code.
+    pub fn success(&self) -> bool {
+        self.status_code().is_success()
+    }
+
+    /// The HTTP headers of the response.
+    pub fn headers(&self) -> &HeaderMap {
+        self.res.headers()
+    }
+
+    /// The Ably error code of an unsuccessful response, from its
+    /// X-Ably-Errorcode header (HP6).
+    pub fn error_code(&self) -> Option<u32> {
+        self.headers()
+            .get("x-ably-errorcode")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok())
+    }
+
+    /// The Ably error message of an unsuccessful response, from its
+    /// X-Ably-Errormessage header (HP7).
+    pub fn error_message(&self) -> Option<&str> {
+        self.headers()
+            .get("x-ably-errormessage")
+            .and_then(|v| v.to_str().ok())
+    }
+
+    /// Returns whether there is a next page (TG6).
+    pub fn has_next(&self) -> bool {
+        self.link("next").is_some()
+    }
+
+    /// Returns whether this is the last page (TG7).
+    pub fn is_last(&self) -> bool {
+        !self.has_next()
+    }
+
+    /// Retrieve the next page, returning None if this is the last page (TG4).
+    pub async fn next(&self) -> Result<Option<Self>> {
+        self.follow("next").await
+    }
+
+    /// Retrieve the first page, returning None if the response did not
+    /// include a link to the first page (TG5).
+    pub async fn first(&self) -> Result<Option<Self>> {
+        self.follow("first").await
+    }
+
+    /// Retrieve the current page again, returning None if the response did
+    /// not include a link to the current page.
+    pub async fn current(&self) -> Result<Option<Self>> {
+        self.follow("current").await
     }
 
     /// Returns the page's list of items, running them through the item hadler.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the response body can't be deserialized, or if
+    /// any of the items can't be decoded (e.g. a message with an unsupported
+    /// encoding, or encrypted data without the cipher to decrypt it). Use
+    /// items_with_errors to get the items which can't be decoded instead.
     pub async fn items(self) -> Result<Vec<T::Item>> {
+        // An unsuccessful response has an error rather than items.
+        if !self.success() {
+            return Ok(Vec::new());
+        }
+
         let mut items: Vec<T::Item> = self.res.body().await?;
-        items
-            .iter_mut()
-            .for_each(|item| T::decode(item, &self.options));
+        for item in items.iter_mut() {
+            T::decode(item, &self.options)?;
+        }
 
         Ok(items)
     }
 
-    fn next_link(&self) -> Option<Link> {
+    /// Returns the page's list of items, running them through the item
+    /// handler, along with the error from decoding each item if it couldn't
+    /// be decoded.
+    ///
+    /// An item which can't be decoded is returned with the encodings which
+    /// couldn't be applied left in place, and the error is logged (RSL6b).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the response body can't be deserialized.
+    pub async fn items_with_errors(self) -> Result<Vec<(T::Item, Option<Error>)>> {
+        // An unsuccessful response has an error rather than items.
+        if !self.success() {
+            return Ok(Vec::new());
+        }
+
+        let rest = self.context.map(|context| context.rest);
+        let items: Vec<T::Item> = self.res.body().await?;
+
+        Ok(items
+            .into_iter()
+            .map(|mut item| {
+                let err = T::decode(&mut item, &self.options).err();
+                if let (Some(err), Some(rest)) = (&err, &rest) {
+                    rest.inner.opts.log(LogLevel::Error, || {
+                        format!("unable to decode item: {}", log::describe_error(err))
+                    });
+                }
+                (item, err)
+            })
+            .collect())
+    }
+
+    /// Returns the page's items as a stream, running them through the item
+    /// handler.
+    ///
+    /// MessagePack responses are decoded incrementally as the body is
+    /// received, and JSON responses are decoded once the whole body has been
+    /// received. An item which can't be decoded is yielded as an error, and
+    /// the stream continues with the next item.
+    pub fn item_stream(self) -> impl Stream<Item = Result<T::Item>> {
+        let is_msgpack = self.success()
+            && matches!(
+                self.res.content_type(),
+                Some(content_type) if content_type.essence_str() == "application/x-msgpack"
+            );
+
+        if is_msgpack {
+            let options = self.options;
+            let items = self.res.msgpack_items().map(move |item| {
+                let mut item = item?;
+                T::decode(&mut item, &options)?;
+                Ok(item)
+            });
+            future::Either::Left(items)
+        } else {
+            let options = self.options;
+            let items = stream::once(async move {
+                // An unsuccessful response has an error rather than items.
+                if !self.res.status().is_success() {
+                    return Ok(Vec::new());
+                }
+                self.res.body::<Vec<T::Item>>().await
+            })
+            .map_ok(move |items| {
+                let options = options.clone();
+                stream::iter(items.into_iter().map(move |mut item| {
+                    T::decode(&mut item, &options)?;
+                    Ok(item)
+                }))
+            })
+            .try_flatten();
+            future::Either::Right(items)
+        }
+    }
+
+    /// Returns the raw body of the page without decoding it, for example to
+    /// archive the exact payload received from Ably.
+    pub async fn bytes(self) -> Result<Bytes> {
+        self.res.bytes().await
+    }
+
+    /// Returns the underlying Response of the page.
+    pub fn into_response(self) -> Response {
+        self.res
+    }
+
+    /// Send a request for the page referenced by the Link header with the
+    /// given rel.
+    async fn follow(&self, rel: &str) -> Result<Option<Self>> {
+        let link = match self.link(rel) {
+            Some(link) => link,
+            None => return Ok(None),
+        };
+
+        let context = self.context.as_ref().ok_or_else(|| {
+            Error::new(
+                ErrorCode::BadRequest,
+                "page was not retrieved by a paginated request",
+            )
+        })?;
+
+        let mut req = context
+            .req
+            .try_clone()
+            .ok_or_else(|| Error::new(ErrorCode::BadRequest, "not a pageable request"))?;
+        *req.url_mut() = link.resolve(req.url())?;
+        let template = req.try_clone();
+
+        let res = context.rest.send(req, context.auth).await?;
+
+        Ok(Some(Self::with_context(
+            res,
+            self.options.clone(),
+            template.map(|req| PageContext {
+                rest: context.rest.clone(),
+                req,
+                auth: context.auth,
+            }),
+        )))
+    }
+
+    /// Returns the links in the Link headers of the response.
+    ///
+    /// Header values which can't be parsed are ignored.
+    pub fn links(&self) -> Vec<Link> {
         self.res
             .inner
             .headers()
             .get_all(reqwest::header::LINK)
             .iter()
-            .flat_map(Link::try_from)
-            .find(|l| l.rel == "next")
+            .filter_map(|v| v.to_str().ok())
+            .filter_map(|v| Link::parse(v).ok())
+            .flatten()
+            .collect()
+    }
+
+    fn link(&self, rel: &str) -> Option<Link> {
+        self.links().into_iter().find(|l| l.has_rel(rel))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msgpack_response(body: Vec<u8>) -> Response {
+        let res = ::http::Response::builder()
+            .header(reqwest::header::CONTENT_TYPE, "application/x-msgpack")
+            .body(body)
+            .unwrap();
+        Response::new(res.into())
+    }
+
+    #[test]
+    fn msgpack_value_len_requires_whole_value() {
+        let value = serde_json::json!({"name": "test", "data": [1, 2.5, null, "x".repeat(300)]});
+        let data = rmp_serde::to_vec(&value).unwrap();
+
+        assert_eq!(msgpack_value_len(&data), Some(data.len()));
+        for len in 0..data.len() {
+            assert_eq!(msgpack_value_len(&data[..len]), None, "prefix of {}", len);
+        }
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn msgpack_value_len_matches_encoded_len(value in crate::testing::json_value()) {
+            let data = rmp_serde::to_vec(&value).unwrap();
+
+            proptest::prop_assert_eq!(msgpack_value_len(&data), Some(data.len()));
+            for len in 0..data.len() {
+                proptest::prop_assert_eq!(msgpack_value_len(&data[..len]), None);
+            }
+        }
+
+        #[test]
+        fn msgpack_header_parsing_never_panics(data in proptest::collection::vec(proptest::prelude::any::<u8>(), 0..64)) {
+            let _ = msgpack_array_header(&data);
+            let _ = msgpack_value_len(&data);
+        }
+    }
+
+    #[tokio::test]
+    async fn msgpack_items_decodes_each_item() {
+        let values: Vec<json::Value> = (0..20)
+            .map(|i| serde_json::json!({"id": i, "data": "x".repeat(i * 10)}))
+            .collect();
+        let res = msgpack_response(rmp_serde::to_vec(&values).unwrap());
+
+        let items: Vec<json::Value> = res.msgpack_items().try_collect().await.unwrap();
+        assert_eq!(items, values);
+    }
+
+    #[tokio::test]
+    async fn msgpack_items_fails_on_truncated_body() {
+        let values = vec!["a", "b", "c"];
+        let mut data = rmp_serde::to_vec(&values).unwrap();
+        data.pop();
+        let res = msgpack_response(data);
+
+        let items: Vec<Result<String>> = res.msgpack_items().collect().await;
+        assert_eq!(items.len(), 3);
+        assert_eq!(
+            items[2].as_ref().unwrap_err().code,
+            ErrorCode::InvalidRequestBody
+        );
+    }
+
+    #[test]
+    fn params_set_replaces_existing_values() {
+        let params = Params::from([("a", "1"), ("b", "2")]).set("a", "3");
+        assert_eq!(
+            params.iter().collect::<Vec<_>>(),
+            vec![("b", "2"), ("a", "3")]
+        );
+    }
+
+    #[test]
+    fn params_merge() {
+        let mut params = Params::from([("a", "1"), ("b", "2")]);
+        params.merge(Params::from([("b", "3"), ("c", "4")]));
+        assert_eq!(params.get("a"), Some("1"));
+        assert_eq!(params.get("b"), Some("3"));
+        assert_eq!(params.get("c"), Some("4"));
+    }
+
+    #[test]
+    fn params_serialize_as_query() {
+        let req = reqwest::Client::new()
+            .get("https://rest.ably.io/time")
+            .query(&Params::from([("a", "1"), ("b", "x y")]))
+            .build()
+            .unwrap();
+        assert_eq!(req.url().query(), Some("a=1&b=x+y"));
+    }
+
+    #[test]
+    fn headers_merge_replaces_all_values() {
+        let mut map = HeaderMap::new();
+        map.append("x-a", HeaderValue::from_static("1"));
+        map.append("x-a", HeaderValue::from_static("2"));
+        map.append("x-b", HeaderValue::from_static("3"));
+        let mut headers = Headers::from(map);
+
+        headers.merge(Headers::from_pairs([("X-A", "4")]).unwrap());
+
+        let map = HeaderMap::from(headers);
+        assert_eq!(map.get_all("x-a").iter().collect::<Vec<_>>(), vec!["4"]);
+        assert_eq!(map["x-b"], "3");
+    }
+
+    #[test]
+    fn headers_from_invalid_pairs() {
+        let err = Headers::from_pairs([("invalid name", "a")]).expect_err("Expected invalid name");
+        assert_eq!(err.code, ErrorCode::InvalidHeader);
+        assert!(Headers::from_pairs([("X-A", "invalid\nvalue")]).is_err());
+    }
+
+    #[test]
+    fn link_parse_ably_link() {
+        let links = Link::parse(
+            r#"<./messages?limit=10&direction=forwards&cont=true&format=json&firstStart=0&end=1635552598723>; rel="next""#,
+        )
+        .unwrap();
+        assert_eq!(
+            links,
+            vec![Link {
+                target: "./messages?limit=10&direction=forwards&cont=true&format=json&firstStart=0&end=1635552598723".to_string(),
+                rels: vec!["next".to_string()],
+                params: vec![],
+            }]
+        );
+    }
+
+    #[test]
+    fn link_parse_multiple_links() {
+        let links = Link::parse(
+            r#"<./messages?start=0>; rel="first", <./messages?start=0>; rel="current",<./messages?cont=true>;rel=next"#,
+        )
+        .unwrap();
+        assert_eq!(links.len(), 3);
+        assert!(links[0].has_rel("first"));
+        assert!(links[1].has_rel("current"));
+        assert!(links[2].has_rel("next"));
+        assert_eq!(links[2].target, "./messages?cont=true");
+    }
+
+    #[test]
+    fn link_parse_quoted_params() {
+        let links = Link::parse(
+            r#"<https://example.com/a,b;c>; rel="next last"; title="a \"quoted\", title; with separators"; hreflang=en"#,
+        )
+        .unwrap();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].target, "https://example.com/a,b;c");
+        assert_eq!(links[0].rels, vec!["next", "last"]);
+        assert_eq!(
+            links[0].param("title"),
+            Some(r#"a "quoted", title; with separators"#)
+        );
+        assert_eq!(links[0].param("HREFLANG"), Some("en"));
+    }
+
+    #[test]
+    fn link_parse_uses_first_rel() {
+        let links = Link::parse(r#"<./a>; REL=next; rel=prev"#).unwrap();
+        assert_eq!(links[0].rels, vec!["next"]);
+    }
+
+    #[test]
+    fn link_parse_invalid() {
+        for header in [
+            "./messages; rel=next",
+            "<./messages; rel=next",
+            r#"<./messages>; rel="next"#,
+            "<./a>; rel=next <./b>",
+            "<./a>; =next",
+        ] {
+            let err = Link::parse(header).expect_err(header);
+            assert_eq!(err.code, ErrorCode::InvalidHeader);
+        }
+    }
+
+    #[test]
+    fn link_resolve() {
+        let base =
+            reqwest::Url::parse("https://rest.ably.io/channels/test/messages?limit=10").unwrap();
+
+        let link = &Link::parse("<./messages?cont=true>; rel=next").unwrap()[0];
+        assert_eq!(
+            link.resolve(&base).unwrap().as_str(),
+            "https://rest.ably.io/channels/test/messages?cont=true"
+        );
+
+        let link = &Link::parse("<https://rest.ably.io:443/x?y=z>; rel=next").unwrap()[0];
+        assert_eq!(
+            link.resolve(&base).unwrap().as_str(),
+            "https://rest.ably.io/x?y=z"
+        );
+    }
+
+    #[test]
+    fn link_resolve_rejects_other_origins() {
+        let base =
+            reqwest::Url::parse("https://rest.ably.io/channels/test/messages?limit=10").unwrap();
+
+        for target in [
+            "https://other.ably.io/x?y=z",
+            "http://rest.ably.io/x?y=z",
+            "https://rest.ably.io:8443/x?y=z",
+            "//other.ably.io/x?y=z",
+        ] {
+            let link = &Link::parse(&format!("<{}>; rel=next", target)).unwrap()[0];
+            let err = link.resolve(&base).expect_err(target);
+            assert_eq!(err.code, ErrorCode::InvalidHeader, "{}", target);
+        }
+    }
+
+    #[test]
+    fn retry_after_seconds() {
+        let v = HeaderValue::from_static("120");
+        assert_eq!(parse_retry_after(&v), Some(Duration::from_secs(120)));
+    }
+
+    #[test]
+    fn retry_after_date() {
+        let v = HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT");
+        assert_eq!(parse_retry_after(&v), Some(Duration::ZERO));
+
+        let date = (chrono::Utc::now() + chrono::Duration::minutes(5)).to_rfc2822();
+        let v = HeaderValue::from_str(&date).unwrap();
+        let delay = parse_retry_after(&v).expect("Expected a delay");
+        assert!(delay > Duration::from_secs(4 * 60) && delay <= Duration::from_secs(5 * 60));
+    }
+
+    #[test]
+    fn retry_after_invalid() {
+        let v = HeaderValue::from_static("soon");
+        assert_eq!(parse_retry_after(&v), None);
     }
 }
@@ -1,4 +1,135 @@
+use std::fmt;
+
+use chrono::{SecondsFormat, TimeZone, Utc};
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+
 pub use serde_json::Value;
 
 /// A convenient type alias for a JSON object with string keys.
 pub type Map = serde_json::Map<String, Value>;
+
+/// The MessagePack extension type of timestamps.
+const MSGPACK_TIMESTAMP_EXT: i8 = -1;
+
+/// A Visitor which deserializes a JSON Value from any self-describing format,
+/// mapping values which can't be represented in JSON as faithfully as
+/// possible rather than failing:
+///
+/// * binary data is mapped to a base64 encoded string
+/// * MessagePack timestamps are mapped to an RFC 3339 string
+/// * other MessagePack extension types are mapped to an object containing the
+///   extension type and the base64 encoded data
+pub(crate) struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any valid JSON value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::Number(v.into()))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Value, E> {
+        Ok(Value::Number(v.into()))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Value, E> {
+        Ok(serde_json::Number::from_f64(v).map_or(Value::Null, Value::Number))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Value, E> {
+        Ok(Value::String(base64::encode(v)))
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    /// MessagePack extension types are deserialized as a newtype struct
+    /// containing the extension type and data.
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Value, D::Error> {
+        let (ext, data): (i8, serde_bytes::ByteBuf) = Deserialize::deserialize(deserializer)?;
+
+        if ext != MSGPACK_TIMESTAMP_EXT {
+            return Ok(serde_json::json!({
+                "type": ext,
+                "data": base64::encode(&data),
+            }));
+        }
+
+        msgpack_timestamp(&data)
+            .map(Value::String)
+            .ok_or_else(|| de::Error::custom("invalid MessagePack timestamp"))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(JsonValue(value)) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(Value::Array(values))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut values = Map::new();
+        while let Some((key, JsonValue(value))) = map.next_entry::<String, _>()? {
+            values.insert(key, value);
+        }
+        Ok(Value::Object(values))
+    }
+}
+
+/// A JSON Value deserialized using ValueVisitor.
+struct JsonValue(Value);
+
+impl<'de> Deserialize<'de> for JsonValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ValueVisitor).map(Self)
+    }
+}
+
+/// Decode the data of a MessagePack timestamp extension into an RFC 3339
+/// string, returning None if the data is invalid.
+fn msgpack_timestamp(data: &[u8]) -> Option<String> {
+    let (secs, nanos) = match data.len() {
+        4 => (u32::from_be_bytes(data.try_into().ok()?) as i64, 0),
+        8 => {
+            let v = u64::from_be_bytes(data.try_into().ok()?);
+            ((v & 0x3_ffff_ffff) as i64, (v >> 34) as u32)
+        }
+        12 => (
+            i64::from_be_bytes(data[4..].try_into().ok()?),
+            u32::from_be_bytes(data[..4].try_into().ok()?),
+        ),
+        _ => return None,
+    };
+
+    let time = Utc.timestamp_opt(secs, nanos).single()?;
+    Some(time.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+}
@@ -0,0 +1,100 @@
+//! Logging of the decisions the client makes internally, such as retrying
+//! requests against fallback hosts, renewing tokens and following links to
+//! subsequent pages, so that they can be observed through an application's
+//! own logging framework.
+//!
+//! Log messages never include credentials: keys and tokens are omitted, and
+//! requests are described by their method, host and path without their
+//! query string or headers.
+//!
+//! # Example
+//!
+//! ```
+//! use ably::log::{LogHandler, LogLevel};
+//!
+//! #[derive(Debug)]
+//! struct Stderr;
+//!
+//! impl LogHandler for Stderr {
+//!     fn log(&self, level: LogLevel, message: &str) {
+//!         eprintln!("[ably] {:?}: {}", level, message);
+//!     }
+//! }
+//!
+//! let client = ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc")
+//!     .log_handler(Stderr)
+//!     .log_level(LogLevel::Debug)
+//!     .rest()?;
+//! # Ok::<(), ably::Error>(())
+//! ```
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::error::Error;
+
+/// The severity of a log message, in increasing order of verbosity.
+///
+/// A LogHandler is passed messages at the client's log level and below, so
+/// LogLevel::Debug includes errors, warnings and info messages.
+///
+/// Defaults to LogLevel::Error.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogLevel {
+    /// A request or operation failed.
+    #[default]
+    Error,
+    /// Something unexpected happened which the client recovered from, such
+    /// as a request being retried against a fallback host.
+    Warn,
+    /// A notable change of the client's state, such as a token being
+    /// obtained.
+    Info,
+    /// The decisions the client makes, such as retries, token renewals and
+    /// pagination.
+    Debug,
+    /// Every request the client sends and the status of its response.
+    Verbose,
+}
+
+/// Handles the log messages of a client, for example by forwarding them to
+/// the log or tracing crates.
+///
+/// The LogHandler is set with ClientOptions::log_handler and is only passed
+/// messages at or below the level set with ClientOptions::log_level.
+pub trait LogHandler: fmt::Debug + Send + Sync {
+    /// Called with each log message.
+    fn log(&self, level: LogLevel, message: &str);
+}
+
+impl<T: LogHandler + ?Sized> LogHandler for Arc<T> {
+    fn log(&self, level: LogLevel, message: &str) {
+        (**self).log(level, message)
+    }
+}
+
+/// Describes a request by its method, host and path, omitting the query
+/// string and headers which may contain credentials.
+pub(crate) fn describe_request(req: &reqwest::Request) -> String {
+    let url = req.url();
+    format!(
+        "{} {}{}",
+        req.method(),
+        url.host_str().unwrap_or_default(),
+        url.path()
+    )
+}
+
+/// Describes an error by its message and codes, omitting its cause which may
+/// include a URL containing credentials.
+pub(crate) fn describe_error(err: &Error) -> String {
+    match err.status_code {
+        Some(status) => format!(
+            "{}; statusCode={}; code={}",
+            err.message,
+            status,
+            err.code.code()
+        ),
+        None => format!("{}; code={}", err.message, err.code.code()),
+    }
+}
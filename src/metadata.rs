@@ -0,0 +1,154 @@
+use futures::stream::Stream;
+use serde::Deserialize;
+
+use crate::rest::{Decode, Rest};
+use crate::{http, Result};
+
+/// The channel metadata API, for discovering the active channels of an app.
+pub struct ChannelsMetadata<'a> {
+    rest: &'a Rest,
+}
+
+impl<'a> ChannelsMetadata<'a> {
+    pub(crate) fn new(rest: &'a Rest) -> Self {
+        Self { rest }
+    }
+
+    /// Start building a request to list the active channels of the app,
+    /// which requires the channel-metadata capability.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> ably::Result<()> {
+    /// use futures::TryStreamExt;
+    ///
+    /// # let channels = serde_json::json!([{"channelId": "rust-example", "status": {"isActive": true}}]);
+    /// # let transport = ably::mock::MockTransport::new().on(ably::http::Method::GET, "/channels", 200, channels);
+    /// # let client = ably::Rest::with_http_executor(ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc"), transport)?;
+    /// let channels: Vec<_> = client
+    ///     .channels_metadata()
+    ///     .list()
+    ///     .prefix("rust-")
+    ///     .items()
+    ///     .try_collect()
+    ///     .await?;
+    ///
+    /// assert_eq!(channels[0].channel_id, "rust-example");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn list(&self) -> RequestBuilder<'a> {
+        RequestBuilder {
+            inner: self
+                .rest
+                .paginated_request_with_options(http::Method::GET, "/channels", ()),
+        }
+    }
+
+    /// Retrieve the details of the named channel, including its occupancy
+    /// metrics, which requires the channel-metadata capability.
+    ///
+    /// This is equivalent to Channel::status.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> ably::Result<()> {
+    /// # let details = serde_json::json!({"channelId": "rust-example", "status": {"isActive": true, "occupancy": {"metrics": {"publishers": 1}}}});
+    /// # let transport = ably::mock::MockTransport::new().on(ably::http::Method::GET, "/channels/rust-example", 200, details);
+    /// # let client = ably::Rest::with_http_executor(ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc"), transport)?;
+    /// let details = client.channels_metadata().get("rust-example").await?;
+    ///
+    /// assert_eq!(details.status.occupancy.metrics.publishers, 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get(&self, name: &str) -> Result<ChannelDetails> {
+        self.rest
+            .request(http::Method::GET, &format!("/channels/{}", name))
+            .send()
+            .await?
+            .body()
+            .await
+    }
+}
+
+/// A builder to construct a request to list active channels.
+pub struct RequestBuilder<'a> {
+    inner: http::PaginatedRequestBuilder<'a, ChannelDetails>,
+}
+
+impl<'a> RequestBuilder<'a> {
+    /// Limit the number of results per page.
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.inner = self.inner.limit(limit);
+        self
+    }
+
+    /// Only list channels whose names start with the given prefix.
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.inner = self.inner.params(&[("prefix", prefix)]);
+        self
+    }
+
+    /// Request a stream of pages of channel details.
+    pub fn pages(self) -> impl Stream<Item = Result<http::PaginatedResult<ChannelDetails>>> + 'a {
+        self.inner.pages()
+    }
+
+    /// Request a stream of channel details, transparently walking pages.
+    pub fn items(self) -> impl Stream<Item = Result<ChannelDetails>> + 'a {
+        self.inner.items()
+    }
+
+    /// Retrieve the first page of channel details.
+    pub async fn send(self) -> Result<http::PaginatedResult<ChannelDetails>> {
+        self.inner.send().await
+    }
+}
+
+/// The details of a channel retrieved from the [REST channel status
+/// endpoint].
+///
+/// [REST channel status endpoint]: https://ably.com/docs/api/rest-api#channel-status
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ChannelDetails {
+    pub channel_id: String,
+    pub status: ChannelStatus,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ChannelStatus {
+    pub is_active: bool,
+    pub occupancy: ChannelOccupancy,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ChannelOccupancy {
+    pub metrics: ChannelMetrics,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ChannelMetrics {
+    pub connections: u64,
+    pub presence_connections: u64,
+    pub presence_members: u64,
+    pub presence_subscribers: u64,
+    pub publishers: u64,
+    pub subscribers: u64,
+}
+
+impl Decode for ChannelDetails {
+    type Options = ();
+    type Item = Self;
+    fn decode(_item: &mut Self::Item, _options: &Self::Options) -> Result<()> {
+        Ok(())
+    }
+}
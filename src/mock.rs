@@ -0,0 +1,220 @@
+//! A mock HTTP transport for testing code which uses a Rest client without
+//! sending requests to Ably.
+//!
+//! # Example
+//!
+//! ```
+//! # #[tokio::main]
+//! # async fn main() -> ably::Result<()> {
+//! use std::sync::Arc;
+//!
+//! use ably::http::Method;
+//! use ably::mock::MockTransport;
+//! use serde_json::json;
+//!
+//! let transport = Arc::new(MockTransport::new().on(Method::GET, "/time", 200, json!([1650000000000_i64])));
+//! let client = ably::Rest::with_http_executor(
+//!     ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc"),
+//!     transport.clone(),
+//! )?;
+//!
+//! let time = client.time().await?;
+//! assert_eq!(time.timestamp_millis(), 1650000000000);
+//!
+//! let requests = transport.requests();
+//! assert_eq!(requests[0].method, Method::GET);
+//! assert_eq!(requests[0].path, "/time");
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use chrono::Utc;
+use futures::future::BoxFuture;
+
+use crate::client::{AblyHistory, AblyPublisher};
+use crate::http::{HeaderMap, HttpExecutor, Method};
+use crate::rest::Message;
+use crate::{json, Result};
+
+/// A HttpExecutor which responds to requests with canned JSON responses and
+/// records the requests it receives.
+///
+/// Requests which don't match a response registered with `on` are passed to
+/// the function registered with `respond_with`, or otherwise receive a 404
+/// response containing an Ably error.
+#[derive(Debug, Default)]
+pub struct MockTransport {
+    routes: Vec<Route>,
+    fallback: Option<Responder>,
+    requests: Mutex<Vec<MockRequest>>,
+}
+
+/// A function which returns the status code and JSON body of the response
+/// to a request.
+type RespondFn = dyn Fn(&reqwest::Request) -> (u16, json::Value) + Send + Sync;
+
+struct Responder(Box<RespondFn>);
+
+impl std::fmt::Debug for Responder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Responder")
+    }
+}
+
+#[derive(Debug)]
+struct Route {
+    method: Method,
+    path: String,
+    status: u16,
+    body: json::Value,
+}
+
+/// A request received by a MockTransport.
+#[derive(Clone, Debug)]
+pub struct MockRequest {
+    pub method: Method,
+    pub path: String,
+    pub query: Option<String>,
+    pub headers: HeaderMap,
+    pub body: Option<Vec<u8>>,
+}
+
+impl MockTransport {
+    /// Returns a MockTransport with no registered responses.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Respond to requests with the given method and path with the given
+    /// status code and JSON body.
+    ///
+    /// If multiple responses match a request, the first registered is used.
+    pub fn on(
+        mut self,
+        method: Method,
+        path: impl Into<String>,
+        status: u16,
+        body: json::Value,
+    ) -> Self {
+        self.routes.push(Route {
+            method,
+            path: path.into(),
+            status,
+            body,
+        });
+        self
+    }
+
+    /// Respond to requests which don't match a response registered with `on`
+    /// using the given function, which returns the status code and JSON body
+    /// of the response, for example to mock a stateful part of the REST API.
+    pub fn respond_with(
+        mut self,
+        respond: impl Fn(&reqwest::Request) -> (u16, json::Value) + Send + Sync + 'static,
+    ) -> Self {
+        self.fallback = Some(Responder(Box::new(respond)));
+        self
+    }
+
+    /// Returns the requests received so far, in the order they were
+    /// received.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    fn respond(&self, req: &reqwest::Request) -> (u16, json::Value) {
+        self.routes
+            .iter()
+            .find(|route| route.method == req.method() && route.path == req.url().path())
+            .map(|route| (route.status, route.body.clone()))
+            .or_else(|| self.fallback.as_ref().map(|respond| (respond.0)(req)))
+            .unwrap_or_else(|| {
+                let message = format!("no mock response for {} {}", req.method(), req.url().path());
+                let body = serde_json::json!({
+                    "error": {"code": 40400, "statusCode": 404, "message": message}
+                });
+                (404, body)
+            })
+    }
+}
+
+impl HttpExecutor for MockTransport {
+    fn execute(&self, req: reqwest::Request) -> BoxFuture<'_, Result<reqwest::Response>> {
+        let (status, body) = self.respond(&req);
+
+        self.requests.lock().unwrap().push(MockRequest {
+            method: req.method().clone(),
+            path: req.url().path().to_string(),
+            query: req.url().query().map(ToString::to_string),
+            headers: req.headers().clone(),
+            body: req.body().and_then(|b| b.as_bytes()).map(<[u8]>::to_vec),
+        });
+
+        Box::pin(async move {
+            let res = ::http::Response::builder()
+                .status(status)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(serde_json::to_vec(&body)?)
+                .map_err(|err| {
+                    crate::Error::with_cause(
+                        crate::error::ErrorCode::InternalError,
+                        err,
+                        "invalid mock response",
+                    )
+                })?;
+            Ok(res.into())
+        })
+    }
+}
+
+/// An in-memory fake implementing AblyPublisher and AblyHistory, for unit
+/// testing code which depends on those traits rather than a real client.
+///
+/// Published messages are stored per channel, and returned by history most
+/// recent first.
+#[derive(Debug, Default)]
+pub struct FakeClient {
+    channels: Mutex<HashMap<String, Vec<Message>>>,
+}
+
+impl FakeClient {
+    /// Returns a FakeClient with no published messages.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the messages published to the given channel, in the order
+    /// they were published.
+    pub fn messages(&self, channel: &str) -> Vec<Message> {
+        self.channels
+            .lock()
+            .unwrap()
+            .get(channel)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+impl AblyPublisher for FakeClient {
+    fn publish<'a>(&'a self, channel: &'a str, mut message: Message) -> BoxFuture<'a, Result<()>> {
+        message.timestamp.get_or_insert_with(Utc::now);
+        self.channels
+            .lock()
+            .unwrap()
+            .entry(channel.to_string())
+            .or_default()
+            .push(message);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+impl AblyHistory for FakeClient {
+    fn history<'a>(&'a self, channel: &'a str) -> BoxFuture<'a, Result<Vec<Message>>> {
+        let mut messages = self.messages(channel);
+        messages.reverse();
+        Box::pin(async { Ok(messages) })
+    }
+}
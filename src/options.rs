@@ -1,12 +1,25 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use std::time::Duration;
 
 use crate::auth::{AuthCallback, Credential};
 use crate::error::*;
-use crate::{auth, http, rest, Result};
+use crate::protocol::Protocol;
+use crate::{auth, http, log, rest, Result};
 
 static REST_HOST: &str = "rest.ably.io";
 
+/// The fallback hosts used when neither the environment nor rest_host is
+/// set (RSC15a).
+static DEFAULT_FALLBACK_HOSTS: [&str; 5] = [
+    "a.ably-realtime.com",
+    "b.ably-realtime.com",
+    "c.ably-realtime.com",
+    "d.ably-realtime.com",
+    "e.ably-realtime.com",
+];
+
 /// [Ably client options] for initialising a REST or Realtime client.
 ///
 /// [Ably client options]: https://ably.com/documentation/rest/types#client-options
@@ -20,12 +33,12 @@ pub struct ClientOptions {
     pub(crate) auth_method: http::Method,
 
     /// The HTTP headers to include when requesting a token from auth_url.
-    pub(crate) auth_headers: Option<http::HeaderMap>,
+    pub(crate) auth_headers: http::Headers,
 
     /// The HTTP params to use when requesting a token from auth_url, which are
     /// included in the query string when auth_method is GET, or in the
     /// form-encoded body when auth_method is POST.
-    pub(crate) auth_params: Option<http::UrlQuery>,
+    pub(crate) auth_params: http::Params,
 
     /// Use TLS for all connections. Defaults to true.
     pub(crate) tls: bool,
@@ -40,19 +53,32 @@ pub struct ClientOptions {
     /// An optional custom environment used to construct API URLs.
     pub(crate) environment: Option<String>,
 
+    /// The endpoint the client was configured with, if set, which determines
+    /// the scheme, host and port of API URLs.
+    pub(crate) endpoint: Option<String>,
+
     /// Enable idempotent REST publishing. Defaults to false.
     ///
     /// See https://faqs.ably.com/what-is-idempotent-publishing
     pub(crate) idempotent_rest_publishing: bool,
 
     /// The list of fallback hosts to use in the case of an error necessitating
-    /// the use of an alternative host. Defaults to [a-e].ably-realtime.com.
-    pub(crate) fallback_hosts: Vec<String>,
+    /// the use of an alternative host, if set. Defaults to
+    /// [a-e].ably-realtime.com, or those of the environment if set.
+    pub(crate) fallback_hosts: Option<Vec<String>>,
+
+    /// Use the default fallback hosts even when the environment or rest_host
+    /// is set. Defaults to false.
+    pub(crate) fallback_hosts_use_default: bool,
 
     /// Encode requests using the binary msgpack encoding, or the JSON
     /// encoding. Defaults to msgpack.
     pub(crate) format: rest::Format,
 
+    /// The Ably protocol version sent in the X-Ably-Version header, which
+    /// determines which wire features can be used. Defaults to 1.2.
+    pub(crate) protocol: Protocol,
+
     /// Query the Ably system for the current time when issuing tokens.
     /// Defaults to false.
     pub(crate) query_time: bool,
@@ -60,6 +86,10 @@ pub struct ClientOptions {
     /// Override the default parameters used to request Ably tokens.
     pub(crate) default_token_params: Option<auth::TokenParams>,
 
+    /// The options channels obtained from the client are created with by
+    /// default, if set.
+    pub(crate) default_channel_options: Option<rest::ChannelOptions>,
+
     /// Automatically connect when the Realtime library is instantiated.
     /// Defaults to true.
     pub(crate) auto_connect: bool,
@@ -75,10 +105,10 @@ pub struct ClientOptions {
     pub(crate) realtime_host: String,
 
     /// The TCP port for non-TLS requests. Defaults to 80.
-    pub(crate) port: u32,
+    pub(crate) port: u16,
 
     /// The TCP port for TLS requests. Defaults to 443.
-    pub(crate) tls_port: u32,
+    pub(crate) tls_port: u16,
 
     /// How long to wait before attempting to re-establish a connection which
     /// is in the DISCONNECTED state. Defaults to 15s.
@@ -101,6 +131,17 @@ pub struct ClientOptions {
     /// received. Defaults to 10s.
     pub(crate) http_request_timeout: Duration,
 
+    /// The maximum number of idle connections to keep open to each host, or
+    /// None for reqwest's default of no limit.
+    pub(crate) pool_max_idle_per_host: Option<usize>,
+
+    /// How long idle connections are kept open, or None for reqwest's
+    /// default of 90s.
+    pub(crate) pool_idle_timeout: Option<Duration>,
+
+    /// The interval of TCP keepalive probes, or None to not send them.
+    pub(crate) tcp_keepalive: Option<Duration>,
+
     /// The maximum number of fallback hosts to try when the primary host is
     /// unreachable or it indicates that the request is unserviceable.
     pub(crate) http_max_retry_count: usize,
@@ -109,6 +150,16 @@ pub struct ClientOptions {
     /// the request as failed. Defaults to 15s.
     pub(crate) http_max_retry_duration: Duration,
 
+    /// The number of times to retry a request which is rejected with a 429
+    /// response containing a Retry-After header, after waiting for the
+    /// requested duration. Defaults to 0, meaning rate limited requests
+    /// fail immediately.
+    pub(crate) rate_limit_retry_count: usize,
+
+    /// The maximum number of HTTP requests to have in flight at once, or
+    /// None for no limit. Defaults to None.
+    pub(crate) max_concurrent_requests: Option<usize>,
+
     /// The maximum size of messages that can be published in a single request.
     /// Defaults to 64KiB.
     pub(crate) max_message_size: u64,
@@ -117,13 +168,129 @@ pub struct ClientOptions {
     /// 512KiB.
     pub(crate) max_frame_size: u64,
 
+    /// The maximum size of a response body which will be read into memory,
+    /// or None for no limit. Defaults to None.
+    pub(crate) max_response_body_size: Option<usize>,
+
     /// How long to wait before switching back to the primary host after a
     /// successful request to a fallback endpoint. Defaults to 10m.
     pub(crate) fallback_retry_timeout: Duration,
 
+    /// How long before a token expires to renew it, rather than continuing to
+    /// use it. Defaults to 15s.
+    pub(crate) token_renewal_margin: Duration,
+
+    /// Check the timestamp and ttl of TokenRequests obtained from an auth
+    /// callback or authUrl before exchanging them. Defaults to true.
+    pub(crate) validate_token_requests: bool,
+
     /// Include a random request_id in the query string of all API requests.
     /// Defaults to false.
     pub(crate) add_request_ids: bool,
+
+    /// Additional HTTP headers to include in every REST request, unless the
+    /// request sets a header with the same name.
+    pub(crate) headers: http::Headers,
+
+    /// A pre-configured reqwest::Client to send HTTP requests with, instead
+    /// of one built from these options.
+    pub(crate) http_client: Option<reqwest::Client>,
+
+    /// The HTTP proxy to send requests through, if any.
+    pub(crate) proxy: Option<ProxyOptions>,
+
+    /// Middleware applied to every HTTP request and response.
+    pub(crate) middleware: Vec<Arc<dyn http::Middleware>>,
+
+    /// Listeners notified of the lifecycle of the client's tokens.
+    pub(crate) auth_listeners: Vec<Arc<dyn auth::AuthListener>>,
+
+    /// Signs every HTTP request with a custom scheme, if set.
+    pub(crate) request_signer: Option<Arc<dyn http::RequestSigner>>,
+
+    /// The handler passed the client's log messages, if set.
+    pub(crate) log_handler: Option<Arc<dyn log::LogHandler>>,
+
+    /// The most verbose level of messages passed to the log_handler. Defaults
+    /// to LogLevel::Error.
+    pub(crate) log_level: log::LogLevel,
+
+    /// Fixed IP addresses to use for hosts instead of resolving them.
+    pub(crate) dns_overrides: HashMap<String, Vec<IpAddr>>,
+
+    /// Resolves the IP addresses of hosts instead of the system resolver, if
+    /// set.
+    pub(crate) dns_resolver: Option<Arc<dyn http::Resolver>>,
+
+    /// Additional root certificates to trust when verifying TLS connections.
+    #[cfg(feature = "__tls")]
+    pub(crate) root_certificates: Vec<http::Certificate>,
+}
+
+/// Options for sending HTTP requests through a proxy.
+///
+/// # Example
+///
+/// ```
+/// use ably::options::ProxyOptions;
+///
+/// let proxy = ProxyOptions::new("http://proxy.example.com:3128")
+///     .basic_auth("user", "password")
+///     .no_proxy(vec!["localhost".to_string()]);
+///
+/// let client = ably::ClientOptions::new("<api_key>").proxy(proxy).rest()?;
+/// # Ok::<(), ably::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct ProxyOptions {
+    url: String,
+    credentials: Option<(String, String)>,
+    no_proxy: Vec<String>,
+}
+
+impl ProxyOptions {
+    /// Returns ProxyOptions for sending all requests through the proxy at the
+    /// given URL.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            credentials: None,
+            no_proxy: Vec::new(),
+        }
+    }
+
+    /// Sets the username and password used to authenticate with the proxy.
+    pub fn basic_auth(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.credentials = Some((username.into(), password.into()));
+        self
+    }
+
+    /// Sets the hosts, domains, IP addresses or CIDR blocks which should be
+    /// connected to directly rather than through the proxy.
+    pub fn no_proxy(mut self, hosts: Vec<String>) -> Self {
+        self.no_proxy = hosts;
+        self
+    }
+
+    fn build(&self) -> Result<reqwest::Proxy> {
+        let mut proxy = reqwest::Proxy::all(&self.url).map_err(|err| {
+            Error::with_cause(
+                ErrorCode::BadRequest,
+                err,
+                format!("invalid proxy URL '{}'", self.url),
+            )
+        })?;
+
+        if let Some((username, password)) = &self.credentials {
+            proxy = proxy.basic_auth(username, password);
+        }
+
+        if !self.no_proxy.is_empty() {
+            proxy = proxy.no_proxy(reqwest::NoProxy::from_string(&self.no_proxy.join(",")));
+        }
+
+        Ok(proxy)
+    }
 }
 
 impl ClientOptions {
@@ -134,10 +301,137 @@ impl ClientOptions {
         }
     }
 
+    /// Returns ClientOptions configured from environment variables, for
+    /// twelve-factor deployments:
+    ///
+    /// - `ABLY_KEY`: the API key to authenticate with
+    /// - `ABLY_TOKEN`: the token to authenticate with if `ABLY_KEY` isn't set
+    /// - `ABLY_CLIENT_ID`: the client ID
+    /// - `ABLY_ENDPOINT`: the endpoint, see ClientOptions::endpoint
+    /// - `ABLY_ENVIRONMENT`: the environment
+    /// - `ABLY_REST_HOST`: the REST API host
+    /// - `ABLY_TLS`: whether to use TLS, `true` or `false`
+    /// - `ABLY_PORT` and `ABLY_TLS_PORT`: the ports for non-TLS and TLS
+    ///   requests
+    /// - `ABLY_USE_BINARY_PROTOCOL`: whether to use MessagePack, `true` or
+    ///   `false`
+    ///
+    /// Empty variables are treated as unset, and options which aren't set
+    /// keep their defaults. Options can be overridden by calling the setters
+    /// on the returned ClientOptions.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> ably::Result<()> {
+    /// let client = ably::ClientOptions::from_env()?.rest()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails with an InvalidCredentials error if neither `ABLY_KEY` nor
+    /// `ABLY_TOKEN` is set or `ABLY_KEY` isn't a valid key, and with an
+    /// InvalidParameterValue error naming the variable if any other value is
+    /// invalid. Setting more than one of `ABLY_ENDPOINT`, `ABLY_ENVIRONMENT`
+    /// and `ABLY_REST_HOST` fails as when setting the options directly.
+    pub fn from_env() -> Result<Self> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// Returns ClientOptions configured from the variables returned by the
+    /// given lookup function. See ClientOptions::from_env.
+    pub(crate) fn from_vars(lookup: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let var = |name: &str| lookup(name).filter(|value| !value.is_empty());
+        let parse_port = |name: &str| -> Result<Option<u16>> {
+            var(name)
+                .map(|value| {
+                    value.parse().map_err(|_| {
+                        Error::new(
+                            ErrorCode::InvalidParameterValue,
+                            format!("{} must be a port number, got '{}'", name, value),
+                        )
+                    })
+                })
+                .transpose()
+        };
+        let parse_bool = |name: &str| -> Result<Option<bool>> {
+            var(name)
+                .map(|value| match value.as_str() {
+                    "true" => Ok(true),
+                    "false" => Ok(false),
+                    _ => Err(Error::new(
+                        ErrorCode::InvalidParameterValue,
+                        format!("{} must be 'true' or 'false', got '{}'", name, value),
+                    )),
+                })
+                .transpose()
+        };
+
+        let mut opts = match (var("ABLY_KEY"), var("ABLY_TOKEN")) {
+            (Some(key), _) => Self::with_key(auth::Key::new(&key).map_err(|_| {
+                Error::new(
+                    ErrorCode::InvalidCredentials,
+                    "ABLY_KEY must be an API key of the form '<keyName>:<keySecret>'",
+                )
+            })?),
+            (None, Some(token)) => Self::with_token(token),
+            (None, None) => {
+                return Err(Error::new(
+                    ErrorCode::InvalidCredentials,
+                    "ABLY_KEY or ABLY_TOKEN must be set",
+                ))
+            }
+        };
+
+        if let Some(client_id) = var("ABLY_CLIENT_ID") {
+            opts = opts.client_id(client_id)?;
+        }
+        if let Some(endpoint) = var("ABLY_ENDPOINT") {
+            opts = opts.endpoint(endpoint)?;
+        }
+        if let Some(environment) = var("ABLY_ENVIRONMENT") {
+            opts = opts.environment(environment)?;
+        }
+        if let Some(rest_host) = var("ABLY_REST_HOST") {
+            opts = opts.rest_host(rest_host)?;
+        }
+        if let Some(tls) = parse_bool("ABLY_TLS")? {
+            opts = opts.tls(tls);
+        }
+        if let Some(port) = parse_port("ABLY_PORT")? {
+            opts = opts.port(port);
+        }
+        if let Some(port) = parse_port("ABLY_TLS_PORT")? {
+            opts = opts.tls_port(port);
+        }
+        if let Some(binary) = parse_bool("ABLY_USE_BINARY_PROTOCOL")? {
+            opts = opts.use_binary_protocol(binary);
+        }
+
+        Ok(opts)
+    }
+
     pub fn with_auth_url(url: reqwest::Url) -> Self {
         Self::token_source(Credential::Url(url))
     }
 
+    /// Returns ClientOptions which request tokens from the given AuthUrl,
+    /// using its method, headers and params.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the URL isn't an absolute http or https URL, or if any of
+    /// the headers are invalid.
+    pub fn try_with_auth_url(auth_url: auth::AuthUrl) -> Result<Self> {
+        let (url, options) = auth_url.build()?;
+        Ok(Self::with_auth_url(url)
+            .auth_method(options.method)
+            .auth_headers(options.headers)
+            .auth_params(options.params))
+    }
+
     pub fn with_auth_callback(callback: Arc<dyn AuthCallback>) -> Self {
         Self::token_source(Credential::Callback(callback))
     }
@@ -160,6 +454,14 @@ impl ClientOptions {
         Self::token_source(Credential::TokenDetails(auth::TokenDetails::token(token)))
     }
 
+    /// Returns ClientOptions which authenticate with the given token, for
+    /// example one persisted from a previous run of the application.
+    ///
+    /// Without a means to renew it, requests fail once the token expires.
+    pub fn with_token_details(details: auth::TokenDetails) -> Self {
+        Self::token_source(Credential::TokenDetails(details))
+    }
+
     /// Set the client ID, used for identifying this client when publishing
     /// messages or for presence purposes. Can be any utf-8 string except the
     /// reserved wildcard string '*'.
@@ -178,6 +480,48 @@ impl ClientOptions {
         Ok(self)
     }
 
+    /// Sets whether to use TLS for all connections. Defaults to true.
+    ///
+    /// Disabling TLS is only intended for local development. A client which
+    /// would send its API key using basic auth fails to initialise with an
+    /// InvalidUseOfBasicAuthOverNonTLSTransport error when TLS is disabled,
+    /// so it must use token auth instead.
+    pub fn tls(mut self, tls: bool) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Sets the TCP port used for requests when TLS is disabled, for example
+    /// to connect to a local emulator. Defaults to 80.
+    ///
+    /// The port is ignored if the rest_host includes a port.
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Sets the TCP port used for requests when TLS is enabled, for example
+    /// to connect to a dedicated cluster behind a load balancer on a
+    /// non-standard port. Defaults to 443.
+    ///
+    /// The port is ignored if the rest_host includes a port.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> ably::Result<()> {
+    /// let client = ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc")
+    ///     .rest_host("ably.example.com")?
+    ///     .tls_port(8443)
+    ///     .rest()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn tls_port(mut self, port: u16) -> Self {
+        self.tls_port = port;
+        self
+    }
+
     /// Indicates whether token authentication should be used even if an API
     /// key is present.
     pub fn use_token_auth(mut self, v: bool) -> Self {
@@ -185,6 +529,29 @@ impl ClientOptions {
         self
     }
 
+    /// Sets the HTTP method used to request tokens from the auth_url, which
+    /// is either GET or POST. Defaults to GET.
+    pub fn auth_method(mut self, method: http::Method) -> Self {
+        self.auth_method = method;
+        self
+    }
+
+    /// Sets the HTTP headers included when requesting tokens from the
+    /// auth_url.
+    pub fn auth_headers(mut self, headers: impl Into<http::Headers>) -> Self {
+        self.auth_headers = headers.into();
+        self
+    }
+
+    /// Sets the params included when requesting tokens from the auth_url,
+    /// either in the query string or the form-encoded body depending on the
+    /// auth_method. TokenParams take precedence over params with the same
+    /// name (RSA8c2).
+    pub fn auth_params(mut self, params: http::Params) -> Self {
+        self.auth_params = params;
+        self
+    }
+
     /// Sets the environment. See [TO3k1].
     ///
     /// # Example
@@ -206,7 +573,7 @@ impl ClientOptions {
     /// [T03k1]: https://docs.ably.io/client-lib-development-guide/features/#TO3k1
     pub fn environment(mut self, environment: impl Into<String>) -> Result<Self> {
         // Only allow the environment to be set if rest_host is the default.
-        if self.rest_host != REST_HOST {
+        if self.rest_host != REST_HOST || self.endpoint.is_some() {
             return Err(Error::new(
                 ErrorCode::BadRequest,
                 "Cannot set both environment and rest_host",
@@ -217,15 +584,6 @@ impl ClientOptions {
 
         self.rest_host = format!("{}-rest.ably.io", environment);
 
-        // Generate the fallback hosts.
-        self.fallback_hosts = vec![
-            format!("{}-a-fallback.ably-realtime.com", environment),
-            format!("{}-b-fallback.ably-realtime.com", environment),
-            format!("{}-c-fallback.ably-realtime.com", environment),
-            format!("{}-d-fallback.ably-realtime.com", environment),
-            format!("{}-e-fallback.ably-realtime.com", environment),
-        ];
-
         // Track that the environment was set.
         self.environment = Some(environment);
 
@@ -233,7 +591,11 @@ impl ClientOptions {
     }
 
     /// Sets the message format to MessagePack if the argument is true, or JSON
-    /// if the argument is false.
+    /// if the argument is false. Defaults to true (TO3f).
+    ///
+    /// The format is used for request bodies, and requested for responses
+    /// in the Accept header of every request to the REST API, including
+    /// requests for subsequent pages.
     pub fn use_binary_protocol(mut self, v: bool) -> Self {
         self.format = if v {
             rest::Format::MessagePack
@@ -243,12 +605,39 @@ impl ClientOptions {
         self
     }
 
+    /// Sets the Ably protocol version to use. Features only available in newer
+    /// protocol versions, such as message actions, are rejected client side
+    /// when an older version is configured.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> ably::Result<()> {
+    /// let client = ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc")
+    ///     .protocol(ably::Protocol::V2)
+    ///     .rest()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
     /// Set the default TokenParams.
     pub fn default_token_params(mut self, params: auth::TokenParams) -> Self {
         self.default_token_params = Some(params);
         self
     }
 
+    /// Set the options every channel obtained from the client is created
+    /// with, for example to encrypt messages on all channels with the same
+    /// cipher. A cipher set with ChannelBuilder::cipher takes precedence.
+    pub fn default_channel_options(mut self, opts: rest::ChannelOptions) -> Self {
+        self.default_channel_options = Some(opts);
+        self
+    }
+
     /// Sets the rest_host. See [TO3k2].
     ///
     /// # Example
@@ -276,9 +665,12 @@ impl ClientOptions {
                 "Cannot set both environment and rest_host",
             ));
         }
-
-        // TODO: only unset these if they're the defaults
-        self.fallback_hosts = Vec::new();
+        if self.endpoint.is_some() {
+            return Err(Error::new(
+                ErrorCode::BadRequest,
+                "Cannot set both endpoint and rest_host",
+            ));
+        }
 
         // Track that the rest_host was set.
         self.rest_host = rest_host.into();
@@ -286,9 +678,194 @@ impl ClientOptions {
         Ok(self)
     }
 
-    /// Sets the fallback hosts.
+    /// Sets the endpoint the client sends requests to, which is one of:
+    ///
+    /// - a URL such as `http://localhost:8080`, which sets the host, whether
+    ///   TLS is used, and the port if it isn't the default for the scheme
+    /// - a hostname or IP address with an optional port such as
+    ///   `localhost:8080`, `ably.example.com` or `[::1]:8080`, which is
+    ///   equivalent to setting rest_host, and the port and tls_port if a port
+    ///   is given
+    /// - an environment name such as `sandbox`, which is equivalent to
+    ///   setting environment
+    ///
+    /// This allows pointing the client at an Ably-compatible mock in CI, or
+    /// at a forward proxy listening on a non-standard port. Requests are not
+    /// retried against fallback hosts for a custom host unless fallback_hosts
+    /// is also set.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> ably::Result<()> {
+    /// let client = ably::ClientOptions::new("appID.tokenID")
+    ///     .endpoint("http://localhost:8080")?
+    ///     .rest()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails if environment or rest_host is already set, if the endpoint is
+    /// a URL which is invalid or whose scheme isn't http or https, or if it
+    /// is a host with an invalid port. An IPv6 address must be enclosed in
+    /// brackets, since a bare address such as `::1` can't be told apart from
+    /// a host and port.
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Result<Self> {
+        if self.environment.is_some() || self.rest_host != REST_HOST {
+            return Err(Error::new(
+                ErrorCode::BadRequest,
+                "Cannot set endpoint with environment or rest_host",
+            ));
+        }
+
+        let endpoint = endpoint.into();
+        if endpoint.contains("://") {
+            let url = reqwest::Url::parse(&endpoint).map_err(|err| {
+                Error::with_cause(
+                    ErrorCode::BadRequest,
+                    err,
+                    format!("invalid endpoint '{}'", endpoint),
+                )
+            })?;
+            self.tls = match url.scheme() {
+                "https" => true,
+                "http" => false,
+                scheme => {
+                    return Err(Error::new(
+                        ErrorCode::BadRequest,
+                        format!(
+                            "invalid endpoint scheme '{}', must be http or https",
+                            scheme
+                        ),
+                    ))
+                }
+            };
+            if let Some(port) = url.port() {
+                if self.tls {
+                    self.tls_port = port;
+                } else {
+                    self.port = port;
+                }
+            }
+            self.rest_host = url
+                .host_str()
+                .ok_or_else(|| {
+                    Error::new(
+                        ErrorCode::BadRequest,
+                        format!("invalid endpoint '{}', missing host", endpoint),
+                    )
+                })?
+                .to_string();
+        } else if endpoint.contains(':') || endpoint.contains('[') {
+            let (host, port) = split_host_port(&endpoint)?;
+            self.rest_host = host;
+            if let Some(port) = port {
+                self.port = port;
+                self.tls_port = port;
+            }
+        } else if endpoint.contains('.') || endpoint == "localhost" {
+            self.rest_host = endpoint.clone();
+        } else {
+            self = self.environment(endpoint.clone())?;
+        }
+
+        self.endpoint = Some(endpoint);
+        Ok(self)
+    }
+
+    /// Sets the fallback hosts, for example those of a dedicated cluster,
+    /// replacing the defaults derived from the environment (TO3k6).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> ably::Result<()> {
+    /// let client = ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc")
+    ///     .rest_host("rest.cluster.example.com")?
+    ///     .fallback_hosts(vec![
+    ///         "fallback-1.cluster.example.com".to_string(),
+    ///         "fallback-2.cluster.example.com".to_string(),
+    ///     ])
+    ///     .rest()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// The client fails to initialise if fallback_hosts_use_default is also
+    /// set.
     pub fn fallback_hosts(mut self, hosts: Vec<String>) -> Self {
-        self.fallback_hosts = hosts;
+        self.fallback_hosts = Some(hosts);
+        self
+    }
+
+    /// Use the default fallback hosts even when the environment or rest_host
+    /// is set, for applications relying on the legacy behaviour (TO3k7).
+    ///
+    /// The client fails to initialise if fallback_hosts is also set.
+    pub fn fallback_hosts_use_default(mut self, v: bool) -> Self {
+        self.fallback_hosts_use_default = v;
+        self
+    }
+
+    /// Returns the fallback hosts to retry requests against: the
+    /// fallback_hosts if set, otherwise the defaults for the environment,
+    /// or none if a custom rest_host is set (RSC15b).
+    pub(crate) fn resolved_fallback_hosts(&self) -> Vec<String> {
+        if let Some(hosts) = &self.fallback_hosts {
+            return hosts.clone();
+        }
+
+        match &self.environment {
+            Some(environment) if !self.fallback_hosts_use_default => ["a", "b", "c", "d", "e"]
+                .iter()
+                .map(|id| format!("{}-{}-fallback.ably-realtime.com", environment, id))
+                .collect(),
+            None if !self.fallback_hosts_use_default && self.rest_host != REST_HOST => Vec::new(),
+            _ => DEFAULT_FALLBACK_HOSTS
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+        }
+    }
+
+    /// Give published messages without an ID a unique client generated ID,
+    /// so that Ably doesn't publish a message twice when the request is
+    /// retried against a fallback host after it succeeded (RSL1k). Defaults
+    /// to false.
+    pub fn idempotent_rest_publishing(mut self, v: bool) -> Self {
+        self.idempotent_rest_publishing = v;
+        self
+    }
+
+    /// Include a random request_id in the query string of all API requests,
+    /// which is reused across fallback retries and included in any resulting
+    /// error so it can be quoted to Ably support (RSC7c).
+    pub fn add_request_ids(mut self, v: bool) -> Self {
+        self.add_request_ids = v;
+        self
+    }
+
+    /// Sets additional HTTP headers to include in every REST request, for
+    /// example a tenant or tracing header required by a gateway in front of
+    /// Ably.
+    ///
+    /// Headers set on an individual request, and those set by the client
+    /// such as Authorization, take precedence over these.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ably::http::Headers;
+    ///
+    /// let client = ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc")
+    ///     .headers(Headers::from_pairs([("X-Tenant-Id", "tenant-1")])?)
+    ///     .rest()?;
+    /// # Ok::<(), ably::Error>(())
+    /// ```
+    pub fn headers(mut self, headers: impl Into<http::Headers>) -> Self {
+        self.headers = headers.into();
         self
     }
 
@@ -298,70 +875,674 @@ impl ClientOptions {
         self
     }
 
+    /// Sets the maximum number of idle connections kept open to each host
+    /// for reuse by subsequent requests. Defaults to no limit.
+    ///
+    /// Ignored if a pre-configured http_client is set.
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// Sets how long idle connections are kept open for reuse. Defaults to
+    /// 90s.
+    ///
+    /// Ignored if a pre-configured http_client is set.
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Sends TCP keepalive probes at the given interval on open connections,
+    /// so that idle connections aren't dropped by intermediaries such as
+    /// NAT gateways. Defaults to not sending probes.
+    ///
+    /// Ignored if a pre-configured http_client is set.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// let client = ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc")
+    ///     .pool_max_idle_per_host(32)
+    ///     .pool_idle_timeout(Duration::from_secs(60))
+    ///     .tcp_keepalive(Duration::from_secs(30))
+    ///     .rest()?;
+    /// # Ok::<(), ably::Error>(())
+    /// ```
+    pub fn tcp_keepalive(mut self, interval: Duration) -> Self {
+        self.tcp_keepalive = Some(interval);
+        self
+    }
+
     /// Sets the maximum number of HTTP retries.
     pub fn http_max_retry_count(mut self, count: usize) -> Self {
         self.http_max_retry_count = count;
         self
     }
 
-    fn rest_url(&self) -> Result<reqwest::Url> {
+    /// Sets the number of times to retry a request which is rate limited
+    /// with a 429 response, waiting for the duration given in the
+    /// Retry-After header before each retry.
+    ///
+    /// Whether or not requests are retried, the Retry-After duration is
+    /// available in Error.retry_after.
+    pub fn rate_limit_retry_count(mut self, count: usize) -> Self {
+        self.rate_limit_retry_count = count;
+        self
+    }
+
+    /// Sets a HTTP proxy to send all requests through.
+    ///
+    /// The proxy is not used if a pre-configured reqwest::Client is set with
+    /// http_client, in which case the proxy should be configured on that
+    /// client instead.
+    pub fn proxy(mut self, proxy: ProxyOptions) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Adds a root certificate to trust when verifying TLS connections, in
+    /// addition to the TLS backend's built-in roots, for example when
+    /// connecting through a TLS-intercepting proxy.
+    ///
+    /// Root certificates are not used if a pre-configured reqwest::Client is
+    /// set with http_client.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn run(pem: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    /// let cert = ably::http::Certificate::from_pem(pem)?;
+    ///
+    /// let client = ably::ClientOptions::new("<api_key>")
+    ///     .add_root_certificate(cert)
+    ///     .rest()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "__tls")]
+    pub fn add_root_certificate(mut self, cert: http::Certificate) -> Self {
+        self.root_certificates.push(cert);
+        self
+    }
+
+    /// Registers middleware to apply to every HTTP request and response.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ably::http::Middleware;
+    ///
+    /// #[derive(Debug)]
+    /// struct TenantHeader(&'static str);
+    ///
+    /// impl Middleware for TenantHeader {
+    ///     fn on_request(&self, req: &mut reqwest::Request) -> ably::Result<()> {
+    ///         req.headers_mut()
+    ///             .insert("X-Tenant", ably::http::HeaderValue::from_static(self.0));
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let client = ably::ClientOptions::new("<api_key>")
+    ///     .middleware(TenantHeader("tenant-1"))
+    ///     .rest()?;
+    /// # Ok::<(), ably::Error>(())
+    /// ```
+    pub fn middleware(mut self, middleware: impl http::Middleware + 'static) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Registers an AuthListener to be notified when the client requests,
+    /// receives, or fails to obtain tokens, and when its token expires.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ably::auth::{AuthEvent, AuthListener};
+    ///
+    /// #[derive(Debug)]
+    /// struct LogTokens;
+    ///
+    /// impl AuthListener for LogTokens {
+    ///     fn on_auth_event(&self, event: &AuthEvent<'_>) {
+    ///         if let AuthEvent::TokenReceived(token) = event {
+    ///             println!("received token {:?}", token.metadata);
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let client = ably::ClientOptions::with_auth_url("https://example.com/auth".parse().unwrap())
+    ///     .auth_listener(LogTokens)
+    ///     .rest()?;
+    /// # Ok::<(), ably::Error>(())
+    /// ```
+    pub fn auth_listener(mut self, listener: impl auth::AuthListener + 'static) -> Self {
+        self.auth_listeners.push(Arc::new(listener));
+        self
+    }
+
+    /// Sets a RequestSigner to sign every HTTP request with a custom scheme,
+    /// for example so that requests can traverse an authenticated egress
+    /// gateway.
+    pub fn request_signer(mut self, signer: impl http::RequestSigner + 'static) -> Self {
+        self.request_signer = Some(Arc::new(signer));
+        self
+    }
+
+    /// Sets a LogHandler to pass the client's log messages to, such as those
+    /// describing fallback retries, token renewals and pagination. See the
+    /// [log](crate::log) module.
+    pub fn log_handler(mut self, handler: impl log::LogHandler + 'static) -> Self {
+        self.log_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Sets the most verbose level of messages passed to the LogHandler.
+    /// Defaults to LogLevel::Error.
+    pub fn log_level(mut self, level: log::LogLevel) -> Self {
+        self.log_level = level;
+        self
+    }
+
+    /// Sends requests for the given host to the given IP address instead of
+    /// resolving it, for example to test against a local Ably-compatible mock
+    /// server.
+    ///
+    /// Calling resolve more than once for a host adds further addresses. The
+    /// port requests are sent to is taken from the URL, so only the IP
+    /// address is overridden.
+    ///
+    /// This option is ignored if a reqwest::Client is provided with
+    /// http_client.
+    pub fn resolve(mut self, host: impl Into<String>, ip: IpAddr) -> Self {
+        self.dns_overrides.entry(host.into()).or_default().push(ip);
+        self
+    }
+
+    /// Sets a Resolver to resolve the IP addresses of hosts instead of the
+    /// system resolver, for example in a split-horizon DNS environment.
+    ///
+    /// Addresses set with resolve take precedence over the Resolver. This
+    /// option is ignored if a reqwest::Client is provided with http_client.
+    pub fn dns_resolver(mut self, resolver: impl http::Resolver + 'static) -> Self {
+        self.dns_resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    /// Limits the size of response bodies which will be read into memory,
+    /// for example to protect constrained devices from large history pages.
+    ///
+    /// Requests whose responses have a larger body fail with an
+    /// InvalidContentLength error.
+    pub fn max_response_body_size(mut self, bytes: usize) -> Self {
+        self.max_response_body_size = Some(bytes);
+        self
+    }
+
+    /// Sets a pre-configured reqwest::Client to send HTTP requests with, for
+    /// example to configure proxies, connection pooling or TLS once and share
+    /// the client between multiple Rest clients.
+    ///
+    /// The client's own timeouts are used instead of http_request_timeout
+    /// and http_open_timeout.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let http_client = reqwest::Client::builder()
+    ///     .pool_max_idle_per_host(10)
+    ///     .build()?;
+    ///
+    /// let client = ably::ClientOptions::new("<api_key>")
+    ///     .http_client(http_client)
+    ///     .rest()?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn http_client(mut self, client: reqwest::Client) -> Self {
+        self.http_client = Some(client);
+        self
+    }
+
+    /// Sets how long before a token expires to renew it.
+    ///
+    /// Tokens are reused for every request until they are within this
+    /// margin of expiring, at which point a new token is requested before
+    /// sending the next request. Defaults to 15s.
+    pub fn token_renewal_margin(mut self, margin: Duration) -> Self {
+        self.token_renewal_margin = margin;
+        self
+    }
+
+    /// Sets whether to check the timestamp and ttl of TokenRequests obtained
+    /// from an auth callback or authUrl before exchanging them for a token.
+    ///
+    /// A TokenRequest which is stale, or has a negative ttl, then fails with
+    /// a descriptive error without a round trip to Ably. The check compares
+    /// against the local clock unless the query_time option is set, in which
+    /// case it uses the server time, so also enable query_time on clients
+    /// which can't rely on their clock. Defaults to true.
+    pub fn validate_token_requests(mut self, validate: bool) -> Self {
+        self.validate_token_requests = validate;
+        self
+    }
+
+    /// Sets whether to query the Ably server for the current time when
+    /// signing token requests with an API key (TO3p).
+    ///
+    /// The server time is queried once, and the offset from the local clock
+    /// is then used for the timestamp of every TokenRequest the client signs,
+    /// so that clients with a skewed clock don't have their token requests
+    /// rejected. Defaults to false.
+    pub fn query_time(mut self, query_time: bool) -> Self {
+        self.query_time = query_time;
+        self
+    }
+
+    /// Limits the number of HTTP requests the client has in flight at once,
+    /// for example so that bulk publishing doesn't exhaust sockets or trip
+    /// Ably rate limits.
+    ///
+    /// Requests over the limit wait for an earlier request to complete, and
+    /// fail with a TimeoutError if they wait longer than the HTTP request
+    /// timeout. A request remains in flight until its response body has been
+    /// read. The limit must not be 0.
+    pub fn max_concurrent_requests(mut self, max: usize) -> Self {
+        self.max_concurrent_requests = Some(max);
+        self
+    }
+
+    /// Sets the maximum size of a single message, which should match the
+    /// limit of the Ably account. Defaults to 64KiB.
+    ///
+    /// Publishing a message which exceeds the limit after it is encoded and
+    /// encrypted fails with a MaximumMessageLengthExceeded error without
+    /// sending a request.
+    pub fn max_message_size(mut self, bytes: u64) -> Self {
+        self.max_message_size = bytes;
+        self
+    }
+
+    /// Sets the maximum size of a single request body, which should match
+    /// the limit of the Ably account. Defaults to 512KiB.
+    pub fn max_frame_size(mut self, bytes: u64) -> Self {
+        self.max_frame_size = bytes;
+        self
+    }
+
+    pub(crate) fn rest_url(&self) -> Result<reqwest::Url> {
         let rest_url = if self.tls {
             format!("https://{}", self.rest_host)
         } else {
             format!("http://{}", self.rest_host)
         };
-        let rest_url = reqwest::Url::parse(&rest_url)?;
+        let mut rest_url = reqwest::Url::parse(&rest_url)?;
+
+        // Use the configured port unless the rest_host includes one. Setting
+        // the default port for the scheme leaves the URL without a port.
+        if rest_url.port().is_none() {
+            let port = if self.tls { self.tls_port } else { self.port };
+            rest_url
+                .set_port(Some(port))
+                .map_err(|_| Error::new(ErrorCode::BadRequest, "invalid REST API URL"))?;
+        }
+
         Ok(rest_url)
     }
 
+    /// Returns a snapshot of the effective configuration as JSON, for
+    /// including in startup logs or support requests.
+    ///
+    /// The snapshot never contains secrets: only the name of an API key is
+    /// included, tokens and auth header and param values are omitted, and
+    /// query strings and credentials are stripped from the authUrl and proxy
+    /// URL.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let opts = ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc").environment("sandbox")?;
+    ///
+    /// let snapshot = opts.to_redacted_json();
+    /// assert_eq!(snapshot["auth"]["keyName"], "aaaaaa.bbbbbb");
+    /// assert_eq!(snapshot["restHost"], "sandbox-rest.ably.io");
+    /// assert!(!snapshot.to_string().contains("cccccc"));
+    /// # Ok::<(), ably::Error>(())
+    /// ```
+    pub fn to_redacted_json(&self) -> serde_json::Value {
+        let mut auth = match &self.credential {
+            Credential::Key(key) => serde_json::json!({"type": "key", "keyName": key.name}),
+            Credential::TokenDetails(_) => serde_json::json!({"type": "token"}),
+            Credential::TokenRequest(req) => {
+                serde_json::json!({"type": "tokenRequest", "keyName": req.key_name})
+            }
+            Credential::Callback(_) => serde_json::json!({"type": "authCallback"}),
+            Credential::Url(url) => {
+                serde_json::json!({"type": "authUrl", "authUrl": redact_url(url.as_str())})
+            }
+        };
+        auth["method"] = self.auth_method.as_str().into();
+        auth["headers"] = self
+            .auth_headers
+            .as_map()
+            .keys()
+            .map(|k| k.as_str())
+            .collect();
+        auth["params"] = self.auth_params.iter().map(|(name, _)| name).collect();
+        auth["useTokenAuth"] = self.use_token_auth.into();
+        auth["queryTime"] = self.query_time.into();
+        auth["validateTokenRequests"] = self.validate_token_requests.into();
+        auth["tokenRenewalMarginMs"] = millis(self.token_renewal_margin).into();
+
+        let features: Vec<&str> = [
+            ("native-tls", cfg!(feature = "native-tls")),
+            ("native-tls-alpn", cfg!(feature = "native-tls-alpn")),
+            ("rustls", cfg!(feature = "rustls")),
+            ("rustls-native-roots", cfg!(feature = "rustls-native-roots")),
+            ("gzip", cfg!(feature = "gzip")),
+            ("deflate", cfg!(feature = "deflate")),
+        ]
+        .into_iter()
+        .filter_map(|(name, enabled)| enabled.then_some(name))
+        .collect();
+
+        #[cfg(feature = "__tls")]
+        let root_certificates = self.root_certificates.len();
+        #[cfg(not(feature = "__tls"))]
+        let root_certificates = 0;
+
+        serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "features": features,
+            "auth": auth,
+            "clientId": self.client_id,
+            "environment": self.environment,
+            "endpoint": self.endpoint,
+            "restUrl": self.rest_url().ok().map(|url| url.to_string()),
+            "restHost": self.rest_host,
+            "fallbackHosts": self.resolved_fallback_hosts(),
+            "tls": self.tls,
+            "port": self.port,
+            "tlsPort": self.tls_port,
+            "format": format!("{:?}", self.format),
+            "protocol": format!("{:?}", self.protocol),
+            "idempotentRestPublishing": self.idempotent_rest_publishing,
+            "addRequestIds": self.add_request_ids,
+            "headers": self.headers.as_map().keys().map(|k| k.as_str()).collect::<Vec<_>>(),
+            "httpOpenTimeoutMs": millis(self.http_open_timeout),
+            "httpRequestTimeoutMs": millis(self.http_request_timeout),
+            "poolMaxIdlePerHost": self.pool_max_idle_per_host,
+            "poolIdleTimeoutMs": self.pool_idle_timeout.map(millis),
+            "tcpKeepaliveMs": self.tcp_keepalive.map(millis),
+            "httpMaxRetryCount": self.http_max_retry_count,
+            "httpMaxRetryDurationMs": millis(self.http_max_retry_duration),
+            "fallbackRetryTimeoutMs": millis(self.fallback_retry_timeout),
+            "rateLimitRetryCount": self.rate_limit_retry_count,
+            "maxConcurrentRequests": self.max_concurrent_requests,
+            "maxMessageSize": self.max_message_size,
+            "maxFrameSize": self.max_frame_size,
+            "maxResponseBodySize": self.max_response_body_size,
+            "proxy": self.proxy.as_ref().map(|proxy| serde_json::json!({
+                "url": redact_url(&proxy.url),
+                "basicAuth": proxy.credentials.is_some(),
+                "noProxy": proxy.no_proxy,
+            })),
+            "customHttpClient": self.http_client.is_some(),
+            "middleware": self.middleware.len(),
+            "authListeners": self.auth_listeners.len(),
+            "requestSigner": self.request_signer.is_some(),
+            "logHandler": self.log_handler.is_some(),
+            "logLevel": format!("{:?}", self.log_level),
+            "dnsOverrides": self.dns_overrides.keys().collect::<Vec<_>>(),
+            "dnsResolver": self.dns_resolver.is_some(),
+            "rootCertificates": root_certificates,
+        })
+    }
+
+    /// Check that the options are consistent, failing with the error the
+    /// client would fail to initialise with if not:
+    ///
+    /// - a token or API key must not be empty
+    ///   (UnableToObtainCredentialsFromGivenParameters)
+    /// - the auth_method must be GET or POST when using an authUrl
+    ///   (InvalidParameterValue)
+    /// - an API key can't be sent using basic auth when TLS is disabled
+    ///   (InvalidUseOfBasicAuthOverNonTLSTransport)
+    /// - the clientId must not be empty or the wildcard '*'
+    ///   (InvalidClientID)
+    /// - at most one of environment and rest_host can be set, and neither
+    ///   with endpoint (BadRequest)
+    /// - fallback_hosts_use_default can't be set with fallback_hosts or
+    ///   endpoint (BadRequest)
+    /// - the port, tls_port and max_concurrent_requests must not be 0, and
+    ///   the max_message_size must not exceed the max_frame_size
+    ///   (InvalidParameterValue)
+    ///
+    /// The setters check what they can as each option is set, but this
+    /// catches combinations which are only invalid once all options are set.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ably::error::ErrorCode;
+    ///
+    /// let opts = ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc")
+    ///     .fallback_hosts(vec!["fallback.example.com".to_string()])
+    ///     .fallback_hosts_use_default(true);
+    ///
+    /// let err = opts.validate().unwrap_err();
+    /// assert_eq!(err.code, ErrorCode::BadRequest);
+    /// ```
+    pub fn validate(&self) -> Result<()> {
+        match &self.credential {
+            Credential::Key(key) if key.name.is_empty() || key.value.is_empty() => {
+                return Err(Error::new(
+                    ErrorCode::UnableToObtainCredentialsFromGivenParameters,
+                    "API key must not be empty",
+                ));
+            }
+            Credential::Key(_) if !self.tls && !self.use_token_auth => {
+                return Err(auth::basic_auth_over_non_tls_error());
+            }
+            Credential::TokenDetails(details) if details.token.is_empty() => {
+                return Err(Error::new(
+                    ErrorCode::UnableToObtainCredentialsFromGivenParameters,
+                    "token must not be empty",
+                ));
+            }
+            Credential::Url(_)
+                if self.auth_method != http::Method::GET
+                    && self.auth_method != http::Method::POST =>
+            {
+                return Err(Error::new(
+                    ErrorCode::InvalidParameterValue,
+                    format!("auth_method must be GET or POST, got {}", self.auth_method),
+                ));
+            }
+            _ => (),
+        }
+
+        match self.client_id.as_deref() {
+            Some("") => {
+                return Err(Error::new(
+                    ErrorCode::InvalidClientID,
+                    "clientId must not be empty",
+                ))
+            }
+            Some("*") => {
+                return Err(Error::new(
+                    ErrorCode::InvalidClientID,
+                    "Can’t use '*' as a clientId as that string is reserved",
+                ))
+            }
+            _ => (),
+        }
+
+        if let Some(environment) = &self.environment {
+            if self.rest_host != format!("{}-rest.ably.io", environment) {
+                return Err(Error::new(
+                    ErrorCode::BadRequest,
+                    "Cannot set both environment and rest_host",
+                ));
+            }
+        }
+
+        if self.fallback_hosts.is_some() && self.fallback_hosts_use_default {
+            return Err(Error::new(
+                ErrorCode::BadRequest,
+                "Cannot set both fallback_hosts and fallback_hosts_use_default",
+            ));
+        }
+
+        if self.endpoint.is_some() && self.fallback_hosts_use_default {
+            return Err(Error::new(
+                ErrorCode::BadRequest,
+                "Cannot set both endpoint and fallback_hosts_use_default",
+            ));
+        }
+
+        if self.port == 0 || self.tls_port == 0 {
+            return Err(Error::new(
+                ErrorCode::InvalidParameterValue,
+                "port and tls_port must not be 0",
+            ));
+        }
+
+        if self.max_concurrent_requests == Some(0) {
+            return Err(Error::new(
+                ErrorCode::InvalidParameterValue,
+                "max_concurrent_requests must not be 0",
+            ));
+        }
+
+        if self.max_message_size > self.max_frame_size {
+            return Err(Error::new(
+                ErrorCode::InvalidParameterValue,
+                format!(
+                    "max_message_size {} must not exceed max_frame_size {}",
+                    self.max_message_size, self.max_frame_size
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
     /// Returns a Rest client using the ClientOptions.
     ///
     /// # Errors
     ///
     /// This method fails if the ClientOptions are not valid:
     ///
+    /// - the options must pass ClientOptions::validate
     /// - the REST API URL must be valid
     ///
     /// [RSC1b]: https://docs.ably.io/client-lib-development-guide/features/#RSC1b
     pub fn rest(self) -> Result<rest::Rest> {
         let rest_url = self.rest_url()?;
-        let mut default_headers = http::HeaderMap::new();
-        default_headers.insert("X-Ably-Version", http::HeaderValue::from_static("1.2"));
+        let http_client = self.build_http_client()?;
+        let executor = Arc::new(http_client.clone());
 
-        if let Some(client_id) = &self.client_id {
-            default_headers.insert("X-Ably-ClientId", base64::encode(client_id).parse()?);
+        rest::Rest::create(http_client, executor, self, rest_url)
+    }
+
+    /// Returns the reqwest::Client used to build and send HTTP requests.
+    pub(crate) fn build_http_client(&self) -> Result<reqwest::Client> {
+        if let Some(client) = &self.http_client {
+            return Ok(client.clone());
         }
 
-        let http_client = reqwest::Client::builder()
-            .default_headers(default_headers)
+        let mut builder = reqwest::Client::builder()
             .timeout(self.http_request_timeout)
             .connect_timeout(self.http_open_timeout)
-            .build()?;
+            .tcp_keepalive(self.tcp_keepalive);
+
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+
+        if let Some(timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy.build()?);
+        }
+
+        #[cfg(feature = "__tls")]
+        for cert in &self.root_certificates {
+            builder = builder.add_root_certificate(cert.clone());
+        }
+
+        if let Some(resolver) = &self.dns_resolver {
+            builder = builder.dns_resolver(Arc::new(http::ReqwestResolver(resolver.clone())));
+        }
 
-        Ok(rest::Rest::create(http_client, self, rest_url))
+        for (host, ips) in &self.dns_overrides {
+            let addrs: Vec<SocketAddr> = ips.iter().map(|ip| SocketAddr::new(*ip, 0)).collect();
+            builder = builder.resolve_to_addrs(host, &addrs);
+        }
+
+        builder.build().map_err(Into::into)
+    }
+
+    /// Returns the headers to include in every REST request.
+    ///
+    /// These are set on each request rather than as default headers of the
+    /// reqwest::Client so that they're also sent when using a pre-configured
+    /// client.
+    pub(crate) fn default_headers(&self) -> Result<http::HeaderMap> {
+        let mut headers = http::HeaderMap::new();
+        headers.insert(
+            crate::protocol::VERSION_HEADER,
+            self.protocol.header_value(),
+        );
+
+        if let Some(client_id) = &self.client_id {
+            headers.insert("X-Ably-ClientId", base64::encode(client_id).parse()?);
+        }
+
+        Ok(headers)
+    }
+
+    /// Pass the message returned by the given function to the LogHandler if
+    /// one is set and the level is enabled.
+    pub(crate) fn log(&self, level: log::LogLevel, message: impl FnOnce() -> String) {
+        if let Some(handler) = &self.log_handler {
+            if level <= self.log_level {
+                handler.log(level, &message());
+            }
+        }
+    }
+
+    /// Returns whether messages at the given level are passed to a
+    /// LogHandler.
+    pub(crate) fn log_enabled(&self, level: log::LogLevel) -> bool {
+        self.log_handler.is_some() && level <= self.log_level
     }
 
     pub fn token_source(token: Credential) -> Self {
         Self {
             credential: token,
             auth_method: http::Method::GET,
-            auth_headers: None,
-            auth_params: None,
+            auth_headers: http::Headers::new(),
+            auth_params: http::Params::new(),
             tls: true,
             client_id: None,
             use_token_auth: false,
             environment: None,
+            endpoint: None,
             idempotent_rest_publishing: false,
-            fallback_hosts: vec![
-                "a.ably-realtime.com".to_string(),
-                "b.ably-realtime.com".to_string(),
-                "c.ably-realtime.com".to_string(),
-                "d.ably-realtime.com".to_string(),
-                "e.ably-realtime.com".to_string(),
-            ],
-            format: rest::Format::MessagePack,
+            fallback_hosts: None,
+            fallback_hosts_use_default: false,
+            format: rest::DEFAULT_FORMAT,
+            protocol: Protocol::default(),
             query_time: false,
             default_token_params: None,
+            default_channel_options: None,
             auto_connect: true,
             rest_host: REST_HOST.to_string(),
             realtime_host: "realtime.ably.io".to_string(),
@@ -372,12 +1553,91 @@ impl ClientOptions {
             channel_retry_timeout: Duration::from_secs(15),
             http_open_timeout: Duration::from_secs(4),
             http_request_timeout: Duration::from_secs(10),
+            pool_max_idle_per_host: None,
+            pool_idle_timeout: None,
+            tcp_keepalive: None,
             http_max_retry_count: 3,
             http_max_retry_duration: Duration::from_secs(15),
+            rate_limit_retry_count: 0,
+            max_concurrent_requests: None,
             max_message_size: 64 * 1024,
             max_frame_size: 512 * 1024,
+            max_response_body_size: None,
             fallback_retry_timeout: Duration::from_secs(10 * 60),
+            token_renewal_margin: Duration::from_secs(15),
+            validate_token_requests: true,
             add_request_ids: false,
+            headers: http::Headers::new(),
+            http_client: None,
+            proxy: None,
+            middleware: Vec::new(),
+            auth_listeners: Vec::new(),
+            request_signer: None,
+            log_handler: None,
+            log_level: log::LogLevel::default(),
+            dns_overrides: HashMap::new(),
+            dns_resolver: None,
+            #[cfg(feature = "__tls")]
+            root_certificates: Vec::new(),
         }
     }
 }
+
+/// Split an endpoint of the form host[:port] into its host and port, where an
+/// IPv6 address must be enclosed in brackets, as in `[::1]:8080`.
+fn split_host_port(endpoint: &str) -> Result<(String, Option<u16>)> {
+    let invalid = |reason: &str| {
+        Error::new(
+            ErrorCode::BadRequest,
+            format!("invalid endpoint '{}', {}", endpoint, reason),
+        )
+    };
+
+    let (host, port) = match endpoint.rfind(']') {
+        Some(i) if endpoint.starts_with('[') => match &endpoint[i + 1..] {
+            "" => (endpoint, None),
+            rest => match rest.strip_prefix(':') {
+                Some(port) => (&endpoint[..=i], Some(port)),
+                None => return Err(invalid("expected a port after the IPv6 address")),
+            },
+        },
+        _ => match endpoint.split_once(':') {
+            Some((_, port)) if port.contains(':') => {
+                return Err(invalid("IPv6 addresses must be enclosed in brackets"))
+            }
+            Some((host, port)) => (host, Some(port)),
+            None => (endpoint, None),
+        },
+    };
+
+    if host.is_empty() {
+        return Err(invalid("missing host"));
+    }
+    url::Host::parse(host).map_err(|_| invalid("invalid host"))?;
+    let port = port
+        .map(|port| match port.parse() {
+            Ok(port) if port != 0 => Ok(port),
+            _ => Err(invalid("invalid port")),
+        })
+        .transpose()?;
+
+    Ok((host.to_string(), port))
+}
+
+/// Returns the URL with any credentials and query string removed.
+fn redact_url(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(mut url) => {
+            let _ = url.set_username("");
+            let _ = url.set_password(None);
+            url.set_query(None);
+            url.set_fragment(None);
+            url.to_string()
+        }
+        Err(_) => "<invalid URL>".to_string(),
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
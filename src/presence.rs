@@ -1,14 +1,66 @@
+use chrono::{DateTime, Utc};
 use futures::stream::Stream;
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
 
-use crate::{http, rest, Result};
+use crate::rest::{ChannelOptions, Data, Decode, Encoding};
+use crate::{codec, http, Result};
+
+/// The maximum number of members returned in each page of a presence
+/// request (RSP3a).
+pub const MAX_LIMIT: u32 = 1000;
+
+/// A member's presence on a channel, either its current state returned by
+/// Presence::get or a change of state returned by Presence::history (TP).
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresenceMessage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub action: PresenceAction,
+    pub client_id: String,
+    pub connection_id: String,
+    #[serde(default, skip_serializing_if = "Data::is_none")]
+    pub data: Data,
+    #[serde(default, skip_serializing_if = "Encoding::is_none")]
+    pub encoding: Encoding,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "chrono::serde::ts_milliseconds_option"
+    )]
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+/// The action of a PresenceMessage, which is serialized as its numeric
+/// value (TP2).
+#[derive(Clone, Debug, Deserialize_repr, PartialEq, Eq, Serialize_repr)]
+#[serde(untagged)]
+#[repr(u8)]
+pub enum PresenceAction {
+    Absent,
+    Present,
+    Enter,
+    Leave,
+    Update,
+}
+
+impl Decode for PresenceMessage {
+    type Options = Option<ChannelOptions>;
+    type Item = Self;
+
+    fn decode(item: &mut Self::Item, options: &Self::Options) -> Result<()> {
+        codec::decode(&mut item.data, &mut item.encoding, options.as_ref())
+    }
+}
 
 /// A type alias for a PaginatedRequestBuilder which uses a MessageItemHandler
 /// to handle pages of presence messages returned from a presence request.
-pub type PaginatedRequestBuilder<'a> = http::PaginatedRequestBuilder<'a, rest::PresenceMessage>;
+pub type PaginatedRequestBuilder<'a> = http::PaginatedRequestBuilder<'a, PresenceMessage>;
 
 /// A type alias for a PaginatedResult which uses a MessageItemHandler to
 /// handle pages of presence messages returned from a presence request.
-pub type PaginatedResult = http::PaginatedResult<rest::PresenceMessage>;
+pub type PaginatedResult = http::PaginatedResult<PresenceMessage>;
 
 /// A builder to construct a REST presence request.
 pub struct RequestBuilder<'a> {
@@ -20,9 +72,11 @@ impl<'a> RequestBuilder<'a> {
         Self { inner }
     }
 
-    /// Limit the number of results per page.
+    /// Limit the number of members per page, which defaults to 100.
+    ///
+    /// The limit is clamped to between 1 and MAX_LIMIT.
     pub fn limit(mut self, limit: u32) -> Self {
-        self.inner = self.inner.limit(limit);
+        self.inner = self.inner.limit(limit.clamp(1, MAX_LIMIT));
         self
     }
 
@@ -45,6 +99,11 @@ impl<'a> RequestBuilder<'a> {
         self.inner.pages()
     }
 
+    /// Request a stream of presence messages, transparently walking pages.
+    pub fn items(self) -> impl Stream<Item = Result<PresenceMessage>> + 'a {
+        self.inner.items()
+    }
+
     /// Retrieve the first page of presence messages.
     pub async fn send(self) -> Result<PaginatedResult> {
         self.inner.send().await
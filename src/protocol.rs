@@ -0,0 +1,118 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::error::{Error, ErrorCode};
+use crate::{http, Result};
+
+/// The name of the HTTP header used to tell Ably which protocol version the
+/// client speaks.
+pub const VERSION_HEADER: &str = "X-Ably-Version";
+
+/// A version of the [Ably protocol].
+///
+/// The protocol version is sent in the `X-Ably-Version` header of every REST
+/// request, and determines both the shape of the responses Ably sends back
+/// and which wire features the client is allowed to use.
+///
+/// [Ably protocol]: https://ably.com/docs/client-lib-development-guide/protocol
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Protocol {
+    /// Protocol version 1.2.
+    #[default]
+    V1_2,
+    /// Protocol version 2, which adds message actions, serials and versions.
+    V2,
+}
+
+impl Protocol {
+    /// Returns the version string sent in the `X-Ably-Version` header.
+    pub fn version(&self) -> &'static str {
+        match self {
+            Self::V1_2 => "1.2",
+            Self::V2 => "2",
+        }
+    }
+
+    /// Returns whether messages can carry an action (create, update, delete)
+    /// along with a serial and version.
+    pub fn supports_message_actions(&self) -> bool {
+        *self >= Self::V2
+    }
+
+    /// Returns an error if a feature requiring at least the given protocol
+    /// version is used with this version.
+    pub fn require(&self, min: Protocol, feature: &str) -> Result<()> {
+        if *self >= min {
+            return Ok(());
+        }
+
+        Err(Error::new(
+            ErrorCode::BadRequest,
+            format!(
+                "{} requires protocol version {} (client is using {})",
+                feature,
+                min.version(),
+                self.version()
+            ),
+        ))
+    }
+
+    pub(crate) fn header_value(&self) -> http::HeaderValue {
+        http::HeaderValue::from_static(self.version())
+    }
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.version())
+    }
+}
+
+impl TryFrom<&str> for Protocol {
+    type Error = Error;
+
+    /// Parse a protocol version from a string like "1.2" or "2".
+    fn try_from(s: &str) -> Result<Self> {
+        match s {
+            "1.2" => Ok(Self::V1_2),
+            "2" | "2.0" => Ok(Self::V2),
+            _ => Err(Error::new(
+                ErrorCode::BadRequest,
+                format!("unsupported protocol version '{}'", s),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protocol_default_is_1_2() {
+        assert_eq!(Protocol::default(), Protocol::V1_2);
+        assert_eq!(Protocol::default().version(), "1.2");
+    }
+
+    #[test]
+    fn protocol_parse() {
+        assert_eq!(Protocol::try_from("1.2").unwrap(), Protocol::V1_2);
+        assert_eq!(Protocol::try_from("2").unwrap(), Protocol::V2);
+        assert_eq!(Protocol::try_from("2.0").unwrap(), Protocol::V2);
+        assert!(Protocol::try_from("1.1").is_err());
+    }
+
+    #[test]
+    fn protocol_message_actions_gated_on_v2() {
+        assert!(!Protocol::V1_2.supports_message_actions());
+        assert!(Protocol::V2.supports_message_actions());
+
+        let err = Protocol::V1_2
+            .require(Protocol::V2, "message actions")
+            .expect_err("Expected message actions to be rejected on 1.2");
+        assert_eq!(err.code, ErrorCode::BadRequest);
+        assert!(Protocol::V2
+            .require(Protocol::V2, "message actions")
+            .is_ok());
+    }
+}
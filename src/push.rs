@@ -0,0 +1,218 @@
+use futures::stream::Stream;
+use serde::{Deserialize, Serialize};
+
+use crate::rest::{Decode, Rest};
+use crate::{http, json, Result};
+
+/// The number of device registrations DeviceRegistrations::all requests in
+/// each page.
+const ALL_PAGE_LIMIT: u32 = 1000;
+
+/// The push notification functionality of the REST API.
+pub struct Push<'a> {
+    rest: &'a Rest,
+}
+
+impl<'a> Push<'a> {
+    pub(crate) fn new(rest: &'a Rest) -> Self {
+        Self { rest }
+    }
+
+    /// Returns the push admin API, which requires the push-admin capability.
+    pub fn admin(&self) -> PushAdmin<'a> {
+        PushAdmin { rest: self.rest }
+    }
+}
+
+/// The push admin API, for managing the devices registered to receive push
+/// notifications.
+pub struct PushAdmin<'a> {
+    rest: &'a Rest,
+}
+
+impl<'a> PushAdmin<'a> {
+    /// Returns the API for managing device registrations.
+    pub fn device_registrations(&self) -> DeviceRegistrations<'a> {
+        DeviceRegistrations { rest: self.rest }
+    }
+}
+
+/// The API for managing the devices registered to receive push
+/// notifications.
+pub struct DeviceRegistrations<'a> {
+    rest: &'a Rest,
+}
+
+impl<'a> DeviceRegistrations<'a> {
+    /// Start building a request to list device registrations.
+    pub fn list(&self) -> RequestBuilder<'a> {
+        RequestBuilder {
+            inner: self.rest.paginated_request_with_options(
+                http::Method::GET,
+                "/push/deviceRegistrations",
+                (),
+            ),
+        }
+    }
+
+    /// Returns a stream of all device registrations, for example to
+    /// periodically reconcile them with a local database of devices.
+    ///
+    /// Pages are requested until all registrations have been returned, and
+    /// page requests which fail during an Ably maintenance window are
+    /// retried rather than ending the stream. Rate limited page requests are
+    /// retried according to ClientOptions::rate_limit_retry_count.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> ably::Result<()> {
+    /// use futures::TryStreamExt;
+    ///
+    /// # let devices = serde_json::json!([{"id": "device-1", "platform": "ios", "formFactor": "phone"}]);
+    /// # let transport = ably::mock::MockTransport::new().on(ably::http::Method::GET, "/push/deviceRegistrations", 200, devices);
+    /// # let client = ably::Rest::with_http_executor(ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc"), transport)?;
+    /// let devices: Vec<_> = client
+    ///     .push()
+    ///     .admin()
+    ///     .device_registrations()
+    ///     .all()
+    ///     .try_collect()
+    ///     .await?;
+    ///
+    /// assert_eq!(devices[0].id, "device-1");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn all(&self) -> impl Stream<Item = Result<DeviceDetails>> + 'a {
+        self.list()
+            .limit(ALL_PAGE_LIMIT)
+            .inner
+            .resume_on_maintenance()
+            .items()
+    }
+}
+
+/// A builder to construct a request to list device registrations.
+pub struct RequestBuilder<'a> {
+    inner: http::PaginatedRequestBuilder<'a, DeviceDetails>,
+}
+
+impl<'a> RequestBuilder<'a> {
+    /// Limit the number of results per page.
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.inner = self.inner.limit(limit);
+        self
+    }
+
+    /// Only list devices registered with the given client_id.
+    pub fn client_id(mut self, client_id: &str) -> Self {
+        self.inner = self.inner.params(&[("clientId", client_id)]);
+        self
+    }
+
+    /// Only list the device with the given ID.
+    pub fn device_id(mut self, device_id: &str) -> Self {
+        self.inner = self.inner.params(&[("deviceId", device_id)]);
+        self
+    }
+
+    /// Request a stream of pages of device registrations.
+    pub fn pages(self) -> impl Stream<Item = Result<http::PaginatedResult<DeviceDetails>>> + 'a {
+        self.inner.pages()
+    }
+
+    /// Request a stream of device registrations, transparently walking
+    /// pages.
+    pub fn items(self) -> impl Stream<Item = Result<DeviceDetails>> + 'a {
+        self.inner.items()
+    }
+
+    /// Retrieve the first page of device registrations.
+    pub async fn send(self) -> Result<http::PaginatedResult<DeviceDetails>> {
+        self.inner.send().await
+    }
+}
+
+/// A push notification payload, which is delivered to the devices
+/// subscribed to a channel when set in the push field of a message's
+/// Extras.
+///
+/// The apns, fcm and web fields override the payload for a particular
+/// transport, and are passed through as is.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PushPayload {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notification: Option<PushNotification>,
+    /// Arbitrary key-value data delivered to the app with the notification.
+    #[serde(skip_serializing_if = "json::Map::is_empty")]
+    pub data: json::Map,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub apns: Option<json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fcm: Option<json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub web: Option<json::Value>,
+}
+
+/// The user visible notification of a PushPayload.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PushNotification {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sound: Option<String>,
+    /// Notifications with the same collapse key replace each other on the
+    /// device rather than being shown separately.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collapse_key: Option<String>,
+}
+
+/// A device registered to receive push notifications.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DeviceDetails {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    pub form_factor: String,
+    pub platform: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<json::Map>,
+    pub push: DevicePushDetails,
+}
+
+/// The details of how push notifications are delivered to a device.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DevicePushDetails {
+    /// The transport specific details of the device, for example its APNs
+    /// device token.
+    pub recipient: json::Map,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<DevicePushState>,
+}
+
+/// The state of push notification delivery to a device.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum DevicePushState {
+    Active,
+    Failing,
+    Failed,
+}
+
+impl Decode for DeviceDetails {
+    type Options = ();
+    type Item = Self;
+    fn decode(_item: &mut Self::Item, _options: &Self::Options) -> Result<()> {
+        Ok(())
+    }
+}
@@ -1,35 +1,72 @@
 use std::marker::PhantomData;
+use std::ops::{Bound, RangeBounds};
 use std::sync::Arc;
+use std::time::Duration;
 
 use chrono::prelude::*;
-use lazy_static::lazy_static;
+use futures::stream::{self, Stream, StreamExt};
 use rand::seq::SliceRandom;
-use rand::thread_rng;
-use regex::Regex;
-use serde::de::DeserializeOwned;
+use rand::{thread_rng, Rng};
+use serde::de::{DeserializeOwned, Visitor};
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
-use crate::auth::Auth;
-use crate::crypto::CipherParams;
+use crate::auth::{Auth, AuthContext, AuthMode, AuthReason};
+use crate::crypto::Cipher;
 use crate::error::*;
 use crate::http::PaginatedRequestBuilder;
+use crate::log::LogLevel;
 use crate::options::ClientOptions;
 use crate::stats::Stats;
-use crate::{http, json, presence, stats, Result};
+use crate::{
+    batch, codec, compress, health, http, json, log, metadata, presence, push, stats, Result,
+};
+
+pub use crate::presence::{PresenceAction, PresenceMessage};
 
 pub const DEFAULT_FORMAT: Format = Format::MessagePack;
 
+/// The maximum number of publish requests Channel::publish_all sends
+/// concurrently.
+const PUBLISH_ALL_CONCURRENCY: usize = 10;
+
+/// The maximum number of channels Rest::export_history retrieves the history
+/// of concurrently.
+const EXPORT_HISTORY_CONCURRENCY: usize = 10;
+
+/// The number of messages Rest::export_history requests in each page.
+const EXPORT_HISTORY_PAGE_LIMIT: u32 = 1000;
+
 /// A client for the [Ably REST API].
 ///
 /// [Ably REST API]: https://ably.com/documentation/rest-api
 #[derive(Debug)]
 pub(crate) struct RestInner {
-    #[allow(dead_code)]
-    pub channels: (),
+    /// The state of each channel obtained with Channels::get, keyed by
+    /// channel name, until it is released with Channels::release.
+    pub channels: std::sync::Mutex<std::collections::HashMap<String, Arc<ChannelState>>>,
     pub reqwest: reqwest::Client,
+    pub executor: Arc<dyn http::HttpExecutor>,
+    pub headers: http::HeaderMap,
+    pub limiter: Option<Arc<tokio::sync::Semaphore>>,
     pub opts: ClientOptions,
     pub url: reqwest::Url,
+
+    /// The token most recently obtained to authenticate requests, which is
+    /// reused until it is about to expire or is rejected.
+    pub token: std::sync::Mutex<Option<crate::auth::TokenDetails>>,
+
+    /// Held whilst the token is renewed, so that concurrent requests which
+    /// need a new token share a single renewal.
+    pub renewing: tokio::sync::Mutex<()>,
+
+    /// The TokenParams and AuthOptions passed to the most recent call to
+    /// Auth::authorize, which are used to request subsequent tokens.
+    pub authorized: std::sync::Mutex<Option<(crate::auth::TokenParams, crate::auth::AuthOptions)>>,
+
+    /// The offset of the Ably server's clock from the local clock, which is
+    /// queried once when the query_time option is set.
+    pub time_offset: std::sync::Mutex<Option<chrono::Duration>>,
 }
 
 #[derive(Debug, Clone)]
@@ -46,23 +83,114 @@ impl Rest {
         Channels { rest: self }
     }
 
+    pub fn push(&self) -> push::Push<'_> {
+        push::Push::new(self)
+    }
+
+    /// Returns the channel metadata API, for listing the app's active
+    /// channels.
+    pub fn channels_metadata(&self) -> metadata::ChannelsMetadata<'_> {
+        metadata::ChannelsMetadata::new(self)
+    }
+
     pub fn options(&self) -> &ClientOptions {
         &self.inner.opts
     }
 
+    /// Returns the limits which published messages must satisfy, as set with
+    /// ClientOptions::max_message_size and ClientOptions::max_frame_size.
+    pub fn limits(&self) -> Limits {
+        Limits {
+            max_message_size: self.inner.opts.max_message_size,
+            max_frame_size: self.inner.opts.max_frame_size,
+        }
+    }
+
     pub fn new(key: &str) -> Result<Self> {
         ClientOptions::new(key).rest()
     }
 
-    pub(crate) fn create(reqwest: reqwest::Client, opts: ClientOptions, url: reqwest::Url) -> Self {
-        Self {
+    /// Returns a Rest client configured from environment variables such as
+    /// `ABLY_KEY` and `ABLY_ENVIRONMENT`. See ClientOptions::from_env.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> ably::Result<()> {
+    /// let client = ably::Rest::from_env()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_env() -> Result<Self> {
+        ClientOptions::from_env()?.rest()
+    }
+
+    /// Returns a Rest client which uses the given HttpExecutor to send HTTP
+    /// requests instead of a reqwest::Client.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ably::http::HttpExecutor;
+    /// use futures::future::BoxFuture;
+    ///
+    /// #[derive(Debug)]
+    /// struct LoggingExecutor(reqwest::Client);
+    ///
+    /// impl HttpExecutor for LoggingExecutor {
+    ///     fn execute(
+    ///         &self,
+    ///         req: reqwest::Request,
+    ///     ) -> BoxFuture<'_, ably::Result<reqwest::Response>> {
+    ///         println!("{} {}", req.method(), req.url());
+    ///         HttpExecutor::execute(&self.0, req)
+    ///     }
+    /// }
+    ///
+    /// let opts = ably::ClientOptions::new("<api_key>");
+    /// let client = ably::Rest::with_http_executor(opts, LoggingExecutor(reqwest::Client::new()))?;
+    /// # Ok::<(), ably::Error>(())
+    /// ```
+    pub fn with_http_executor(
+        opts: ClientOptions,
+        executor: impl http::HttpExecutor + 'static,
+    ) -> Result<Self> {
+        let reqwest = opts.build_http_client()?;
+        let url = opts.rest_url()?;
+        Self::create(reqwest, Arc::new(executor), opts, url)
+    }
+
+    pub(crate) fn create(
+        reqwest: reqwest::Client,
+        executor: Arc<dyn http::HttpExecutor>,
+        opts: ClientOptions,
+        url: reqwest::Url,
+    ) -> Result<Self> {
+        let rest = Self {
             inner: Arc::new(RestInner {
                 reqwest,
+                executor,
+                headers: opts.default_headers()?,
+                limiter: opts
+                    .max_concurrent_requests
+                    .map(|max| Arc::new(tokio::sync::Semaphore::new(max))),
                 opts,
                 url,
-                channels: (),
+                channels: Default::default(),
+                token: Default::default(),
+                renewing: Default::default(),
+                authorized: Default::default(),
+                time_offset: Default::default(),
             }),
+        };
+
+        rest.inner.opts.validate()?;
+
+        if let crate::auth::Credential::TokenDetails(token) = &rest.inner.opts.credential {
+            rest.auth().check_client_id(token)?;
         }
+
+        Ok(rest)
     }
 
     /// Start building a GET request to /stats.
@@ -82,6 +210,7 @@ impl Rest {
     ///     .stats()
     ///     .start("2021-09-09:15:00")
     ///     .end("2021-09-09:15:05")
+    ///     .unit(ably::stats::Unit::Minute)
     ///     .send()
     ///     .await?;
     ///
@@ -93,21 +222,174 @@ impl Rest {
         self.paginated_request_with_options(http::Method::GET, "/stats", ())
     }
 
-    /// Sends a GET request to /time and returns the server time in UTC.
+    /// Retrieve the message history of each of the given channels within the
+    /// given time range, returning a stream of each message along with the
+    /// name of its channel.
+    ///
+    /// The history of up to 10 channels is retrieved concurrently, so
+    /// messages from different channels are interleaved, but the messages of
+    /// each channel are returned oldest first. Messages are decrypted with
+    /// the cipher of their channel if one is set. The stream ends with an
+    /// error if any history request fails.
     ///
     /// # Example
     ///
     /// ```
-    /// # async fn run() -> ably::Result<()> {
-    /// let client = ably::Rest::from("<api_key>");
+    /// # #[tokio::main]
+    /// # async fn main() -> ably::Result<()> {
+    /// use chrono::{Duration, Utc};
+    /// use futures::TryStreamExt;
+    ///
+    /// # let transport = ably::mock::MockTransport::new()
+    /// #     .on(ably::http::Method::GET, "/channels/orders/history", 200, serde_json::json!([{"data": "order-1"}]))
+    /// #     .on(ably::http::Method::GET, "/channels/payments/history", 200, serde_json::json!([{"data": "payment-1"}]));
+    /// # let client = ably::Rest::with_http_executor(ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc"), transport)?;
+    /// let yesterday = Utc::now() - Duration::days(1);
+    ///
+    /// let mut export = Box::pin(client.export_history(["orders", "payments"], yesterday..));
+    /// while let Some((channel, msg)) = export.try_next().await? {
+    ///     println!("{}: {:?}", channel, msg.data);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn export_history<'a>(
+        &'a self,
+        channels: impl IntoIterator<Item = impl Into<String>> + 'a,
+        range: impl RangeBounds<DateTime<Utc>>,
+    ) -> impl Stream<Item = Result<(String, Message)>> + 'a {
+        let start = match range.start_bound() {
+            Bound::Included(t) => Some(t.timestamp_millis()),
+            Bound::Excluded(t) => Some(t.timestamp_millis() + 1),
+            Bound::Unbounded => None,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(t) => Some(t.timestamp_millis()),
+            Bound::Excluded(t) => Some(t.timestamp_millis() - 1),
+            Bound::Unbounded => None,
+        };
+
+        stream::iter(channels)
+            .map(move |name| {
+                let name = name.into();
+                let mut req = self
+                    .channels()
+                    .get(name.clone())
+                    .history()
+                    .forwards()
+                    .limit(EXPORT_HISTORY_PAGE_LIMIT);
+                if let Some(start) = start {
+                    req = req.start(&start.to_string());
+                }
+                if let Some(end) = end {
+                    req = req.end(&end.to_string());
+                }
+                Box::pin(
+                    req.items()
+                        .map(move |msg| msg.map(|msg| (name.clone(), msg))),
+                )
+            })
+            .flatten_unordered(EXPORT_HISTORY_CONCURRENCY)
+    }
+
+    /// Publish messages to multiple channels in a single request to the
+    /// batch publish endpoint, returning a BatchResult for each
+    /// BatchPublishSpec in the same order (RSC22).
+    ///
+    /// The messages of each spec are published to every channel in the
+    /// spec. Publishing to some channels can fail whilst succeeding for
+    /// others, so the results should be checked for failures. Messages are
+    /// encoded but not encrypted, since channel ciphers aren't applied.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> ably::Result<()> {
+    /// use ably::batch::BatchPublishSpec;
+    /// use ably::rest::Message;
+    ///
+    /// # let res = serde_json::json!([{"successCount": 2, "failureCount": 0, "results": [
+    /// #     {"channel": "match-1", "messageId": "abc", "serials": ["01"]},
+    /// #     {"channel": "match-2", "messageId": "def", "serials": ["02"]},
+    /// # ]}]);
+    /// # let transport = ably::mock::MockTransport::new().on(ably::http::Method::POST, "/messages", 201, res);
+    /// # let client = ably::Rest::with_http_executor(ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc"), transport)?;
+    /// let score = Message {
+    ///     name: Some("score".to_string()),
+    ///     data: "2-1".into(),
+    ///     ..Default::default()
+    /// };
+    ///
+    /// let results = client
+    ///     .batch_publish(vec![BatchPublishSpec::new(["match-1", "match-2"], vec![score])])
+    ///     .await?;
+    /// assert!(results[0].is_success());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails if the request itself fails, for example because the client
+    /// isn't authorised, but not if publishing to individual channels fails.
+    pub async fn batch_publish(
+        &self,
+        mut specs: Vec<batch::BatchPublishSpec>,
+    ) -> Result<Vec<batch::BatchResult<batch::BatchPublishResult>>> {
+        let format = self.inner.opts.format;
+        for spec in specs.iter_mut() {
+            if self.inner.opts.idempotent_rest_publishing {
+                Self::assign_message_ids(&mut spec.messages);
+            }
+            for msg in spec.messages.iter_mut() {
+                msg.encode(&format, None)?;
+            }
+            self.limits().check(&spec.messages, format)?;
+        }
+
+        // Protocol version 2 responses contain a BatchResult per spec,
+        // whereas earlier versions fail the whole request with a
+        // batchResponse on partial failure.
+        let version = http::Headers::from_pairs([(
+            crate::protocol::VERSION_HEADER,
+            crate::protocol::Protocol::V2.version(),
+        )])?;
+
+        self.request(http::Method::POST, "/messages")
+            .headers(version)
+            .body(&specs)
+            .send()
+            .await?
+            .body()
+            .await
+    }
+
+    /// Sends a GET request to /time and returns the server time in UTC.
+    ///
+    /// # Example
     ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> ably::Result<()> {
+    /// # use ably::{http::Method, mock::MockTransport};
+    /// # let transport = MockTransport::new().on(Method::GET, "/time", 200, serde_json::json!([1650000000000_i64]));
+    /// # let client = ably::Rest::with_http_executor(ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc"), transport)?;
     /// let time = client.time().await?;
+    /// assert_eq!(time.to_rfc3339(), "2022-04-15T05:20:00+00:00");
     /// # Ok(())
     /// # }
     /// ```
     pub async fn time(&self) -> Result<DateTime<Utc>> {
+        self.request_time(true).await
+    }
+
+    /// Retrieve the server time, optionally without authenticating, so that
+    /// it can be queried while signing a token request.
+    pub(crate) async fn request_time(&self, authenticate: bool) -> Result<DateTime<Utc>> {
         let mut res: Vec<i64> = self
             .request(http::Method::GET, "/time")
+            .authenticate(authenticate)
             .send()
             .await?
             .body()
@@ -125,6 +407,27 @@ impl Rest {
         })
     }
 
+    /// Check that the client can resolve the REST host, connect to it, and
+    /// authenticate with its credentials, for example to implement a
+    /// readiness probe.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # async fn run() -> ably::Result<()> {
+    /// let client = ably::Rest::from("<api_key>");
+    ///
+    /// let health = client.health_check().await;
+    /// if !health.is_healthy() {
+    ///     println!("Ably is unavailable: {:?}", health);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn health_check(&self) -> health::HealthCheck {
+        health::check(self).await
+    }
+
     /// Start building a HTTP request to the Ably REST API.
     ///
     /// Returns a RequestBuilder which can be used to set query params, headers
@@ -160,7 +463,7 @@ impl Rest {
     pub fn request(&self, method: http::Method, path: &str) -> http::RequestBuilder {
         let mut url = self.inner.url.clone();
         url.set_path(path);
-        self.request_url(method, url)
+        self.request_url(method, url).accept_format()
     }
 
     pub(crate) fn request_url(
@@ -170,7 +473,10 @@ impl Rest {
     ) -> http::RequestBuilder {
         http::RequestBuilder::new(
             self,
-            self.inner.reqwest.request(method, url),
+            self.inner
+                .reqwest
+                .request(method, url)
+                .headers(self.inner.headers.clone()),
             self.inner.opts.format,
         )
     }
@@ -218,26 +524,215 @@ impl Rest {
         http::PaginatedRequestBuilder::new(self.request(method, path), options)
     }
 
+    /// Start building a paginated HTTP request to an arbitrary endpoint of
+    /// the Ably REST API, for example one which the library doesn't yet wrap
+    /// (RSC19).
+    ///
+    /// Use `json::Value` as the item type to get back an
+    /// http::HttpPaginatedResponse, which exposes the status code and headers
+    /// of each page along with its JSON items.
+    ///
+    /// Unlike other requests, a response with an error status code is
+    /// returned as a page with no items rather than as an error, so that its
+    /// status code and headers can be inspected; only a failure to send the
+    /// request results in an error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> ably::Result<()> {
+    /// use ably::http::Method;
+    ///
+    /// # let transport = ably::mock::MockTransport::new().on(Method::GET, "/channels", 200, serde_json::json!(["channel-1", "channel-2"]));
+    /// # let client = ably::Rest::with_http_executor(ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc"), transport)?;
+    /// let res = client
+    ///     .paginated_request::<serde_json::Value>(Method::GET, "/channels")
+    ///     .params(&[("limit", "10")])
+    ///     .send()
+    ///     .await?;
+    ///
+    /// assert_eq!(res.status_code(), 200);
+    /// let items = res.items().await?;
+    /// assert_eq!(items, vec!["channel-1", "channel-2"]);
+    /// # Ok(())
+    /// # }
+    /// ```
     pub fn paginated_request<'a, T: DeserializeOwned + Send + 'static>(
         &'a self,
         method: http::Method,
         path: &str,
     ) -> http::PaginatedRequestBuilder<DecodeRaw<T>> {
         self.paginated_request_with_options(method, path, ())
+            .return_error_responses()
     }
 
     /// Send the given request, retrying against fallback hosts if it fails.
+    ///
+    /// If ClientOptions.add_request_ids is set, a random request_id param is
+    /// added to the request before it is sent, and is included in any
+    /// resulting error (RSC7c).
     pub(crate) async fn send(
+        &self,
+        mut req: reqwest::Request,
+        auth: Option<AuthMode>,
+    ) -> Result<http::Response> {
+        // Fallback requests are cloned from this request, so they all share
+        // the same request_id.
+        let request_id = if self.inner.opts.add_request_ids {
+            let id = Self::generate_request_id();
+            req.url_mut()
+                .query_pairs_mut()
+                .append_pair("request_id", &id);
+            Some(id)
+        } else {
+            None
+        };
+
+        self.send_with_rate_limit_retries(req, auth)
+            .await
+            .map_err(|mut err| {
+                if err.request_id.is_none() {
+                    err.request_id = request_id;
+                }
+                err
+            })
+    }
+
+    /// Returns the current time according to the Ably servers if the offset
+    /// of the server's clock is known, and the local time otherwise.
+    pub(crate) fn server_now(&self) -> DateTime<Utc> {
+        let offset = *self.inner.time_offset.lock().unwrap();
+        Utc::now() + offset.unwrap_or_else(chrono::Duration::zero)
+    }
+
+    /// Generate a url-safe base64 encoded request_id from 12 random bytes.
+    fn generate_request_id() -> String {
+        base64::encode_config(thread_rng().gen::<[u8; 12]>(), base64::URL_SAFE)
+    }
+
+    /// Generate a base64 encoded base for the IDs of idempotently published
+    /// messages from 9 random bytes (RSL1k1).
+    fn generate_message_id_base() -> String {
+        base64::encode(thread_rng().gen::<[u8; 9]>())
+    }
+
+    /// Give the messages IDs of the form <base>:<index> from a generated
+    /// base, unless any of them already has an ID (RSL1k1, RSL1k2).
+    fn assign_message_ids(messages: &mut [Message]) {
+        if messages.iter().any(|msg| msg.id.is_some()) {
+            return;
+        }
+
+        let base = Self::generate_message_id_base();
+        for (i, msg) in messages.iter_mut().enumerate() {
+            msg.id = Some(format!("{}:{}", base, i));
+        }
+    }
+
+    /// Send the request, retrying up to ClientOptions.rate_limit_retry_count
+    /// times if it is rate limited with a Retry-After header.
+    async fn send_with_rate_limit_retries(
+        &self,
+        mut req: reqwest::Request,
+        auth: Option<AuthMode>,
+    ) -> Result<http::Response> {
+        let mut retries = 0;
+        loop {
+            let next_req = req.try_clone();
+
+            let err = match self.send_with_reauth(req, auth).await {
+                Ok(res) => return Ok(res),
+                Err(err) => err,
+            };
+
+            let delay = match (err.status_code, err.retry_after, next_req) {
+                (Some(429), Some(delay), Some(next_req))
+                    if retries < self.inner.opts.rate_limit_retry_count =>
+                {
+                    req = next_req;
+                    delay
+                }
+                _ => return Err(err),
+            };
+
+            retries += 1;
+            self.inner.opts.log(LogLevel::Warn, || {
+                format!(
+                    "request rate limited, retrying in {:?}: {}",
+                    delay,
+                    log::describe_error(&err)
+                )
+            });
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Send the request, and if it is authenticated with a token which Ably
+    /// rejects, for example because it has expired, obtain a new token and
+    /// retry the request once (RSA4b).
+    async fn send_with_reauth(
+        &self,
+        req: reqwest::Request,
+        auth: Option<AuthMode>,
+    ) -> Result<http::Response> {
+        let mode = match auth {
+            Some(mode) => mode,
+            None => return self.send_with_fallbacks(req, None).await,
+        };
+
+        let next_req = req.try_clone();
+
+        let err = match self
+            .send_with_fallbacks(req, Some((mode, AuthContext::default())))
+            .await
+        {
+            Ok(res) => return Ok(res),
+            Err(err) => err,
+        };
+
+        match next_req {
+            Some(req) if Self::is_token_error(&err) && self.auth().can_renew() => {
+                self.inner.opts.log(LogLevel::Debug, || {
+                    format!(
+                        "token rejected, renewing it and retrying request: {}",
+                        log::describe_error(&err)
+                    )
+                });
+                let context = AuthContext {
+                    reason: AuthReason::Renewal,
+                    attempt: 2,
+                    previous_error: Some(&err),
+                };
+                self.send_with_fallbacks(req, Some((mode, context))).await
+            }
+            _ => Err(err),
+        }
+    }
+
+    /// Return whether the error indicates that the token used to
+    /// authenticate the request was rejected (RSA4b).
+    fn is_token_error(err: &Error) -> bool {
+        (40140..40150).contains(&err.code.code())
+    }
+
+    /// Send the request, retrying it against fallback hosts if it fails with
+    /// a retriable error.
+    ///
+    /// If auth is set, the request is authenticated with the client's
+    /// credentials using its AuthMode, passing its AuthContext to the
+    /// AuthCallback if a token is requested.
+    async fn send_with_fallbacks(
         &self,
         req: reqwest::Request,
-        authenticate: bool,
+        auth: Option<(AuthMode, AuthContext<'_>)>,
     ) -> Result<http::Response> {
         // Executing the request will consume it, so clone it first for a
         // potential retry later.
         let mut next_req = req.try_clone();
 
         // Execute the request, and return the response if it succeeds.
-        let mut err = match self.execute(req, authenticate).await {
+        let mut err = match self.execute(req, auth).await {
             Ok(res) => return Ok(res),
             Err(err) => err,
         };
@@ -247,12 +742,11 @@ impl Rest {
             return Err(err);
         }
 
-        if self.inner.opts.fallback_hosts.is_empty() {
+        // Create a randomised list of fallback hosts if they're set.
+        let mut hosts = self.inner.opts.resolved_fallback_hosts();
+        if hosts.is_empty() {
             return Err(err);
         }
-
-        // Create a randomised list of fallback hosts if they're set.
-        let mut hosts = self.inner.opts.fallback_hosts.clone();
         hosts.shuffle(&mut thread_rng());
 
         // Try sending the request to the fallback hosts, capped at
@@ -272,9 +766,16 @@ impl Rest {
                     format!("invalid fallback host '{}': {}", host, err),
                 )
             })?;
+            self.inner.opts.log(LogLevel::Warn, || {
+                format!(
+                    "retrying {} against a fallback host after error: {}",
+                    log::describe_request(&req),
+                    log::describe_error(&err)
+                )
+            });
 
             // Execute the request, and return the response if it succeeds.
-            err = match self.execute(req, authenticate).await {
+            err = match self.execute(req, auth).await {
                 Ok(res) => return Ok(res),
                 Err(err) => err,
             };
@@ -291,25 +792,76 @@ impl Rest {
     async fn execute(
         &self,
         mut req: reqwest::Request,
-        authenticate: bool,
+        auth: Option<(AuthMode, AuthContext<'_>)>,
     ) -> Result<http::Response> {
-        if authenticate {
-            self.auth().with_auth_headers(&mut req).await?;
+        if let Some((mode, context)) = auth {
+            self.auth()
+                .with_auth_headers(&mut req, mode, context)
+                .await?;
         }
 
-        let res = self.inner.reqwest.execute(req).await?;
+        for middleware in &self.inner.opts.middleware {
+            middleware.on_request(&mut req)?;
+        }
+
+        if let Some(signer) = &self.inner.opts.request_signer {
+            http::sign_request(signer.as_ref(), &mut req)?;
+        }
+
+        let permit = self.acquire_request_permit().await?;
+        self.inner.opts.log(LogLevel::Verbose, || {
+            format!("sending {}", log::describe_request(&req))
+        });
+        // The executor consumes the request, so describe it up front for the
+        // logs which follow, but only if they can be logged.
+        let description = if self.inner.opts.log_enabled(LogLevel::Warn) {
+            log::describe_request(&req)
+        } else {
+            String::new()
+        };
+        let res = self.inner.executor.execute(req).await.inspect_err(|err| {
+            self.inner.opts.log(LogLevel::Warn, || {
+                format!("{} failed: {}", description, log::describe_error(err))
+            });
+        })?;
+        self.inner.opts.log(LogLevel::Verbose, || {
+            format!("{} returned {}", description, res.status())
+        });
+
+        for middleware in &self.inner.opts.middleware {
+            middleware.on_response(&res)?;
+        }
 
         // Return the response if it was successful, otherwise try to decode a
         // JSON error from the response body, falling back to a generic error
         // if decoding fails.
         if res.status().is_success() {
-            return Ok(http::Response::new(res));
+            return Ok(http::Response::new(res)
+                .with_max_body_size(self.inner.opts.max_response_body_size)
+                .with_permit(permit));
         }
 
         let status_code: u32 = res.status().as_u16().into();
-        Err(res
-            .json::<WrappedError>()
-            .await
+        let status = res.status();
+        let headers = res.headers().clone();
+        let retry_after = match status_code {
+            429 | 503 => res
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(http::parse_retry_after),
+            _ => None,
+        };
+        let body = http::Response::new(res)
+            .with_max_body_size(self.inner.opts.max_response_body_size)
+            .with_permit(permit)
+            .bytes()
+            .await;
+        let mut err = body
+            .as_ref()
+            .map_err(ToString::to_string)
+            .and_then(|body| {
+                serde_json::from_slice::<WrappedError>(body).map_err(|err| err.to_string())
+            })
             .map(|e| e.error)
             .unwrap_or_else(|err| {
                 Error::with_status(
@@ -317,7 +869,46 @@ impl Rest {
                     status_code,
                     format!("Unexpected error: {}", err),
                 )
-            }))
+            });
+        err.retry_after = retry_after;
+        if err.cause.is_none() {
+            err.cause = Some(Box::new(http::ErrorResponse {
+                status,
+                headers,
+                body: body.unwrap_or_default(),
+            }));
+        }
+        Err(err)
+    }
+
+    /// Wait for a free request slot if ClientOptions.max_concurrent_requests
+    /// is set, failing if none is available within the request timeout.
+    ///
+    /// The slot is held by the returned permit, which is passed on to the
+    /// response so that it is only released once the body has been read.
+    async fn acquire_request_permit(&self) -> Result<Option<tokio::sync::OwnedSemaphorePermit>> {
+        let limiter = match &self.inner.limiter {
+            Some(limiter) => limiter,
+            None => return Ok(None),
+        };
+
+        let timeout = self.inner.opts.http_request_timeout;
+        match tokio::time::timeout(timeout, limiter.clone().acquire_owned()).await {
+            Ok(Ok(permit)) => Ok(Some(permit)),
+            Ok(Err(err)) => Err(Error::with_cause(
+                ErrorCode::InternalError,
+                err,
+                "request limiter closed",
+            )),
+            Err(_) => Err(Error::new(
+                ErrorCode::TimeoutError,
+                format!(
+                    "timed out after {:?} waiting for one of {} concurrent requests to complete",
+                    timeout,
+                    self.inner.opts.max_concurrent_requests.unwrap_or_default(),
+                ),
+            )),
+        }
     }
 
     /// Return whether a request can be retried based on the error which
@@ -325,8 +916,65 @@ impl Rest {
     fn is_retriable(err: &Error) -> bool {
         match err.status_code {
             Some(code) => (500..=504).contains(&code),
-            None => true,
+            // Requests which timed out waiting for a request slot would just
+            // wait again if retried.
+            None => err.code != ErrorCode::TimeoutError,
+        }
+    }
+}
+
+/// The limits of an Ably account which published messages must satisfy.
+///
+/// Ably rejects publishes which exceed the limits, so Channel::preflight can
+/// be used to check messages against them before publishing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    /// The maximum size of a single message, as returned by Message::size.
+    pub max_message_size: u64,
+    /// The maximum size of a single request body.
+    pub max_frame_size: u64,
+}
+
+impl Limits {
+    /// Check that the given encoded messages are within the limits when sent
+    /// in a single request body in the given format.
+    ///
+    /// Fails with a MaximumMessageLengthExceeded error if any of the messages
+    /// or the request body is too large.
+    pub fn check(&self, messages: &[Message], format: Format) -> Result<()> {
+        for msg in messages {
+            let size = msg.size() as u64;
+            if size > self.max_message_size {
+                return Err(Error::with_status(
+                    ErrorCode::MaximumMessageLengthExceeded,
+                    400,
+                    format!(
+                        "message size {} exceeds the maximum message size of {} bytes",
+                        size, self.max_message_size
+                    ),
+                ));
+            }
+        }
+
+        let body = match (format, messages) {
+            (Format::MessagePack, [msg]) => rmp_serde::to_vec_named(msg)?,
+            (Format::MessagePack, _) => rmp_serde::to_vec_named(messages)?,
+            (Format::JSON, [msg]) => serde_json::to_vec(msg)?,
+            (Format::JSON, _) => serde_json::to_vec(messages)?,
+        };
+        let size = body.len() as u64;
+        if size > self.max_frame_size {
+            return Err(Error::with_status(
+                ErrorCode::MaximumMessageLengthExceeded,
+                400,
+                format!(
+                    "request body size {} exceeds the maximum frame size of {} bytes",
+                    size, self.max_frame_size
+                ),
+            ));
         }
+
+        Ok(())
     }
 }
 
@@ -353,87 +1001,304 @@ impl From<&str> for Rest {
 }
 
 /// Options for publishing messages on a channel.
-#[derive(Clone)]
+///
+/// # Example
+///
+/// ```
+/// use ably::crypto::CipherParams;
+/// use ably::http::Params;
+/// use ably::rest::ChannelOptions;
+///
+/// let opts = ChannelOptions::new()
+///     .cipher(CipherParams::builder().string("WUP6u0K7MXI5Zeo0VppPwg==")?.build()?)
+///     .params(Params::from([("quickAck", "true")]));
+/// let client = ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc")
+///     .default_channel_options(opts)
+///     .rest()?;
+/// # Ok::<(), ably::Error>(())
+/// ```
+#[derive(Clone, Default)]
 pub struct ChannelOptions {
-    pub(crate) cipher: Option<CipherParams>,
+    pub(crate) cipher: Option<Arc<dyn Cipher>>,
+    pub(crate) params: http::Params,
+    pub(crate) max_decompressed_size: Option<u64>,
 }
 
-/// Start building a Channel to publish a message.
-pub struct ChannelBuilder<'a> {
-    rest: &'a Rest,
-    name: String,
-    cipher: Option<CipherParams>,
-}
+impl ChannelOptions {
+    /// Returns ChannelOptions without a cipher or params.
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-impl<'a> ChannelBuilder<'a> {
-    fn new(rest: &'a Rest, name: String) -> Self {
-        Self {
-            rest,
-            name,
-            cipher: None,
-        }
+    /// Set the cipher used to encrypt published messages and decrypt
+    /// retrieved messages, which is either CipherParams or a custom Cipher.
+    pub fn cipher(mut self, cipher: impl Cipher + 'static) -> Self {
+        self.cipher = Some(Arc::new(cipher));
+        self
     }
 
-    /// Set the channel cipher parameters.
-    pub fn cipher(mut self, cipher: CipherParams) -> Self {
-        self.cipher = Some(cipher);
+    /// Set the params included in the query string of publish requests.
+    pub fn params(mut self, params: http::Params) -> Self {
+        self.params = params;
         self
     }
 
-    /// Build the Channel.
-    pub fn get(self) -> Channel<'a> {
-        let opts = Some(ChannelOptions {
-            cipher: self.cipher,
-        });
+    /// Set the maximum size of the data of a retrieved compressed message
+    /// once it is decompressed. Defaults to 1MiB.
+    ///
+    /// A message whose data would decompress to more than this fails to
+    /// decode with an InvalidMessageDataOrEncoding error, so that a small
+    /// message from any publisher can't expand to exhaust memory.
+    pub fn max_decompressed_size(mut self, bytes: u64) -> Self {
+        self.max_decompressed_size = Some(bytes);
+        self
+    }
+}
 
-        Channel {
-            name: self.name.clone(),
-            rest: self.rest,
-            presence: Presence::new(self.rest, self.name, opts.clone()),
-            opts,
-        }
+impl std::fmt::Debug for ChannelOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Omit the cipher key.
+        f.debug_struct("ChannelOptions")
+            .field("cipher", &self.cipher.as_ref().map(|_| "<redacted>"))
+            .field("params", &self.params)
+            .field("max_decompressed_size", &self.max_decompressed_size)
+            .finish()
     }
 }
 
-/// A collection of Channels.
-#[derive(Clone, Debug)]
-pub struct Channels<'a> {
-    rest: &'a Rest,
+/// The state of a channel which is shared by every Channel obtained from the
+/// client's Channels with the same name.
+#[derive(Debug, Default)]
+pub(crate) struct ChannelState {
+    opts: std::sync::Mutex<ChannelOptions>,
 }
 
-impl<'a> Channels<'a> {
-    pub fn new(rest: &'a Rest) -> Self {
-        Self { rest }
+impl ChannelState {
+    fn new(opts: ChannelOptions) -> Self {
+        Self {
+            opts: std::sync::Mutex::new(opts),
+        }
     }
 
-    /// Start building a Channel with the given name.
-    pub fn name(&self, name: impl Into<String>) -> ChannelBuilder<'a> {
-        ChannelBuilder::new(self.rest, name.into())
+    fn options(&self) -> ChannelOptions {
+        self.opts.lock().unwrap().clone()
     }
 
-    /// Build and return a Channel with the given name.
-    pub fn get(&self, name: impl Into<String>) -> Channel<'a> {
-        self.name(name).get()
+    fn set_options(&self, opts: ChannelOptions) {
+        *self.opts.lock().unwrap() = opts;
     }
 }
 
-/// An Ably Channel to publish messages to or retrieve history or presence for.
-pub struct Channel<'a> {
-    pub name: String,
-    pub presence: Presence<'a>,
+/// Start building a Channel to publish a message.
+pub struct ChannelBuilder<'a> {
     rest: &'a Rest,
-    opts: Option<ChannelOptions>,
+    name: String,
+    opts: ChannelOptions,
 }
 
-impl<'a> Channel<'a> {
+impl<'a> ChannelBuilder<'a> {
+    fn new(rest: &'a Rest, name: String) -> Self {
+        let opts = match rest.inner.channels.lock().unwrap().get(&name) {
+            Some(state) => state.options(),
+            None => rest
+                .inner
+                .opts
+                .default_channel_options
+                .clone()
+                .unwrap_or_default(),
+        };
+
+        Self { rest, name, opts }
+    }
+
+    /// Set the channel cipher parameters, overriding the cipher of the
+    /// channel's current options.
+    pub fn cipher(mut self, cipher: impl Cipher + 'static) -> Self {
+        self.opts.cipher = Some(Arc::new(cipher));
+        self
+    }
+
+    /// Set the channel options, replacing the channel's current options and
+    /// any cipher set so far.
+    pub fn options(mut self, opts: ChannelOptions) -> Self {
+        self.opts = opts;
+        self
+    }
+
+    /// Build the Channel, applying the options to every other Channel with
+    /// the same name.
+    pub fn get(self) -> Channel<'a> {
+        let state = self
+            .rest
+            .inner
+            .channels
+            .lock()
+            .unwrap()
+            .entry(self.name.clone())
+            .or_default()
+            .clone();
+        state.set_options(self.opts);
+
+        Channel::new(self.rest, self.name, state)
+    }
+}
+
+/// The client's collection of Channels.
+///
+/// Channels obtained with the same name share their state, such as their
+/// options, until the channel is released.
+#[derive(Clone, Debug)]
+pub struct Channels<'a> {
+    rest: &'a Rest,
+}
+
+impl<'a> Channels<'a> {
+    pub fn new(rest: &'a Rest) -> Self {
+        Self { rest }
+    }
+
+    /// Start building a Channel with the given name.
+    pub fn name(&self, name: impl Into<String>) -> ChannelBuilder<'a> {
+        ChannelBuilder::new(self.rest, name.into())
+    }
+
+    /// Return the Channel with the given name, creating it with the client's
+    /// default channel options if it doesn't already exist.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let client = ably::Rest::from("aaaaaa.bbbbbb:cccccc");
+    ///
+    /// let channel = client.channels().get("rust-example");
+    /// assert!(client.channels().exists("rust-example"));
+    /// ```
+    pub fn get(&self, name: impl Into<String>) -> Channel<'a> {
+        let name = name.into();
+        let state = self
+            .rest
+            .inner
+            .channels
+            .lock()
+            .unwrap()
+            .entry(name.clone())
+            .or_insert_with(|| {
+                Arc::new(ChannelState::new(
+                    self.rest
+                        .inner
+                        .opts
+                        .default_channel_options
+                        .clone()
+                        .unwrap_or_default(),
+                ))
+            })
+            .clone();
+
+        Channel::new(self.rest, name, state)
+    }
+
+    /// Return the Channel with the given name, replacing its options with
+    /// the given options.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ably::crypto::CipherParams;
+    /// use ably::rest::ChannelOptions;
+    ///
+    /// let client = ably::Rest::from("aaaaaa.bbbbbb:cccccc");
+    /// let cipher = CipherParams::builder().string("WUP6u0K7MXI5Zeo0VppPwg==")?.build()?;
+    ///
+    /// let channel = client
+    ///     .channels()
+    ///     .get_with_options("rust-example", ChannelOptions::new().cipher(cipher));
+    /// # Ok::<(), ably::Error>(())
+    /// ```
+    pub fn get_with_options(&self, name: impl Into<String>, opts: ChannelOptions) -> Channel<'a> {
+        self.name(name).options(opts).get()
+    }
+
+    /// Returns whether a Channel with the given name has been obtained and
+    /// not released.
+    pub fn exists(&self, name: &str) -> bool {
+        self.rest.inner.channels.lock().unwrap().contains_key(name)
+    }
+
+    /// Returns an iterator over the channels which have been obtained and not
+    /// released, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = Channel<'a>> {
+        let rest = self.rest;
+        let channels = rest
+            .inner
+            .channels
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, state)| (name.clone(), state.clone()))
+            .collect::<Vec<_>>();
+
+        channels
+            .into_iter()
+            .map(move |(name, state)| Channel::new(rest, name, state))
+    }
+
+    /// Release the channel with the given name, dropping its state so that a
+    /// subsequent Channels::get returns a new channel with the client's
+    /// default channel options.
+    ///
+    /// Returns whether the channel existed. Channels already obtained with
+    /// the name keep working, but no longer share state with new ones.
+    pub fn release(&self, name: &str) -> bool {
+        self.rest
+            .inner
+            .channels
+            .lock()
+            .unwrap()
+            .remove(name)
+            .is_some()
+    }
+}
+
+/// An Ably Channel to publish messages to or retrieve history or presence for.
+pub struct Channel<'a> {
+    pub name: String,
+    pub presence: Presence<'a>,
+    rest: &'a Rest,
+    state: Arc<ChannelState>,
+}
+
+impl<'a> Channel<'a> {
+    fn new(rest: &'a Rest, name: String, state: Arc<ChannelState>) -> Self {
+        Self {
+            presence: Presence::new(rest, name.clone(), state.clone()),
+            name,
+            rest,
+            state,
+        }
+    }
+
+    /// Returns the channel's current options.
+    pub fn options(&self) -> ChannelOptions {
+        self.state.options()
+    }
+
+    /// Replace the channel's options, which apply to subsequent publish,
+    /// history and presence requests on every Channel with the same name.
+    ///
+    /// REST channels have no attachment to Ably, so the options take effect
+    /// immediately without re-attaching.
+    pub fn set_options(&self, opts: ChannelOptions) {
+        self.state.set_options(opts);
+    }
+
     /// Start building a request to publish a message on the channel.
     pub fn publish(&self) -> PublishBuilder {
         let mut builder = PublishBuilder::new(self.rest, self.name.clone());
 
-        if let Some(opts) = &self.opts {
-            if let Some(cipher) = &opts.cipher {
-                builder = builder.cipher(cipher.clone());
-            }
+        let opts = self.options();
+        builder.cipher = opts.cipher;
+        if !opts.params.is_empty() {
+            builder = builder.params(&opts.params);
         }
 
         builder
@@ -443,32 +1308,375 @@ impl<'a> Channel<'a> {
     ///
     /// Returns a history::RequestBuilder which is used to set parameters
     /// before sending the history request.
-    pub fn history(&self) -> PaginatedRequestBuilder<Message> {
+    pub fn history(&self) -> PaginatedRequestBuilder<'a, Message> {
         self.rest.paginated_request_with_options(
             http::Method::GET,
             &format!("/channels/{}/history", self.name),
-            self.opts.clone(),
+            Some(self.options()),
         )
     }
+
+    /// Publish a message with the given name and data serialized as JSON.
+    ///
+    /// This is a shorthand for `channel.publish().name(name).json(data).send()`.
+    /// The data can be decoded by subscribers with Message::decode_json.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> ably::Result<()> {
+    /// # let transport = ably::mock::MockTransport::new().on(ably::http::Method::POST, "/channels/rust-example/messages", 201, serde_json::json!({}));
+    /// # let client = ably::Rest::with_http_executor(ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc"), transport)?;
+    /// #[derive(serde::Serialize)]
+    /// struct Score {
+    ///     home: u32,
+    ///     away: u32,
+    /// }
+    ///
+    /// let channel = client.channels().get("rust-example");
+    /// channel.publish_json("score", &Score { home: 2, away: 1 }).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn publish_json<T: Serialize + ?Sized>(
+        &self,
+        name: impl Into<String>,
+        data: &T,
+    ) -> Result<()> {
+        self.publish().name(name).json(data).send().await
+    }
+
+    /// Publish each of the given messages in a separate request, sending up
+    /// to 10 requests concurrently, and return the result of each publish in
+    /// the same order as the messages.
+    ///
+    /// Unlike a batch publish, a failure to publish one message does not
+    /// prevent the others from being published.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> ably::Result<()> {
+    /// use ably::rest::Message;
+    ///
+    /// # let transport = ably::mock::MockTransport::new().on(ably::http::Method::POST, "/channels/rust-example/messages", 201, serde_json::json!({}));
+    /// # let client = ably::Rest::with_http_executor(ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc"), transport)?;
+    /// let channel = client.channels().get("rust-example");
+    ///
+    /// let messages = (0..5)
+    ///     .map(|i| Message {
+    ///         data: format!("message {}", i).into(),
+    ///         ..Default::default()
+    ///     })
+    ///     .collect();
+    ///
+    /// let results = channel.publish_all(messages).await;
+    /// assert!(results.iter().all(|res| res.is_ok()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn publish_all(&self, messages: Vec<Message>) -> Vec<Result<()>> {
+        stream::iter(messages)
+            .map(|msg| self.publish().message(msg).send())
+            .buffered(PUBLISH_ALL_CONCURRENCY)
+            .collect()
+            .await
+    }
+
+    /// Check that the given messages can be published to the channel in a
+    /// single request without exceeding the client's Limits, without
+    /// publishing them.
+    ///
+    /// The messages are encoded and encrypted in the same way as when they
+    /// are published, so the check includes the overhead of encryption.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ably::rest::Message;
+    ///
+    /// let client = ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc")
+    ///     .max_message_size(16)
+    ///     .rest()?;
+    /// let channel = client.channels().get("rust-example");
+    ///
+    /// let msg = Message {
+    ///     data: "this message is too large".into(),
+    ///     ..Default::default()
+    /// };
+    /// let err = channel.preflight(&[msg]).unwrap_err();
+    /// assert_eq!(err.code, ably::error::ErrorCode::MaximumMessageLengthExceeded);
+    /// # Ok::<(), ably::Error>(())
+    /// ```
+    pub fn preflight(&self, messages: &[Message]) -> Result<()> {
+        let format = self.rest.inner.opts.format;
+        let messages = self.encode_messages(messages.to_vec(), &self.options())?;
+
+        self.rest.limits().check(&messages, format)
+    }
+
+    /// Publish the given messages to the channel in a single request, so
+    /// that they are published in one round trip and either all succeed or
+    /// all fail.
+    ///
+    /// Each message is encoded and encrypted as if published individually,
+    /// and the messages are checked against the client's Limits before the
+    /// request is sent. If idempotent_rest_publishing is enabled and none of
+    /// the messages have an ID, they are given IDs sharing a base, with the
+    /// index of each message as the serial (RSL1k1).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> ably::Result<()> {
+    /// use ably::rest::Message;
+    ///
+    /// # let transport = ably::mock::MockTransport::new().on(ably::http::Method::POST, "/channels/rust-example/messages", 201, serde_json::json!({}));
+    /// # let client = ably::Rest::with_http_executor(ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc"), transport)?;
+    /// let channel = client.channels().get("rust-example");
+    ///
+    /// let messages = (0..5)
+    ///     .map(|i| Message {
+    ///         name: Some("score".to_string()),
+    ///         data: format!("update {}", i).into(),
+    ///         ..Default::default()
+    ///     })
+    ///     .collect();
+    ///
+    /// channel.publish_batch(messages).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails with a MaximumMessageLengthExceeded error without sending the
+    /// request if any message or the request body is too large.
+    pub async fn publish_batch(&self, mut messages: Vec<Message>) -> Result<()> {
+        if messages.is_empty() {
+            return Ok(());
+        }
+
+        if self.rest.inner.opts.idempotent_rest_publishing {
+            Rest::assign_message_ids(&mut messages);
+        }
+
+        let format = self.rest.inner.opts.format;
+        let opts = self.options();
+        let messages = self.encode_messages(messages, &opts)?;
+        self.rest.limits().check(&messages, format)?;
+
+        let mut req = self.rest.request(
+            http::Method::POST,
+            &format!("/channels/{}/messages", self.name),
+        );
+        if !opts.params.is_empty() {
+            req = req.params(&opts.params);
+        }
+        req.body(&messages).send().await.map(|_| ())
+    }
+
+    /// Encode the given messages as they are published with the given
+    /// channel options.
+    fn encode_messages(
+        &self,
+        messages: Vec<Message>,
+        opts: &ChannelOptions,
+    ) -> Result<Vec<Message>> {
+        let format = self.rest.inner.opts.format;
+        messages
+            .into_iter()
+            .map(|mut msg| {
+                msg.encode(&format, opts.cipher.as_deref())?;
+                Ok(msg)
+            })
+            .collect()
+    }
+
+    /// Retrieve the channel's details, including its current occupancy.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> ably::Result<()> {
+    /// # let details = serde_json::json!({"channelId": "rust-example", "status": {"isActive": true, "occupancy": {"metrics": {"subscribers": 3}}}});
+    /// # let transport = ably::mock::MockTransport::new().on(ably::http::Method::GET, "/channels/rust-example", 200, details);
+    /// # let client = ably::Rest::with_http_executor(ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc"), transport)?;
+    /// let channel = client.channels().get("rust-example");
+    ///
+    /// let details = channel.status().await?;
+    /// assert_eq!(details.status.occupancy.metrics.subscribers, 3);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn status(&self) -> Result<metadata::ChannelDetails> {
+        self.rest.channels_metadata().get(&self.name).await
+    }
+
+    /// Returns a stream of the channel's details, retrieved immediately and
+    /// then every interval, for tracking occupancy without a realtime
+    /// connection.
+    ///
+    /// The stream never ends, so should be dropped when no longer needed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # async fn run() -> ably::Result<()> {
+    /// use futures::TryStreamExt;
+    /// use std::time::Duration;
+    ///
+    /// let client = ably::Rest::from("<api_key>");
+    /// let channel = client.channels().get("rust-example");
+    ///
+    /// let mut poll = Box::pin(channel.occupancy_poll(Duration::from_secs(10)));
+    /// while let Some(details) = poll.try_next().await? {
+    ///     println!("{} subscribers", details.status.occupancy.metrics.subscribers);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn occupancy_poll(
+        &self,
+        interval: Duration,
+    ) -> impl Stream<Item = Result<metadata::ChannelDetails>> + 'a {
+        let rest = self.rest;
+        let name = self.name.clone();
+
+        stream::unfold(true, move |first| {
+            let name = name.clone();
+            async move {
+                if !first {
+                    tokio::time::sleep(interval).await;
+                }
+                Some((rest.channels_metadata().get(&name).await, false))
+            }
+        })
+    }
+
+    /// Update a previously published message, replacing its name, data and
+    /// extras with those of the given message.
+    ///
+    /// The message is identified by its serial, so is typically one
+    /// retrieved from the channel's history. Requires protocol version 2.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> ably::Result<()> {
+    /// # let transport = ably::mock::MockTransport::new()
+    /// #     .on(ably::http::Method::GET, "/channels/rust-example/history", 200, serde_json::json!([{"serial": "01:0", "action": 0, "data": "helo"}]))
+    /// #     .on(ably::http::Method::PATCH, "/channels/rust-example/messages/01:0", 200, serde_json::json!({}));
+    /// # let opts = ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc").protocol(ably::Protocol::V2);
+    /// # let client = ably::Rest::with_http_executor(opts, transport)?;
+    /// let channel = client.channels().get("rust-example");
+    ///
+    /// let mut msg = channel.history().send().await?.items().await?.remove(0);
+    /// msg.data = "hello".into();
+    ///
+    /// channel.update_message(msg).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn update_message(&self, msg: Message) -> Result<()> {
+        self.send_message_action(msg, MessageAction::Update).await
+    }
+
+    /// Delete a previously published message, identified by its serial.
+    ///
+    /// Any name, data or extras set on the message are stored with the
+    /// deleted version. Requires protocol version 2.
+    pub async fn delete_message(&self, msg: Message) -> Result<()> {
+        self.send_message_action(msg, MessageAction::Delete).await
+    }
+
+    async fn send_message_action(&self, mut msg: Message, action: MessageAction) -> Result<()> {
+        self.rest
+            .inner
+            .opts
+            .protocol
+            .require(crate::protocol::Protocol::V2, "message updates")?;
+
+        let serial = msg.serial.clone().ok_or_else(|| {
+            Error::new(
+                ErrorCode::InvalidParameterValue,
+                "message serial is required to update or delete a message",
+            )
+        })?;
+
+        let (method, path) = match action {
+            MessageAction::Delete => (
+                http::Method::POST,
+                format!("/channels/{}/messages/{}/delete", self.name, serial),
+            ),
+            _ => (
+                http::Method::PATCH,
+                format!("/channels/{}/messages/{}", self.name, serial),
+            ),
+        };
+
+        msg.action = Some(action);
+        let opts = self.options();
+        msg.encode(&self.rest.inner.opts.format, opts.cipher.as_deref())?;
+
+        self.rest
+            .request(method, &path)
+            .body(&msg)
+            .send()
+            .await
+            .map(|_| ())
+    }
 }
 
 pub struct Presence<'a> {
     rest: &'a Rest,
     name: String,
-    opts: Option<ChannelOptions>,
+    state: Arc<ChannelState>,
 }
 
 impl<'a> Presence<'a> {
-    fn new(rest: &'a Rest, name: String, opts: Option<ChannelOptions>) -> Self {
-        Self { rest, name, opts }
+    fn new(rest: &'a Rest, name: String, state: Arc<ChannelState>) -> Self {
+        Self { rest, name, state }
     }
 
-    /// Start building a presence request for the channel.
+    /// Start building a request for the members currently present on the
+    /// channel, which are returned in pages of up to 100 members unless a
+    /// limit is set.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> ably::Result<()> {
+    /// use futures::TryStreamExt;
+    ///
+    /// # let members = serde_json::json!([{"action": 1, "clientId": "client-1", "connectionId": "conn-1"}]);
+    /// # let transport = ably::mock::MockTransport::new().on(ably::http::Method::GET, "/channels/rust-example/presence", 200, members);
+    /// # let client = ably::Rest::with_http_executor(ably::ClientOptions::new("aaaaaa.bbbbbb:cccccc"), transport)?;
+    /// let channel = client.channels().get("rust-example");
+    ///
+    /// let members: Vec<_> = channel
+    ///     .presence
+    ///     .get()
+    ///     .client_id("client-1")
+    ///     .items()
+    ///     .try_collect()
+    ///     .await?;
+    ///
+    /// assert_eq!(members[0].connection_id, "conn-1");
+    /// # Ok(())
+    /// # }
+    /// ```
     pub fn get(&self) -> presence::RequestBuilder {
         let req = self.rest.paginated_request_with_options(
             http::Method::GET,
             &format!("/channels/{}/presence", self.name),
-            self.opts.clone(),
+            Some(self.state.options()),
         );
         presence::RequestBuilder::new(req)
     }
@@ -481,7 +1689,7 @@ impl<'a> Presence<'a> {
         self.rest.paginated_request_with_options(
             http::Method::GET,
             &format!("/channels/{}/presence/history", self.name),
-            self.opts.clone(),
+            Some(self.state.options()),
         )
     }
 }
@@ -491,7 +1699,11 @@ pub struct PublishBuilder<'a> {
     req: http::RequestBuilder<'a>,
     msg: Result<Message>,
     format: Format,
-    cipher: Option<CipherParams>,
+    cipher: Option<Arc<dyn Cipher>>,
+    compress: bool,
+    ephemeral: bool,
+    idempotent: bool,
+    limits: Limits,
 }
 
 impl<'a> PublishBuilder<'a> {
@@ -506,9 +1718,19 @@ impl<'a> PublishBuilder<'a> {
             msg: Ok(Message::default()),
             format: rest.inner.opts.format,
             cipher: None,
+            compress: false,
+            ephemeral: false,
+            idempotent: rest.inner.opts.idempotent_rest_publishing,
+            limits: rest.limits(),
         }
     }
 
+    /// Set the message to publish, replacing any fields set so far.
+    pub(crate) fn message(mut self, msg: Message) -> Self {
+        self.msg = Ok(msg);
+        self
+    }
+
     /// Set the message ID.
     pub fn id(mut self, id: impl Into<String>) -> Self {
         if let Ok(msg) = self.msg.as_mut() {
@@ -565,23 +1787,82 @@ impl<'a> PublishBuilder<'a> {
         self
     }
 
-    /// Set the message extras.
-    pub fn extras(mut self, extras: json::Map) -> Self {
+    /// Set the message extras, either as Extras or as a raw JSON object.
+    pub fn extras(mut self, extras: impl Into<Extras>) -> Self {
         if let Ok(msg) = self.msg.as_mut() {
-            msg.extras = Some(extras);
+            msg.extras = Some(extras.into().into());
         }
         self
     }
 
-    /// Set the params to include in the publish request.
+    /// Set the message timestamp, rather than letting Ably set it to the time
+    /// the message is received, for example when importing historical data.
+    ///
+    /// The timestamp must not be in the future, and Ably may still reject it
+    /// depending on the app configuration.
+    pub fn timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        if let Ok(msg) = self.msg.as_mut() {
+            if timestamp > Utc::now() {
+                self.msg = Err(Error::new(
+                    ErrorCode::InvalidParameterValue,
+                    format!("message timestamp {} is in the future", timestamp),
+                ));
+            } else {
+                msg.timestamp = Some(timestamp);
+            }
+        }
+        self
+    }
+
+    /// Set the params to include in the query string of the publish request,
+    /// in addition to any params set in the channel's options.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # async fn run() -> ably::Result<()> {
+    /// # let client = ably::Rest::new("aaaaaa.bbbbbb:cccccc")?;
+    /// client
+    ///     .channels()
+    ///     .get("test")
+    ///     .publish()
+    ///     .string("a string")
+    ///     .params(&[("quickAck", "true")])
+    ///     .send()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
     pub fn params<T: Serialize + ?Sized>(mut self, params: &T) -> Self {
         self.req = self.req.params(params);
         self
     }
 
     /// Set the cipher to use to encrypt the message.
-    pub fn cipher(mut self, cipher: CipherParams) -> Self {
-        self.cipher = Some(cipher);
+    pub fn cipher(mut self, cipher: impl Cipher + 'static) -> Self {
+        self.cipher = Some(Arc::new(cipher));
+        self
+    }
+
+    /// Gzip the message data before publishing it, marking it as compressed
+    /// with a content-encoding entry in extras.headers so that it is
+    /// automatically decompressed when retrieved by this library.
+    ///
+    /// The data is compressed before it is encrypted. Readers only
+    /// decompress data up to ChannelOptions::max_decompressed_size.
+    pub fn compress(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    /// Publish the message as an ephemeral message, which is delivered to
+    /// subscribers but not persisted in the channel's history, for example
+    /// for high frequency telemetry.
+    ///
+    /// This sets the ephemeral flag in the message extras, and is applied
+    /// after any extras set with the extras method.
+    pub fn ephemeral(mut self, ephemeral: bool) -> Self {
+        self.ephemeral = ephemeral;
         self
     }
 
@@ -589,7 +1870,23 @@ impl<'a> PublishBuilder<'a> {
     pub async fn send(self) -> Result<()> {
         let mut msg = self.msg?;
 
-        msg.encode(&self.format, self.cipher.as_ref())?;
+        // Give the message an ID so that Ably deduplicates it if the request
+        // is retried against a fallback host after it was published. The
+        // retries send the same request body, so they share the ID.
+        if self.idempotent {
+            Rest::assign_message_ids(std::slice::from_mut(&mut msg));
+        }
+
+        if self.ephemeral {
+            msg.set_ephemeral(true);
+        }
+
+        if self.compress {
+            compress::compress(&mut msg)?;
+        }
+
+        msg.encode(&self.format, self.cipher.as_deref())?;
+        self.limits.check(std::slice::from_ref(&msg), self.format)?;
 
         self.req.body(&msg).send().await.map(|_| ())
     }
@@ -597,19 +1894,53 @@ impl<'a> PublishBuilder<'a> {
 
 /// Data is the payload of a message which can either be a utf-8 encoded
 /// string, a JSON serializable object, or a binary array.
-#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
-#[serde(untagged)]
+///
+/// When a message is published, JSON data is sent as a string with a `json`
+/// encoding, and binary data is sent natively with MessagePack or as a base64
+/// string with a `base64` encoding with JSON. These encodings are reversed
+/// when a message is received, so the Data of a received message has the
+/// same variant as the Data it was published with.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Data {
+    /// A utf-8 encoded string.
     String(String),
+    /// A JSON value, such as an object or array.
     JSON(serde_json::Value),
+    /// An array of bytes.
     Binary(serde_bytes::ByteBuf),
+    /// No data.
     None,
 }
 
 impl Data {
-    fn is_none(&self) -> bool {
+    /// Returns whether there is no data.
+    pub fn is_none(&self) -> bool {
         matches!(self, Self::None)
     }
+
+    /// Returns the data if it is a string, otherwise None.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the data if it is JSON, otherwise None.
+    pub fn as_json(&self) -> Option<&serde_json::Value> {
+        match self {
+            Self::JSON(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the data if it is binary, otherwise None.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Self::Binary(v) => Some(v),
+            _ => None,
+        }
+    }
 }
 
 impl Serialize for Data {
@@ -627,6 +1958,100 @@ impl Serialize for Data {
     }
 }
 
+/// Deserialize strings as Data::String, binary data as Data::Binary, and any
+/// other value as Data::JSON.
+///
+/// Values which can't be represented in JSON, such as MessagePack timestamps,
+/// are mapped into Data::JSON by json::ValueVisitor rather than failing.
+impl<'de> Deserialize<'de> for Data {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(DataVisitor)
+    }
+}
+
+struct DataVisitor;
+
+impl<'de> Visitor<'de> for DataVisitor {
+    type Value = Data;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a string, binary data, or a JSON value")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> ::std::result::Result<Data, E> {
+        Ok(Data::String(v.to_string()))
+    }
+
+    fn visit_string<E: serde::de::Error>(self, v: String) -> ::std::result::Result<Data, E> {
+        Ok(Data::String(v))
+    }
+
+    fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> ::std::result::Result<Data, E> {
+        Ok(Data::from(v))
+    }
+
+    fn visit_byte_buf<E: serde::de::Error>(self, v: Vec<u8>) -> ::std::result::Result<Data, E> {
+        Ok(Data::from(v))
+    }
+
+    fn visit_bool<E: serde::de::Error>(self, v: bool) -> ::std::result::Result<Data, E> {
+        json::ValueVisitor.visit_bool(v).map(Data::JSON)
+    }
+
+    fn visit_i64<E: serde::de::Error>(self, v: i64) -> ::std::result::Result<Data, E> {
+        json::ValueVisitor.visit_i64(v).map(Data::JSON)
+    }
+
+    fn visit_u64<E: serde::de::Error>(self, v: u64) -> ::std::result::Result<Data, E> {
+        json::ValueVisitor.visit_u64(v).map(Data::JSON)
+    }
+
+    fn visit_f64<E: serde::de::Error>(self, v: f64) -> ::std::result::Result<Data, E> {
+        json::ValueVisitor.visit_f64(v).map(Data::JSON)
+    }
+
+    fn visit_none<E: serde::de::Error>(self) -> ::std::result::Result<Data, E> {
+        json::ValueVisitor.visit_none().map(Data::JSON)
+    }
+
+    fn visit_unit<E: serde::de::Error>(self) -> ::std::result::Result<Data, E> {
+        json::ValueVisitor.visit_unit().map(Data::JSON)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> ::std::result::Result<Data, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> ::std::result::Result<Data, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        json::ValueVisitor
+            .visit_newtype_struct(deserializer)
+            .map(Data::JSON)
+    }
+
+    fn visit_seq<A>(self, seq: A) -> ::std::result::Result<Data, A::Error>
+    where
+        A: serde::de::SeqAccess<'de>,
+    {
+        json::ValueVisitor.visit_seq(seq).map(Data::JSON)
+    }
+
+    fn visit_map<A>(self, map: A) -> ::std::result::Result<Data, A::Error>
+    where
+        A: serde::de::MapAccess<'de>,
+    {
+        json::ValueVisitor.visit_map(map).map(Data::JSON)
+    }
+}
+
 impl Default for Data {
     fn default() -> Self {
         Self::None
@@ -665,7 +2090,7 @@ impl From<serde_json::Value> for Data {
 
 /// The encoding of a message, which is either unset or is a list of data
 /// encodings separated by the '/' character.
-#[derive(Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
 #[serde(untagged)]
 pub enum Encoding {
     None,
@@ -673,15 +2098,23 @@ pub enum Encoding {
 }
 
 impl Encoding {
-    fn is_none(&self) -> bool {
+    pub(crate) fn is_none(&self) -> bool {
         match self {
             Self::None => true,
             Self::Some(_) => false,
         }
     }
 
+    /// Returns whether the last encoding in the list is base64.
+    fn is_base64(&self) -> bool {
+        match self {
+            Self::Some(s) => s.rsplit('/').next() == Some("base64"),
+            Self::None => false,
+        }
+    }
+
     /// Append the given encoding to the current list of encodings.
-    fn push(&mut self, value: impl Into<String>) {
+    pub(crate) fn push(&mut self, value: impl Into<String>) {
         *self = Self::Some(match self {
             Self::None => value.into(),
             Self::Some(s) => format!("{}/{}", s, value.into()),
@@ -690,7 +2123,7 @@ impl Encoding {
 
     /// Pop the last encoding from the list of encodings, leaving the list
     /// unset if the popped encoding was the only one in the list.
-    fn pop(&mut self) -> Option<String> {
+    pub(crate) fn pop(&mut self) -> Option<String> {
         let mut encodings = match self {
             Self::Some(s) => s.split('/').collect::<Vec<&str>>(),
             Self::None => return None,
@@ -711,14 +2144,106 @@ impl Default for Encoding {
     }
 }
 
+/// The extras of a message, which carry metadata alongside its data and
+/// instruct Ably how to deliver it (TM2i).
+///
+/// The push and headers extras are typed, and any others are kept in other
+/// so that they are published and retrieved as is. Extras which don't match
+/// their expected type are also kept in other rather than being rejected, so
+/// that messages published by other clients can always be read.
+///
+/// # Example
+///
+/// ```
+/// use ably::push::{PushNotification, PushPayload};
+/// use ably::rest::{Extras, Message};
+///
+/// let mut extras = Extras::default();
+/// extras.push = Some(PushPayload {
+///     notification: Some(PushNotification {
+///         title: Some("Goal!".to_string()),
+///         body: Some("2-1".to_string()),
+///         ..Default::default()
+///     }),
+///     ..Default::default()
+/// });
+/// extras.headers = Some([("match".to_string(), "1".into())].into_iter().collect());
+///
+/// let msg = Message {
+///     data: "2-1".into(),
+///     extras: Some(extras.into()),
+///     ..Default::default()
+/// };
+/// assert_eq!(msg.typed_extras().headers.unwrap()["match"], "1");
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(from = "json::Map", into = "json::Map")]
+pub struct Extras {
+    /// A push notification to deliver to devices subscribed to the channel.
+    pub push: Option<push::PushPayload>,
+    /// Arbitrary metadata, which Ably can use to filter messages.
+    pub headers: Option<json::Map>,
+    /// Whether the message is ephemeral, meaning it is not persisted in the
+    /// channel's history.
+    pub ephemeral: bool,
+    /// Any other extras.
+    pub other: json::Map,
+}
+
+impl Extras {
+    /// Returns whether no extras are set.
+    pub fn is_empty(&self) -> bool {
+        self.push.is_none() && self.headers.is_none() && !self.ephemeral && self.other.is_empty()
+    }
+}
+
+impl From<json::Map> for Extras {
+    /// Parse Extras from a JSON object, keeping any push, headers or
+    /// ephemeral extras which don't match their expected type in other.
+    fn from(mut map: json::Map) -> Self {
+        fn take<T: serde::de::DeserializeOwned>(map: &mut json::Map, key: &str) -> Option<T> {
+            let value = serde_json::from_value(map.get(key)?.clone()).ok()?;
+            map.remove(key);
+            Some(value)
+        }
+
+        Self {
+            push: take(&mut map, "push"),
+            headers: take(&mut map, "headers"),
+            ephemeral: take(&mut map, EPHEMERAL_EXTRA).unwrap_or(false),
+            other: map,
+        }
+    }
+}
+
+impl From<Extras> for json::Map {
+    fn from(extras: Extras) -> Self {
+        let mut map = extras.other;
+        if let Some(push) = extras.push {
+            map.insert("push".to_string(), serde_json::json!(push));
+        }
+        if let Some(headers) = extras.headers {
+            map.insert("headers".to_string(), serde_json::json!(headers));
+        }
+        if extras.ephemeral {
+            map.insert(EPHEMERAL_EXTRA.to_string(), json::Value::Bool(true));
+        }
+        map
+    }
+}
+
+/// The extras key which marks a message as ephemeral.
+const EPHEMERAL_EXTRA: &str = "ephemeral";
+
 /// A message which is published to a channel or returned by a history request.
-#[derive(Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Message {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
-    #[serde(skip_serializing_if = "Data::is_none")]
+    #[serde(default, skip_serializing_if = "Data::is_none")]
     pub data: Data,
     #[serde(default, skip_serializing_if = "Encoding::is_none")]
     pub encoding: Encoding,
@@ -728,189 +2253,167 @@ pub struct Message {
     pub connection_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub extras: Option<json::Map>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "chrono::serde::ts_milliseconds_option"
+    )]
+    pub timestamp: Option<DateTime<Utc>>,
+    /// The action which created this version of the message, only set when
+    /// using protocol version 2.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<MessageAction>,
+    /// The serial which uniquely identifies the message within its channel,
+    /// only set when using protocol version 2.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serial: Option<String>,
+    /// The version of the message, which changes each time the message is
+    /// updated or deleted, only set when using protocol version 2.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
 }
 
-impl Message {
-    /// Initialize a Message from the given JSON serialized data.
-    pub fn from_encoded(v: json::Value, opts: Option<&ChannelOptions>) -> Result<Message> {
-        let mut msg: Message = serde_json::from_value(v)?;
-
-        // TODO fix unneeded conversion
-        Message::decode(&mut msg, &opts.cloned());
-
-        Ok(msg)
-    }
+/// The action which created a version of a message.
+///
+/// Actions added by future protocol versions deserialize as Unknown rather
+/// than failing.
+#[derive(Clone, Copy, Debug, Deserialize_repr, PartialEq, Eq, Serialize_repr)]
+#[repr(u8)]
+pub enum MessageAction {
+    Create = 0,
+    Update = 1,
+    Delete = 2,
+    Meta = 3,
+    Summary = 4,
+    #[serde(other)]
+    Unknown = u8::MAX,
+}
 
-    /// Encode the message ready to be sent in the body of a HTTP request.
-    ///
-    /// If the cipher is set, then use it to encrypt the message.
-    pub fn encode(&mut self, format: &Format, cipher: Option<&CipherParams>) -> Result<()> {
-        self.encode_with_iv(format, cipher, None)
+impl Message {
+    /// Returns whether the message is ephemeral, meaning it is not persisted
+    /// in the channel's history.
+    pub fn is_ephemeral(&self) -> bool {
+        matches!(
+            self.extras
+                .as_ref()
+                .and_then(|extras| extras.get(EPHEMERAL_EXTRA)),
+            Some(json::Value::Bool(true))
+        )
     }
 
-    pub(crate) fn encode_with_iv(
-        &mut self,
-        format: &Format,
-        cipher: Option<&CipherParams>,
-        iv: Option<Vec<u8>>,
-    ) -> Result<()> {
-        match &self.data {
-            Data::String(data) => {
-                if let Some(cipher) = cipher {
-                    let data = data.as_bytes();
-                    self.data = cipher.encrypt(iv, data)?.into();
-                    self.encoding.push("utf-8");
-                    self.encoding.push(cipher.encoding());
-                }
-            }
-            Data::Binary(data) => {
-                if let Some(cipher) = cipher {
-                    self.data = cipher.encrypt(iv, data)?.into();
-                    self.encoding.push(cipher.encoding());
-                }
+    /// Set whether the message is ephemeral by setting or removing the
+    /// ephemeral flag in the message extras, so that the flag is carried
+    /// through when the message is published with publish_all.
+    pub fn set_ephemeral(&mut self, ephemeral: bool) {
+        if ephemeral {
+            self.extras
+                .get_or_insert_with(Default::default)
+                .insert(EPHEMERAL_EXTRA.to_string(), json::Value::Bool(true));
+        } else if let Some(extras) = self.extras.as_mut() {
+            extras.remove(EPHEMERAL_EXTRA);
+            if extras.is_empty() {
+                self.extras = None;
             }
-            Data::JSON(data) => {
-                let json_str = serde_json::to_string(data)?;
-
-                if let Some(cipher) = cipher {
-                    let data = json_str.as_bytes();
-                    self.data = cipher.encrypt(iv, data)?.into();
-                    self.encoding.push("json");
-                    self.encoding.push("utf-8");
-                    self.encoding.push(cipher.encoding());
-                } else {
-                    self.data = json_str.into();
-                    self.encoding.push("json");
-                }
-            }
-            Data::None => (),
         }
-
-        // If we have binary data but JSON format, base64 encode the data.
-        if let Data::Binary(data) = &self.data {
-            if format.is_json() {
-                self.data = base64::encode(data).into();
-                self.encoding.push("base64");
-            }
-        };
-
-        Ok(())
     }
-}
 
-#[derive(Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct PresenceMessage {
-    pub action: PresenceAction,
-    pub client_id: String,
-    pub connection_id: String,
-    #[serde(skip_serializing_if = "Data::is_none")]
-    pub data: Data,
-    #[serde(default, skip_serializing_if = "Encoding::is_none")]
-    pub encoding: Encoding,
-}
+    /// Returns the message extras parsed as Extras, which are empty if the
+    /// message has none.
+    pub fn typed_extras(&self) -> Extras {
+        self.extras.clone().map(Extras::from).unwrap_or_default()
+    }
 
-/// Iteratively decode the given data based on the given list of encodings.
-fn decode(data: &mut Data, encoding: &mut Encoding, opts: Option<&ChannelOptions>) {
-    while let Some(enc) = encoding.pop() {
-        *data = match decode_once(data, &enc, opts) {
-            Ok(data) => data,
-            Err(_) => {
-                encoding.push(enc);
-                return;
+    /// Deserialize the message data into the given type.
+    ///
+    /// The data is expected to be JSON, such as data published with
+    /// PublishBuilder::json or Channel::publish_json, or a string containing
+    /// serialized JSON published by a client which doesn't set the `json`
+    /// encoding.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ably::rest::Message;
+    ///
+    /// #[derive(serde::Deserialize)]
+    /// struct Score {
+    ///     home: u32,
+    ///     away: u32,
+    /// }
+    ///
+    /// let msg = Message {
+    ///     data: serde_json::json!({"home": 2, "away": 1}).into(),
+    ///     ..Default::default()
+    /// };
+    /// let score: Score = msg.decode_json()?;
+    /// assert_eq!((score.home, score.away), (2, 1));
+    /// # Ok::<(), ably::Error>(())
+    /// ```
+    pub fn decode_json<T: DeserializeOwned>(&self) -> Result<T> {
+        let res = match &self.data {
+            Data::JSON(v) => T::deserialize(v),
+            Data::String(s) => serde_json::from_str(s),
+            _ => {
+                return Err(Error::new(
+                    ErrorCode::InvalidMessageDataOrEncoding,
+                    "message data is not JSON",
+                ))
             }
-        }
+        };
+        res.map_err(|err| {
+            Error::with_cause(
+                ErrorCode::InvalidMessageDataOrEncoding,
+                err,
+                "unable to deserialize message data",
+            )
+        })
     }
-}
 
-lazy_static! {
-    /// A regular expression to split a data encoding into its format and params.
-    static ref ENCODING_RE: Regex =
-        Regex::new(r#"^(?P<format>[\-\w]+)(?:\+(?P<params>[\-\w]+))?"#).unwrap();
-}
+    /// Initialize a Message from the given JSON serialized data.
+    pub fn from_encoded(v: json::Value, opts: Option<&ChannelOptions>) -> Result<Message> {
+        let mut msg: Message = serde_json::from_value(v)?;
 
-fn decode_once(data: &mut Data, encoding: &str, opts: Option<&ChannelOptions>) -> Result<Data> {
-    let caps = ENCODING_RE
-        .captures(encoding)
-        .ok_or_else(|| Error::new(ErrorCode::InvalidHeader, "Invalid encoding"))?;
-    let format = caps
-        .name("format")
-        .ok_or_else(|| Error::new(ErrorCode::InvalidHeader, "Invalid encoding; missing format"))?
-        .as_str();
+        // TODO fix unneeded conversion
+        Message::decode(&mut msg, &opts.cloned())?;
 
-    match format {
-        "utf-8" => match data {
-            Data::String(s) => Ok(Data::String(s.to_string())),
-            Data::Binary(data) => std::str::from_utf8(data)
-                .map(Into::into)
-                .map_err(Into::into),
-            _ => Err(Error::new(
-                ErrorCode::InvalidMessageDataOrEncoding,
-                "invalid utf-8 message data",
-            )),
-        },
-        "json" => match data {
-            Data::String(s) => serde_json::from_str::<serde_json::Value>(s)
-                .map(Into::into)
-                .map_err(Into::into),
-            _ => Err(Error::new(
-                ErrorCode::InvalidMessageDataOrEncoding,
-                "invalid JSON message data",
-            )),
-        },
-        "base64" => match data {
-            Data::String(s) => base64::decode(s).map(Into::into).map_err(Into::into),
-            _ => Err(Error::new(
-                ErrorCode::InvalidMessageDataOrEncoding,
-                "invalid base64 message data",
-            )),
-        },
-        "cipher" => match data {
-            Data::Binary(ref mut data) => {
-                let opts = opts.ok_or_else(|| {
-                    Error::new(
-                        ErrorCode::BadRequest,
-                        "unable to decrypt message, no channel options",
-                    )
-                })?;
-                let cipher = opts.cipher.as_ref().ok_or_else(|| {
-                    Error::new(
-                        ErrorCode::BadRequest,
-                        "unable to decrypt message, no cipher params",
-                    )
-                })?;
-                let params = caps.name("params").ok_or_else(|| {
-                    Error::new(ErrorCode::InvalidHeader, "Invalid encoding; missing params")
-                })?;
-                if params.as_str() != cipher.algorithm() {
-                    return Err(Error::new(
-                        ErrorCode::BadRequest,
-                        "unable to decrypt message, incompatible cipher params",
-                    ));
-                }
-                cipher.decrypt(data).map(Into::into)
+        Ok(msg)
+    }
+
+    /// Returns the size of the message which counts towards the maximum
+    /// message size, which is the sum of the sizes of its name, client ID,
+    /// extras and data (TO3l8).
+    ///
+    /// The size of encoded data is its size after any encryption, but before
+    /// any base64 encoding.
+    pub fn size(&self) -> usize {
+        let data = match &self.data {
+            Data::String(s) if self.encoding.is_base64() => {
+                let padding = s.bytes().rev().take_while(|&b| b == b'=').count();
+                (s.len() / 4 * 3).saturating_sub(padding)
             }
-            _ => Err(Error::new(
-                ErrorCode::InvalidMessageDataOrEncoding,
-                "invalid cipher message data",
-            )),
-        },
-        _ => Err(Error::new(
-            ErrorCode::InvalidMessageDataOrEncoding,
-            "invalid message encoding",
-        )),
+            Data::String(s) => s.len(),
+            Data::Binary(data) => data.len(),
+            Data::JSON(value) => value.to_string().len(),
+            Data::None => 0,
+        };
+        let extras = self
+            .extras
+            .as_ref()
+            .and_then(|extras| serde_json::to_string(extras).ok())
+            .map_or(0, |extras| extras.len());
+
+        self.name.as_ref().map_or(0, String::len)
+            + self.client_id.as_ref().map_or(0, String::len)
+            + extras
+            + data
     }
-}
 
-#[derive(Clone, Debug, Deserialize_repr, PartialEq, Eq, Serialize_repr)]
-#[serde(untagged)]
-#[repr(u8)]
-pub enum PresenceAction {
-    Absent,
-    Present,
-    Enter,
-    Leave,
-    Update,
+    /// Encode the message ready to be sent in the body of a HTTP request.
+    ///
+    /// If the cipher is set, then use it to encrypt the message.
+    pub fn encode(&mut self, format: &Format, cipher: Option<&dyn Cipher>) -> Result<()> {
+        codec::encode(&mut self.data, &mut self.encoding, format, cipher)
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -920,12 +2423,20 @@ pub enum Format {
 }
 
 impl Format {
-    fn is_json(&self) -> bool {
+    pub(crate) fn is_json(&self) -> bool {
         match self {
             Self::MessagePack => false,
             Self::JSON => true,
         }
     }
+
+    /// Returns the MIME type of request and response bodies in the format.
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::MessagePack => "application/x-msgpack",
+            Self::JSON => "application/json",
+        }
+    }
 }
 
 pub struct DecodeRaw<T>(PhantomData<T>);
@@ -933,35 +2444,40 @@ pub struct DecodeRaw<T>(PhantomData<T>);
 pub trait Decode {
     type Options: Clone + Send;
     type Item: DeserializeOwned + Send + 'static;
-    fn decode(item: &mut Self::Item, options: &Self::Options);
+
+    /// Decode the item, returning an error if it can't be decoded.
+    ///
+    /// An item which can't be fully decoded is left partially decoded, with
+    /// the encodings which couldn't be applied still set (RSL6b).
+    fn decode(item: &mut Self::Item, options: &Self::Options) -> Result<()>;
 }
 
 impl Decode for Message {
     type Options = Option<ChannelOptions>;
     type Item = Self;
 
-    fn decode(item: &mut Self::Item, options: &Self::Options) {
-        crate::rest::decode(&mut item.data, &mut item.encoding, options.as_ref());
+    fn decode(item: &mut Self::Item, options: &Self::Options) -> Result<()> {
+        codec::decode(&mut item.data, &mut item.encoding, options.as_ref())?;
+        let max_size = options
+            .as_ref()
+            .and_then(|opts| opts.max_decompressed_size)
+            .unwrap_or(compress::DEFAULT_MAX_DECOMPRESSED_SIZE);
+        compress::decompress(item, max_size)
     }
 }
 
 impl Decode for Stats {
     type Options = ();
     type Item = Self;
-    fn decode(_item: &mut Self::Item, _options: &Self::Options) {}
-}
-
-impl Decode for PresenceMessage {
-    type Options = Option<ChannelOptions>;
-    type Item = Self;
-
-    fn decode(item: &mut Self::Item, options: &Self::Options) {
-        crate::rest::decode(&mut item.data, &mut item.encoding, options.as_ref());
+    fn decode(_item: &mut Self::Item, _options: &Self::Options) -> Result<()> {
+        Ok(())
     }
 }
 
 impl<T: DeserializeOwned + 'static + Send> Decode for DecodeRaw<T> {
     type Options = ();
     type Item = T;
-    fn decode(_item: &mut Self::Item, _options: &Self::Options) {}
+    fn decode(_item: &mut Self::Item, _options: &Self::Options) -> Result<()> {
+        Ok(())
+    }
 }
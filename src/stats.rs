@@ -1,4 +1,6 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+use crate::http;
 
 /// Ably Application statistics retrieved from [REST stats endpoint].
 ///
@@ -28,7 +30,8 @@ pub struct Stats {
     pub peak_rates: Option<Rates>,
 }
 
-#[derive(Debug, Deserialize)]
+/// The length of the intervals which statistics are aggregated over.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub enum Unit {
     Minute,
@@ -37,6 +40,26 @@ pub enum Unit {
     Month,
 }
 
+impl Unit {
+    /// Returns the unit as it is set in the query string of a stats request.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Minute => "minute",
+            Self::Hour => "hour",
+            Self::Day => "day",
+            Self::Month => "month",
+        }
+    }
+}
+
+impl<'a> http::PaginatedRequestBuilder<'a, Stats> {
+    /// Set the unit of the intervals to aggregate statistics over, which
+    /// defaults to Unit::Minute.
+    pub fn unit(self, unit: Unit) -> Self {
+        self.params(&[("unit", unit.as_str())])
+    }
+}
+
 impl Default for Unit {
     fn default() -> Self {
         Unit::Minute
@@ -0,0 +1,314 @@
+//! Test support for exercising the parts of the library which parse
+//! untrusted network input: Link headers, token requests and their
+//! capabilities, the message encoding chain, and the MessagePack and JSON
+//! codecs.
+//!
+//! Enabled with the `test-support` feature, this module contains [proptest]
+//! strategies for generating valid inputs, and helpers for turning them into
+//! the raw bytes which Ably would send, for example to seed the corpus of
+//! the fuzz targets in the `fuzz` directory.
+//!
+//! # Example
+//!
+//! ```
+//! use ably::rest::Format;
+//! use ably::testing;
+//!
+//! for msg in testing::sample(&testing::message(), 10) {
+//!     let body = testing::encode_message(msg.clone(), Format::MessagePack, None)?;
+//!     let decoded = testing::decode_message(&body, Format::MessagePack, None)?;
+//!     assert_eq!(decoded.data, msg.data);
+//! }
+//! # Ok::<(), ably::Error>(())
+//! ```
+//!
+//! [proptest]: https://docs.rs/proptest
+
+use chrono::{TimeZone, Utc};
+use proptest::collection::{btree_map, vec};
+use proptest::option;
+use proptest::prelude::*;
+use proptest::strategy::ValueTree;
+use proptest::test_runner::TestRunner;
+
+use crate::crypto::{Cipher, CipherParams};
+use crate::http::Link;
+use crate::rest::{ChannelOptions, Data, Decode, Format, Message};
+use crate::{json, Result};
+
+/// Returns count values generated by the given strategy, using a
+/// deterministic RNG so that the same values are returned on every run.
+pub fn sample<S: Strategy>(strategy: &S, count: usize) -> Vec<S::Value> {
+    let mut runner = TestRunner::deterministic();
+    (0..count)
+        .map(|_| {
+            strategy
+                .new_tree(&mut runner)
+                .expect("failed to generate a value")
+                .current()
+        })
+        .collect()
+}
+
+/// Returns a strategy generating links which can be formatted with
+/// link_header and parsed back into the same links.
+pub fn link() -> impl Strategy<Value = Link> {
+    let param = (
+        "[a-z0-9!#$%&'*+.^_`|~-]{1,12}".prop_filter("rel is not a param", |name| name != "rel"),
+        "[^\\x00-\\x1f]{0,16}",
+    );
+
+    ("[^>]{0,64}", vec("[a-z]{1,10}", 0..3), vec(param, 0..3)).prop_map(|(target, rels, params)| {
+        Link {
+            target,
+            rels,
+            params,
+        }
+    })
+}
+
+/// Returns a strategy generating the value of a Link header along with the
+/// links it contains.
+pub fn link_header() -> impl Strategy<Value = (String, Vec<Link>)> {
+    vec(link(), 0..4).prop_map(|links| (format_link_header(&links), links))
+}
+
+/// Format the given links as the value of a Link header, quoting all of the
+/// parameter values.
+pub fn format_link_header(links: &[Link]) -> String {
+    let quote = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+
+    links
+        .iter()
+        .map(|link| {
+            let mut value = format!("<{}>", link.target);
+            if !link.rels.is_empty() {
+                value.push_str(&format!("; rel=\"{}\"", link.rels.join(" ")));
+            }
+            for (name, param) in &link.params {
+                value.push_str(&format!("; {}=\"{}\"", name, quote(param)));
+            }
+            value
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Returns a strategy generating capability strings, which are JSON objects
+/// mapping resource names (which may contain wildcards) to the operations
+/// permitted on them.
+pub fn capability() -> impl Strategy<Value = String> {
+    let resource = prop_oneof![Just("*".to_string()), "[a-z0-9:_-]{1,12}\\*?"];
+    let operation = prop_oneof![
+        Just("*"),
+        Just("publish"),
+        Just("subscribe"),
+        Just("presence"),
+        Just("history"),
+        Just("stats"),
+        Just("channel-metadata"),
+        Just("push-subscribe"),
+        Just("push-admin"),
+    ];
+
+    btree_map(resource, vec(operation, 1..4), 1..4)
+        .prop_map(|capability| serde_json::to_string(&capability).unwrap())
+}
+
+/// Returns a strategy generating JSON values.
+///
+/// Numbers are limited to integers, which round trip exactly through both
+/// JSON and MessagePack.
+pub fn json_value() -> impl Strategy<Value = json::Value> {
+    let leaf = prop_oneof![
+        Just(json::Value::Null),
+        any::<bool>().prop_map(json::Value::Bool),
+        any::<i64>().prop_map(json::Value::from),
+        any::<u64>().prop_map(json::Value::from),
+        ".{0,16}".prop_map(json::Value::String),
+    ];
+
+    leaf.prop_recursive(3, 32, 4, |inner| {
+        prop_oneof![
+            vec(inner.clone(), 0..4).prop_map(json::Value::Array),
+            btree_map(".{0,8}", inner, 0..4)
+                .prop_map(|map| json::Value::Object(map.into_iter().collect())),
+        ]
+    })
+}
+
+/// Returns a strategy generating message data of any type.
+pub fn data() -> impl Strategy<Value = Data> {
+    prop_oneof![
+        Just(Data::None),
+        ".{0,32}".prop_map(Data::String),
+        json_value().prop_map(Data::JSON),
+        vec(any::<u8>(), 0..64).prop_map(|data| Data::Binary(data.into())),
+    ]
+}
+
+/// Returns a strategy generating unencoded messages.
+pub fn message() -> impl Strategy<Value = Message> {
+    (
+        option::of("[a-zA-Z0-9:_-]{1,16}"),
+        option::of(".{0,16}"),
+        data(),
+        option::of("[a-zA-Z0-9_-]{1,16}"),
+        option::of(0i64..4_102_444_800_000),
+    )
+        .prop_map(|(id, name, data, client_id, timestamp)| Message {
+            id,
+            name,
+            data,
+            client_id,
+            timestamp: timestamp.and_then(|ms| Utc.timestamp_millis_opt(ms).single()),
+            ..Default::default()
+        })
+}
+
+/// Returns a strategy generating message encodings, which may list any of
+/// the supported encodings in any order, as well as unsupported ones.
+pub fn encoding() -> impl Strategy<Value = String> {
+    let step = prop_oneof![
+        Just("utf-8".to_string()),
+        Just("json".to_string()),
+        Just("base64".to_string()),
+        Just("cipher+aes-128-cbc".to_string()),
+        Just("cipher+aes-256-cbc".to_string()),
+        "[a-z0-9+-]{1,12}",
+    ];
+
+    vec(step, 1..5).prop_map(|steps| steps.join("/"))
+}
+
+/// Returns a strategy generating AES-128 and AES-256 cipher params.
+pub fn cipher_params() -> impl Strategy<Value = CipherParams> {
+    prop_oneof![vec(any::<u8>(), 16), vec(any::<u8>(), 32)]
+        .prop_map(|key| CipherParams::try_from(key).expect("valid key length"))
+}
+
+/// Encode the message as it would be in the body of a history response in
+/// the given format, encrypting its data with the given cipher.
+pub fn encode_message(
+    mut msg: Message,
+    format: Format,
+    cipher: Option<&dyn Cipher>,
+) -> Result<Vec<u8>> {
+    msg.encode(&format, cipher)?;
+
+    match format {
+        Format::MessagePack => Ok(rmp_serde::to_vec_named(&msg)?),
+        Format::JSON => Ok(serde_json::to_vec(&msg)?),
+    }
+}
+
+/// Decode a message from the body of a response in the given format, in the
+/// same way as the items of a history response, decrypting its data with the
+/// given cipher.
+pub fn decode_message(
+    body: &[u8],
+    format: Format,
+    cipher: Option<CipherParams>,
+) -> Result<Message> {
+    let mut msg: Message = match format {
+        Format::MessagePack => rmp_serde::from_slice(body)?,
+        Format::JSON => serde_json::from_slice(body)?,
+    };
+
+    let opts = cipher.map(|cipher| ChannelOptions::new().cipher(cipher));
+    Message::decode(&mut msg, &opts)?;
+
+    Ok(msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{Key, RequestOrDetails, TokenParams};
+    use crate::rest::Encoding;
+
+    fn assert_messages_eq(decoded: &Message, msg: &Message) {
+        assert_eq!(decoded.id, msg.id);
+        assert_eq!(decoded.name, msg.name);
+        assert_eq!(decoded.data, msg.data);
+        assert_eq!(decoded.encoding, Encoding::None);
+        assert_eq!(decoded.client_id, msg.client_id);
+        assert_eq!(decoded.timestamp, msg.timestamp);
+    }
+
+    proptest! {
+        #[test]
+        fn link_header_round_trips((header, links) in link_header()) {
+            prop_assert_eq!(Link::parse(&header)?, links);
+        }
+
+        #[test]
+        fn link_parse_never_panics(header in "\\PC{0,64}") {
+            let _ = Link::parse(&header);
+        }
+
+        #[test]
+        fn token_request_capability_round_trips(
+            capability in capability(),
+            client_id in option::of("[a-zA-Z0-9_-]{1,16}"),
+        ) {
+            let key = Key::new("aaaaaa.bbbbbb:cccccc")?;
+            let mut params = TokenParams::new().capability(&capability);
+            params.client_id = client_id;
+            let req = params.sign(&key)?;
+
+            let json = serde_json::to_string(&req)?;
+            match serde_json::from_str::<RequestOrDetails>(&json)? {
+                RequestOrDetails::Request(decoded) => {
+                    prop_assert_eq!(&decoded.capability, &capability);
+                    prop_assert_eq!(decoded.mac, req.mac);
+                }
+                RequestOrDetails::Details(_) => prop_assert!(false, "decoded as TokenDetails"),
+            }
+        }
+
+        #[test]
+        fn message_round_trips_json(msg in message()) {
+            let body = encode_message(msg.clone(), Format::JSON, None)?;
+            assert_messages_eq(&decode_message(&body, Format::JSON, None)?, &msg);
+        }
+
+        #[test]
+        fn message_round_trips_msgpack(msg in message()) {
+            let body = encode_message(msg.clone(), Format::MessagePack, None)?;
+            assert_messages_eq(&decode_message(&body, Format::MessagePack, None)?, &msg);
+        }
+
+        #[test]
+        fn encrypted_message_round_trips(
+            msg in message(),
+            cipher in cipher_params(),
+            json in any::<bool>(),
+        ) {
+            let format = if json { Format::JSON } else { Format::MessagePack };
+            let body = encode_message(msg.clone(), format, Some(&cipher))?;
+            assert_messages_eq(&decode_message(&body, format, Some(cipher))?, &msg);
+        }
+
+        #[test]
+        fn decode_arbitrary_encoding_never_panics(
+            data in data(),
+            encoding in encoding(),
+            cipher in option::of(cipher_params()),
+        ) {
+            let mut msg = Message {
+                data,
+                encoding: Encoding::Some(encoding),
+                ..Default::default()
+            };
+            let opts = cipher.map(|cipher| ChannelOptions::new().cipher(cipher));
+            let _ = Message::decode(&mut msg, &opts);
+        }
+
+        #[test]
+        fn decode_arbitrary_bytes_never_panics(body in vec(any::<u8>(), 0..256)) {
+            let _ = decode_message(&body, Format::MessagePack, None);
+            let _ = decode_message(&body, Format::JSON, None);
+        }
+    }
+}